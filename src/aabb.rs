@@ -0,0 +1,70 @@
+use crate::ray;
+use crate::vector3;
+
+#[derive(Copy, Clone)]
+pub struct Aabb {
+    pub minimum: vector3::Point,
+    pub maximum: vector3::Point,
+}
+
+impl Aabb {
+    pub fn new(a: vector3::Point, b: vector3::Point) -> Aabb {
+        Aabb {
+            minimum: a,
+            maximum: b,
+        }
+    }
+
+    pub fn hit(&self, r: &ray::Ray, t_min: f64, t_max: f64) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
+
+    /// The part of `[t_min, t_max]` for which the ray is inside the box.
+    pub fn intersect(&self, r: &ray::Ray, mut t_min: f64, mut t_max: f64) -> Option<(f64, f64)> {
+        for a in 0..3 {
+            let inv_d = 1.0 / r.dir[a];
+            let mut t0 = (self.minimum[a] - r.origin[a]) * inv_d;
+            let mut t1 = (self.maximum[a] - r.origin[a]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max <= t_min {
+                return None;
+            }
+        }
+        Some((t_min, t_max))
+    }
+
+    /// Box enclosing a disk of `radius` centered at `center` and facing `normal`.
+    /// Along each axis the disk extends by `radius * sin(angle between axis and normal)`.
+    pub fn disk(center: vector3::Point, normal: vector3::Vec3, radius: f64) -> Aabb {
+        let n = normal.unit_vector();
+        let e = vector3::Vec3::new(
+            (1.0 - n.x * n.x).max(0.0).sqrt(),
+            (1.0 - n.y * n.y).max(0.0).sqrt(),
+            (1.0 - n.z * n.z).max(0.0).sqrt(),
+        ) * radius;
+        Aabb::new(center - e, center + e)
+    }
+
+    /// Grows the box by `delta` in every direction.
+    pub fn pad(self, delta: f64) -> Aabb {
+        Aabb::new(self.minimum - delta, self.maximum + delta)
+    }
+}
+
+pub fn surrounding_box(box0: Aabb, box1: Aabb) -> Aabb {
+    let small = vector3::Point::new(
+        box0.minimum.x.min(box1.minimum.x),
+        box0.minimum.y.min(box1.minimum.y),
+        box0.minimum.z.min(box1.minimum.z),
+    );
+    let big = vector3::Point::new(
+        box0.maximum.x.max(box1.maximum.x),
+        box0.maximum.y.max(box1.maximum.y),
+        box0.maximum.z.max(box1.maximum.z),
+    );
+    Aabb::new(small, big)
+}
@@ -0,0 +1,384 @@
+use crate::bvh;
+use crate::camera;
+use crate::hittable;
+use crate::transform;
+use crate::vector3;
+use std::ops;
+
+/// How a track moves between its keys.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    Linear,
+    /// Smooth curve through every key, heading at each from the key before it
+    /// toward the key after it
+    CatmullRom,
+    /// Cubic Bezier through every key, steered by the handles given with
+    /// `add_bezier`. Keys added without handles get Catmull-Rom ones.
+    Bezier,
+}
+
+#[derive(Copy, Clone)]
+struct Key<T> {
+    time: f64,
+    value: T,
+    /// Control points before and after the key, for Bezier tracks
+    handles: Option<(T, T)>,
+}
+
+/// A value that changes over time, set at keyframes and interpolated between
+/// them. Before the first key and after the last the value holds still.
+#[derive(Clone)]
+pub struct Track<T> {
+    keys: Vec<Key<T>>,
+    interpolation: Interpolation,
+}
+
+impl<T> Track<T>
+where
+    T: Copy + ops::Add<T, Output = T> + ops::Sub<T, Output = T> + ops::Mul<f64, Output = T>,
+{
+    pub fn new(interpolation: Interpolation, time: f64, value: T) -> Track<T> {
+        let mut track = Track {
+            keys: Vec::new(),
+            interpolation,
+        };
+        track.add(time, value);
+        track
+    }
+
+    /// A track that never changes.
+    pub fn constant(value: T) -> Track<T> {
+        Track::new(Interpolation::Linear, 0.0, value)
+    }
+
+    /// Sets the value at `time`, replacing any key already there.
+    pub fn add(&mut self, time: f64, value: T) {
+        self.insert(Key {
+            time,
+            value,
+            handles: None,
+        });
+    }
+
+    /// Sets the value at `time` along with the Bezier control points the curve
+    /// arrives from and leaves toward.
+    pub fn add_bezier(&mut self, time: f64, value: T, in_handle: T, out_handle: T) {
+        self.insert(Key {
+            time,
+            value,
+            handles: Some((in_handle, out_handle)),
+        });
+    }
+
+    fn insert(&mut self, key: Key<T>) {
+        let i = self.keys.partition_point(|k| k.time < key.time);
+        if self.keys.get(i).is_some_and(|k| k.time == key.time) {
+            self.keys[i] = key;
+        } else {
+            self.keys.insert(i, key);
+        }
+    }
+
+    /// Catmull-Rom control points either side of key `i` for a segment `h` long.
+    fn tangent_handles(&self, i: usize, h: f64) -> (T, T) {
+        let before = self.keys[i.saturating_sub(1)];
+        let after = self.keys[(i + 1).min(self.keys.len() - 1)];
+        let step = (after.value - before.value) * (h / (3.0 * (after.time - before.time)));
+        (self.keys[i].value - step, self.keys[i].value + step)
+    }
+
+    pub fn at(&self, time: f64) -> T {
+        let i = self.keys.partition_point(|k| k.time <= time);
+        if i == 0 {
+            return self.keys[0].value;
+        }
+        if i == self.keys.len() {
+            return self.keys[i - 1].value;
+        }
+        let (a, b) = (self.keys[i - 1], self.keys[i]);
+        let h = b.time - a.time;
+        let s = (time - a.time) / h;
+        let (c1, c2) = match self.interpolation {
+            Interpolation::Linear => return a.value + (b.value - a.value) * s,
+            Interpolation::CatmullRom => (
+                self.tangent_handles(i - 1, h).1,
+                self.tangent_handles(i, h).0,
+            ),
+            Interpolation::Bezier => (
+                a.handles
+                    .map_or_else(|| self.tangent_handles(i - 1, h).1, |(_, out)| out),
+                b.handles
+                    .map_or_else(|| self.tangent_handles(i, h).0, |(inn, _)| inn),
+            ),
+        };
+        let r = 1.0 - s;
+        a.value * (r * r * r)
+            + c1 * (3.0 * r * r * s)
+            + c2 * (3.0 * r * s * s)
+            + b.value * (s * s * s)
+    }
+}
+
+/// A `transform::Transform` with each part on its own track.
+#[derive(Clone)]
+pub struct AnimatedTransform {
+    translation: Track<vector3::Vec3>,
+    rotation: Track<vector3::Vec3>,
+    scale: Track<f64>,
+}
+
+impl AnimatedTransform {
+    pub fn new(
+        translation: Track<vector3::Vec3>,
+        rotation: Track<vector3::Vec3>,
+        scale: Track<f64>,
+    ) -> AnimatedTransform {
+        AnimatedTransform {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    pub fn at(&self, time: f64) -> transform::Transform {
+        transform::Transform::new(
+            self.translation.at(time),
+            self.rotation.at(time),
+            self.scale.at(time),
+        )
+    }
+}
+
+/// A perspective camera whose placement and lens are keyframed.
+#[derive(Clone)]
+pub struct CameraTrack {
+    lookfrom: Track<vector3::Point>,
+    lookat: Track<vector3::Point>,
+    vup: vector3::Vec3,
+    vfov: Track<f64>,
+    aspect: f64,
+    aperture: Track<f64>,
+    /// `None` keeps `lookat` in focus
+    focus_dist: Option<Track<f64>>,
+}
+
+impl CameraTrack {
+    pub fn new(
+        lookfrom: Track<vector3::Point>,
+        lookat: Track<vector3::Point>,
+        vup: vector3::Vec3,
+        vfov: Track<f64>,
+        aspect: f64,
+    ) -> CameraTrack {
+        CameraTrack {
+            lookfrom,
+            lookat,
+            vup,
+            vfov,
+            aspect,
+            aperture: Track::constant(0.0),
+            focus_dist: None,
+        }
+    }
+
+    pub fn set_aperture(&mut self, aperture: Track<f64>) {
+        self.aperture = aperture;
+    }
+
+    pub fn set_focus_dist(&mut self, focus_dist: Track<f64>) {
+        self.focus_dist = Some(focus_dist);
+    }
+
+    pub fn at(&self, time: f64) -> camera::Camera {
+        let lookfrom = self.lookfrom.at(time);
+        let lookat = self.lookat.at(time);
+        let focus_dist = match &self.focus_dist {
+            Some(track) => track.at(time),
+            None => (lookat - lookfrom).length(),
+        };
+        camera::Camera::Perspective(camera::PerspectiveCamera::new(
+            lookfrom,
+            lookat,
+            self.vup,
+            self.vfov.at(time),
+            self.aspect,
+            self.aperture.at(time),
+            focus_dist,
+        ))
+    }
+}
+
+/// What to do with the scene's BVH once objects have moved for a new frame.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum BvhUpdate {
+    /// Grow the existing boxes to fit. Fast, and fine while objects move a little
+    #[default]
+    Refit,
+    /// Build a new tree, for when objects travel far across the scene
+    Rebuild,
+}
+
+/// A scene built once and posed for each frame: static objects, objects moved
+/// by keyframed transforms, and a keyframed camera, all in one BVH.
+pub struct AnimatedScene {
+    world: bvh::Bvh,
+    /// Index into the BVH's objects of each animated instance, with its tracks
+    animated: Vec<(usize, AnimatedTransform)>,
+    camera: CameraTrack,
+    update: BvhUpdate,
+}
+
+impl AnimatedScene {
+    pub fn new(
+        objects: Vec<hittable::HittableObj>,
+        animated: Vec<(hittable::HittableObj, AnimatedTransform)>,
+        camera: CameraTrack,
+    ) -> AnimatedScene {
+        let first = objects.len();
+        let mut all = objects;
+        let mut tracks = Vec::with_capacity(animated.len());
+        for (i, (object, track)) in animated.into_iter().enumerate() {
+            all.push(hittable::HittableObj::Instance(transform::Instance::new(
+                object,
+                track.at(0.0),
+            )));
+            tracks.push((first + i, track));
+        }
+        AnimatedScene {
+            world: bvh::Bvh::new(all),
+            animated: tracks,
+            camera,
+            update: BvhUpdate::default(),
+        }
+    }
+
+    pub fn set_bvh_update(&mut self, update: BvhUpdate) {
+        self.update = update;
+    }
+
+    pub fn world(&self) -> &bvh::Bvh {
+        &self.world
+    }
+
+    /// Poses every animated object for `time` and returns the camera to render it with.
+    pub fn frame(&mut self, time: f64) -> camera::Camera {
+        let objects = self.world.objects_mut();
+        for (i, track) in &self.animated {
+            if let hittable::HittableObj::Instance(instance) = &mut objects[*i] {
+                instance.set_transform(track.at(time));
+            }
+        }
+        match self.update {
+            BvhUpdate::Refit => self.world.refit(),
+            BvhUpdate::Rebuild => self.world.rebuild(),
+        }
+        self.camera.at(time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_pass_through_their_keys() {
+        for interpolation in [
+            Interpolation::Linear,
+            Interpolation::CatmullRom,
+            Interpolation::Bezier,
+        ] {
+            let mut track = Track::new(interpolation, 0.0, 0.0);
+            track.add(2.0, 4.0);
+            track.add(1.0, 1.0);
+            track.add(3.0, 9.0);
+            for (t, v) in [(0.0, 0.0), (1.0, 1.0), (2.0, 4.0), (3.0, 9.0)] {
+                assert!((track.at(t) - v).abs() < 1e-12);
+            }
+            assert_eq!(track.at(-1.0), 0.0);
+            assert_eq!(track.at(5.0), 9.0);
+        }
+
+        let mut linear = Track::new(Interpolation::Linear, 0.0, 0.0);
+        linear.add(2.0, 4.0);
+        assert!((linear.at(0.5) - 1.0).abs() < 1e-12);
+
+        // Evenly spaced keys on a line stay on it
+        let mut smooth = Track::new(Interpolation::CatmullRom, 0.0, 0.0);
+        smooth.add(1.0, 2.0);
+        smooth.add(2.0, 4.0);
+        smooth.add(3.0, 6.0);
+        assert!((smooth.at(1.5) - 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn bezier_handles_shape_the_curve() {
+        // Flat handles ease in and out, like smoothstep
+        let mut track = Track::new(Interpolation::Bezier, 0.0, 0.0);
+        track.add_bezier(0.0, 0.0, 0.0, 0.0);
+        track.add_bezier(1.0, 1.0, 1.0, 1.0);
+        for s in [0.1, 0.25, 0.5, 0.8] {
+            let smoothstep = s * s * (3.0 - 2.0 * s);
+            assert!((track.at(s) - smoothstep).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn scene_moves_objects_between_frames() {
+        use crate::hittable::Hittable;
+        use crate::material;
+        use crate::ray;
+        use crate::sphere;
+        use std::sync::{Arc, Mutex};
+
+        let mat = Arc::new(Mutex::new(material::Material::Lambertian(
+            material::Lambertian::new(vector3::Color::new(0.5, 0.5, 0.5)),
+        )));
+        let ball = hittable::HittableObj::Sphere(sphere::Sphere::new(
+            vector3::Point::new(0.0, 0.0, 0.0),
+            0.5,
+            mat,
+        ));
+        let mut path = Track::new(
+            Interpolation::Linear,
+            0.0,
+            vector3::Vec3::new(0.0, 0.0, 0.0),
+        );
+        path.add(1.0, vector3::Vec3::new(4.0, 0.0, 0.0));
+        let motion = AnimatedTransform::new(
+            path,
+            Track::constant(vector3::Vec3::new(0.0, 0.0, 0.0)),
+            Track::constant(1.0),
+        );
+        let camera = CameraTrack::new(
+            Track::constant(vector3::Point::new(0.0, 0.0, 5.0)),
+            Track::constant(vector3::Point::new(0.0, 0.0, 0.0)),
+            vector3::Vec3::new(0.0, 1.0, 0.0),
+            Track::constant(40.0),
+            1.0,
+        );
+        let mut scene = AnimatedScene::new(Vec::new(), vec![(ball, motion)], camera);
+        let down = |x: f64| {
+            ray::Ray::new(
+                vector3::Point::new(x, 5.0, 0.0),
+                vector3::Vec3::new(0.0, -1.0, 0.0),
+            )
+        };
+        for update in [BvhUpdate::Refit, BvhUpdate::Rebuild] {
+            scene.set_bvh_update(update);
+            scene.frame(0.0);
+            assert!(scene
+                .world()
+                .hit(&down(0.0), 0.001, f64::INFINITY)
+                .is_some());
+            scene.frame(0.75);
+            assert!(scene
+                .world()
+                .hit(&down(0.0), 0.001, f64::INFINITY)
+                .is_none());
+            assert!(scene
+                .world()
+                .hit(&down(3.0), 0.001, f64::INFINITY)
+                .is_some());
+        }
+    }
+}
@@ -0,0 +1,312 @@
+use crate::hittable;
+use crate::material;
+use crate::onb;
+use crate::principled;
+use crate::vector3;
+use std::f64::consts::PI;
+use std::ops::BitOr;
+
+/// Kinds of scattering a BSDF can do, or that a sample came from.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct BsdfFlags(u8);
+
+impl BsdfFlags {
+    pub const REFLECTION: BsdfFlags = BsdfFlags(1);
+    pub const TRANSMISSION: BsdfFlags = BsdfFlags(2);
+    pub const DIFFUSE: BsdfFlags = BsdfFlags(4);
+    pub const GLOSSY: BsdfFlags = BsdfFlags(8);
+    /// Dirac delta lobes, which `eval` and `pdf` report as zero.
+    pub const SPECULAR: BsdfFlags = BsdfFlags(16);
+
+    pub fn contains(self, other: BsdfFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn is_specular(self) -> bool {
+        self.contains(BsdfFlags::SPECULAR)
+    }
+
+    pub fn is_transmission(self) -> bool {
+        self.contains(BsdfFlags::TRANSMISSION)
+    }
+}
+
+impl BitOr for BsdfFlags {
+    type Output = BsdfFlags;
+    fn bitor(self, other: BsdfFlags) -> BsdfFlags {
+        BsdfFlags(self.0 | other.0)
+    }
+}
+
+/// A sampled incident direction. For specular lobes `f` and `pdf` both carry the
+/// delta, so `f * |cos| / pdf` is still the path weight.
+#[derive(Copy, Clone)]
+pub struct BsdfSample {
+    pub wi: vector3::Vec3,
+    pub f: vector3::Color,
+    pub pdf: f64,
+    pub flags: BsdfFlags,
+}
+
+/// Splits one sample in [0, 1) into two by taking alternate bits, for sampling
+/// that needs more dimensions than it's given. Each half keeps 16 bits.
+pub fn split_sample(u: f64) -> [f64; 2] {
+    fn compact(x: u64) -> u64 {
+        let x = x & 0x5555_5555;
+        let x = (x ^ (x >> 1)) & 0x3333_3333;
+        let x = (x ^ (x >> 2)) & 0x0f0f_0f0f;
+        let x = (x ^ (x >> 4)) & 0x00ff_00ff;
+        (x ^ (x >> 8)) & 0x0000_ffff
+    }
+    let bits = (u * 4_294_967_296.0) as u64;
+    [
+        compact(bits) as f64 / 65_536.0,
+        compact(bits >> 1) as f64 / 65_536.0,
+    ]
+}
+
+/// Scattering in a local shading frame with +z along the normal. Directions point
+/// away from the surface. `u` drives the sampled direction, and any discrete choice
+/// between lobes is made from it too, so the same `u` always gives the same sample.
+pub trait BxdfTrait {
+    fn eval(&self, wo: vector3::Vec3, wi: vector3::Vec3) -> vector3::Color;
+    fn sample(&self, wo: vector3::Vec3, u: [f64; 2]) -> Option<BsdfSample>;
+    fn pdf(&self, wo: vector3::Vec3, wi: vector3::Vec3) -> f64;
+    fn flags(&self) -> BsdfFlags;
+}
+
+pub enum Bxdf {
+    Diffuse(Diffuse),
+    SpecularReflection(SpecularReflection),
+    SpecularDielectric(SpecularDielectric),
+    RoughConductor(material::RoughConductor),
+    RoughDielectric(material::RoughDielectric),
+    Principled(principled::PrincipledBsdf),
+    Hair(material::Hair),
+}
+
+impl BxdfTrait for Bxdf {
+    fn eval(&self, wo: vector3::Vec3, wi: vector3::Vec3) -> vector3::Color {
+        match self {
+            Bxdf::Diffuse(x) => x.eval(wo, wi),
+            Bxdf::SpecularReflection(x) => x.eval(wo, wi),
+            Bxdf::SpecularDielectric(x) => x.eval(wo, wi),
+            Bxdf::RoughConductor(x) => x.eval(wo, wi),
+            Bxdf::RoughDielectric(x) => x.eval(wo, wi),
+            Bxdf::Principled(x) => x.eval(wo, wi),
+            Bxdf::Hair(x) => x.eval(wo, wi),
+        }
+    }
+
+    fn sample(&self, wo: vector3::Vec3, u: [f64; 2]) -> Option<BsdfSample> {
+        match self {
+            Bxdf::Diffuse(x) => x.sample(wo, u),
+            Bxdf::SpecularReflection(x) => x.sample(wo, u),
+            Bxdf::SpecularDielectric(x) => x.sample(wo, u),
+            Bxdf::RoughConductor(x) => x.sample(wo, u),
+            Bxdf::RoughDielectric(x) => x.sample(wo, u),
+            Bxdf::Principled(x) => x.sample(wo, u),
+            Bxdf::Hair(x) => x.sample(wo, u),
+        }
+    }
+
+    fn pdf(&self, wo: vector3::Vec3, wi: vector3::Vec3) -> f64 {
+        match self {
+            Bxdf::Diffuse(x) => x.pdf(wo, wi),
+            Bxdf::SpecularReflection(x) => x.pdf(wo, wi),
+            Bxdf::SpecularDielectric(x) => x.pdf(wo, wi),
+            Bxdf::RoughConductor(x) => x.pdf(wo, wi),
+            Bxdf::RoughDielectric(x) => x.pdf(wo, wi),
+            Bxdf::Principled(x) => x.pdf(wo, wi),
+            Bxdf::Hair(x) => x.pdf(wo, wi),
+        }
+    }
+
+    fn flags(&self) -> BsdfFlags {
+        match self {
+            Bxdf::Diffuse(x) => x.flags(),
+            Bxdf::SpecularReflection(x) => x.flags(),
+            Bxdf::SpecularDielectric(x) => x.flags(),
+            Bxdf::RoughConductor(x) => x.flags(),
+            Bxdf::RoughDielectric(x) => x.flags(),
+            Bxdf::Principled(x) => x.flags(),
+            Bxdf::Hair(x) => x.flags(),
+        }
+    }
+}
+
+/// A `Bxdf` placed at a hit point, taking world space directions.
+pub struct Bsdf {
+    pub frame: onb::Onb,
+    pub bxdf: Bxdf,
+}
+
+impl Bsdf {
+    /// Opaque BSDFs get a frame around the normal facing the ray, so both sides of
+    /// a surface look the same. Ones that transmit get the outward normal instead,
+    /// so they can tell whether the ray is inside.
+    pub fn new(rec: &hittable::HitRecord, bxdf: Bxdf) -> Bsdf {
+        let n = if bxdf.flags().is_transmission() && !rec.front_face {
+            rec.normal * -1.0
+        } else {
+            rec.normal
+        };
+        Bsdf {
+            frame: onb::Onb::build_from_wu(n, rec.tangent),
+            bxdf,
+        }
+    }
+
+    pub fn eval(&self, wo: vector3::Vec3, wi: vector3::Vec3) -> vector3::Color {
+        self.bxdf
+            .eval(self.frame.to_local(wo), self.frame.to_local(wi))
+    }
+
+    /// Like `BxdfTrait::sample`, with `wi` returned in world space.
+    pub fn sample(&self, wo: vector3::Vec3, u: [f64; 2]) -> Option<BsdfSample> {
+        let mut s = self.bxdf.sample(self.frame.to_local(wo), u)?;
+        if s.pdf <= 0.0 || s.wi.z == 0.0 {
+            return None;
+        }
+        s.wi = self.frame.local(s.wi);
+        Some(s)
+    }
+
+    pub fn pdf(&self, wo: vector3::Vec3, wi: vector3::Vec3) -> f64 {
+        self.bxdf
+            .pdf(self.frame.to_local(wo), self.frame.to_local(wi))
+    }
+
+    /// Cosine of `w` with the shading normal.
+    pub fn cos_theta(&self, w: vector3::Vec3) -> f64 {
+        vector3::dot(w, self.frame.w)
+    }
+
+    pub fn flags(&self) -> BsdfFlags {
+        self.bxdf.flags()
+    }
+}
+
+/// Lambertian reflection.
+#[derive(Copy, Clone)]
+pub struct Diffuse {
+    pub r: vector3::Color,
+}
+
+impl BxdfTrait for Diffuse {
+    fn eval(&self, wo: vector3::Vec3, wi: vector3::Vec3) -> vector3::Color {
+        if !material::same_hemisphere(wo, wi) {
+            return vector3::Color::new(0.0, 0.0, 0.0);
+        }
+        self.r / PI
+    }
+
+    fn sample(&self, wo: vector3::Vec3, u: [f64; 2]) -> Option<BsdfSample> {
+        let mut wi = vector3::Vec3::cosine_direction(u[0], u[1]);
+        if wo.z < 0.0 {
+            wi.z = -wi.z;
+        }
+        Some(BsdfSample {
+            wi,
+            f: self.eval(wo, wi),
+            pdf: self.pdf(wo, wi),
+            flags: BsdfFlags::DIFFUSE | BsdfFlags::REFLECTION,
+        })
+    }
+
+    fn pdf(&self, wo: vector3::Vec3, wi: vector3::Vec3) -> f64 {
+        if !material::same_hemisphere(wo, wi) {
+            return 0.0;
+        }
+        wi.z.abs() / PI
+    }
+
+    fn flags(&self) -> BsdfFlags {
+        BsdfFlags::DIFFUSE | BsdfFlags::REFLECTION
+    }
+}
+
+/// Perfect mirror that reflects a fraction `r` of the light.
+#[derive(Copy, Clone)]
+pub struct SpecularReflection {
+    pub r: vector3::Color,
+}
+
+impl BxdfTrait for SpecularReflection {
+    fn eval(&self, _wo: vector3::Vec3, _wi: vector3::Vec3) -> vector3::Color {
+        vector3::Color::new(0.0, 0.0, 0.0)
+    }
+
+    fn sample(&self, wo: vector3::Vec3, _u: [f64; 2]) -> Option<BsdfSample> {
+        let wi = vector3::Vec3::new(-wo.x, -wo.y, wo.z);
+        if wi.z == 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            wi,
+            f: self.r / wi.z.abs(),
+            pdf: 1.0,
+            flags: self.flags(),
+        })
+    }
+
+    fn pdf(&self, _wo: vector3::Vec3, _wi: vector3::Vec3) -> f64 {
+        0.0
+    }
+
+    fn flags(&self) -> BsdfFlags {
+        BsdfFlags::SPECULAR | BsdfFlags::REFLECTION
+    }
+}
+
+/// Smooth glass with index of refraction `ir`, +z pointing out of it. Reflection
+/// uses Schlick's approximation.
+#[derive(Copy, Clone)]
+pub struct SpecularDielectric {
+    pub ir: f64,
+}
+
+impl BxdfTrait for SpecularDielectric {
+    fn eval(&self, _wo: vector3::Vec3, _wi: vector3::Vec3) -> vector3::Color {
+        vector3::Color::new(0.0, 0.0, 0.0)
+    }
+
+    fn sample(&self, wo: vector3::Vec3, u: [f64; 2]) -> Option<BsdfSample> {
+        if wo.z == 0.0 {
+            return None;
+        }
+        let white = vector3::Color::new(1.0, 1.0, 1.0);
+        let n = vector3::Vec3::new(0.0, 0.0, 1.0);
+        let refracted = material::refract_local(wo, n, self.ir);
+        let r = match refracted {
+            Some(_) => material::Dielectric::reflectance(wo.z.abs(), self.ir),
+            None => 1.0,
+        };
+
+        match refracted {
+            Some((wi, _)) if wi.z != 0.0 && u[0] >= r => Some(BsdfSample {
+                wi,
+                f: white * ((1.0 - r) / wi.z.abs()),
+                pdf: 1.0 - r,
+                flags: BsdfFlags::SPECULAR | BsdfFlags::TRANSMISSION,
+            }),
+            _ => {
+                let wi = vector3::Vec3::new(-wo.x, -wo.y, wo.z);
+                Some(BsdfSample {
+                    wi,
+                    f: white * (r / wi.z.abs()),
+                    pdf: r,
+                    flags: BsdfFlags::SPECULAR | BsdfFlags::REFLECTION,
+                })
+            }
+        }
+    }
+
+    fn pdf(&self, _wo: vector3::Vec3, _wi: vector3::Vec3) -> f64 {
+        0.0
+    }
+
+    fn flags(&self) -> BsdfFlags {
+        BsdfFlags::SPECULAR | BsdfFlags::REFLECTION | BsdfFlags::TRANSMISSION
+    }
+}
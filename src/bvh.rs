@@ -0,0 +1,268 @@
+use crate::aabb;
+use crate::hittable;
+use crate::hittable::Hittable;
+use crate::ray;
+use crate::vector3;
+
+/// Objects a leaf holds before it's worth splitting further.
+const LEAF_SIZE: usize = 2;
+
+#[derive(Clone)]
+enum NodeKind {
+    /// A run of `order`, as start and length
+    Leaf(usize, usize),
+    Interior(usize, usize),
+}
+
+#[derive(Clone)]
+struct Node {
+    bounds: aabb::Aabb,
+    kind: NodeKind,
+}
+
+/// Bounding volume hierarchy over a scene's objects. Objects that can move keep
+/// their place in the tree: after moving them, `refit` grows the boxes to match,
+/// which is fast but loosens the tree, while `rebuild` starts it over.
+#[derive(Clone)]
+pub struct Bvh {
+    objects: Vec<hittable::HittableObj>,
+    /// Objects in leaf order
+    order: Vec<usize>,
+    /// Objects without bounds, such as planes, tested on every ray
+    unbounded: Vec<usize>,
+    nodes: Vec<Node>,
+}
+
+fn centroid(b: &aabb::Aabb) -> vector3::Point {
+    (b.minimum + b.maximum) * 0.5
+}
+
+impl Bvh {
+    pub fn new(objects: Vec<hittable::HittableObj>) -> Bvh {
+        let mut bvh = Bvh {
+            objects,
+            order: Vec::new(),
+            unbounded: Vec::new(),
+            nodes: Vec::new(),
+        };
+        bvh.rebuild();
+        bvh
+    }
+
+    pub fn objects(&self) -> &[hittable::HittableObj] {
+        &self.objects
+    }
+
+    /// The objects, to move in place. Call `refit` or `rebuild` afterwards.
+    pub fn objects_mut(&mut self) -> &mut [hittable::HittableObj] {
+        &mut self.objects
+    }
+
+    /// Builds the tree from scratch around where the objects are now.
+    pub fn rebuild(&mut self) {
+        let mut items = Vec::new();
+        self.unbounded.clear();
+        for (i, object) in self.objects.iter().enumerate() {
+            match object.bounding_box() {
+                Some(b) => items.push((i, b)),
+                None => self.unbounded.push(i),
+            }
+        }
+        self.nodes.clear();
+        self.order.clear();
+        if !items.is_empty() {
+            self.build(&mut items);
+        }
+    }
+
+    fn build(&mut self, items: &mut [(usize, aabb::Aabb)]) -> usize {
+        let index = self.nodes.len();
+        let bounds = items[1..]
+            .iter()
+            .fold(items[0].1, |acc, (_, b)| aabb::surrounding_box(acc, *b));
+        if items.len() <= LEAF_SIZE {
+            self.nodes.push(Node {
+                bounds,
+                kind: NodeKind::Leaf(self.order.len(), items.len()),
+            });
+            self.order.extend(items.iter().map(|(i, _)| *i));
+            return index;
+        }
+
+        // Halve along the axis the centroids are most spread over
+        let (lo, hi) = items.iter().fold(
+            (
+                vector3::Point::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+                vector3::Point::new(-f64::INFINITY, -f64::INFINITY, -f64::INFINITY),
+            ),
+            |(lo, hi), (_, b)| {
+                let c = centroid(b);
+                (
+                    vector3::Point::new(lo.x.min(c.x), lo.y.min(c.y), lo.z.min(c.z)),
+                    vector3::Point::new(hi.x.max(c.x), hi.y.max(c.y), hi.z.max(c.z)),
+                )
+            },
+        );
+        let extent = hi - lo;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        };
+        let mid = items.len() / 2;
+        items.select_nth_unstable_by(mid, |(_, a), (_, b)| {
+            centroid(a)[axis].total_cmp(&centroid(b)[axis])
+        });
+
+        self.nodes.push(Node {
+            bounds,
+            kind: NodeKind::Interior(0, 0),
+        });
+        let (below, above) = items.split_at_mut(mid);
+        let left = self.build(below);
+        let right = self.build(above);
+        self.nodes[index].kind = NodeKind::Interior(left, right);
+        index
+    }
+
+    /// Recomputes every box bottom-up for where the objects are now, keeping the
+    /// tree's shape. An object that has lost its bounds needs a `rebuild` instead.
+    pub fn refit(&mut self) {
+        // Children are always stored after their parent
+        for index in (0..self.nodes.len()).rev() {
+            let bounds = match self.nodes[index].kind {
+                NodeKind::Leaf(start, len) => self.order[start..start + len]
+                    .iter()
+                    .filter_map(|&i| self.objects[i].bounding_box())
+                    .reduce(aabb::surrounding_box),
+                NodeKind::Interior(left, right) => Some(aabb::surrounding_box(
+                    self.nodes[left].bounds,
+                    self.nodes[right].bounds,
+                )),
+            };
+            if let Some(bounds) = bounds {
+                self.nodes[index].bounds = bounds;
+            }
+        }
+    }
+}
+
+impl hittable::Hittable for Bvh {
+    fn hit(&self, r: &ray::Ray, t_min: f64, t_max: f64) -> Option<hittable::HitRecord> {
+        let mut closest = t_max;
+        let mut result = None;
+        for &i in &self.unbounded {
+            if let Some(rec) = self.objects[i].hit(r, t_min, closest) {
+                closest = rec.t;
+                result = Some(rec);
+            }
+        }
+        if self.nodes.is_empty() {
+            return result;
+        }
+
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !node.bounds.hit(r, t_min, closest) {
+                continue;
+            }
+            match node.kind {
+                NodeKind::Leaf(start, len) => {
+                    for &i in &self.order[start..start + len] {
+                        if let Some(rec) = self.objects[i].hit(r, t_min, closest) {
+                            closest = rec.t;
+                            result = Some(rec);
+                        }
+                    }
+                }
+                NodeKind::Interior(left, right) => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+            }
+        }
+        result
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        if !self.unbounded.is_empty() {
+            return None;
+        }
+        self.nodes.first().map(|n| n.bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material;
+    use crate::sphere;
+    use crate::transform;
+    use std::sync::{Arc, Mutex};
+
+    fn balls(n: usize) -> Vec<hittable::HittableObj> {
+        let mat = Arc::new(Mutex::new(material::Material::Lambertian(
+            material::Lambertian::new(vector3::Color::new(0.5, 0.5, 0.5)),
+        )));
+        (0..n)
+            .map(|i| {
+                let ball = hittable::HittableObj::Sphere(sphere::Sphere::new(
+                    vector3::Point::new(0.0, 0.0, 0.0),
+                    0.4,
+                    mat.clone(),
+                ));
+                hittable::HittableObj::Instance(transform::Instance::new(
+                    ball,
+                    transform::Transform::new(
+                        vector3::Vec3::new(i as f64, 0.0, 0.0),
+                        vector3::Vec3::new(0.0, 0.0, 0.0),
+                        1.0,
+                    ),
+                ))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_the_same_hits_as_a_list() {
+        let objects = balls(9);
+        let mut list = hittable::HittableList::new();
+        for o in &objects {
+            list.add(o.clone());
+        }
+        let bvh = Bvh::new(objects);
+        for k in 0..40 {
+            let r = ray::Ray::new(
+                vector3::Point::new(-3.0, 0.1, 0.0),
+                vector3::Vec3::new(1.0, 0.02 * (k as f64 - 20.0), 0.01 * k as f64),
+            );
+            let a = bvh.hit(&r, 0.001, f64::INFINITY).map(|rec| rec.t);
+            let b = list.hit(&r, 0.001, f64::INFINITY).map(|rec| rec.t);
+            assert_eq!(a, b);
+        }
+    }
+
+    #[test]
+    fn refit_follows_moved_objects() {
+        let mut bvh = Bvh::new(balls(5));
+        let r = ray::Ray::new(
+            vector3::Point::new(2.0, 10.0, 0.0),
+            vector3::Vec3::new(0.0, -1.0, 0.0),
+        );
+        let rec = bvh.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.p.y - 0.4).abs() < 1e-9);
+
+        if let hittable::HittableObj::Instance(i) = &mut bvh.objects_mut()[2] {
+            let mut t = i.transform();
+            t.translation = vector3::Vec3::new(2.0, 5.0, 0.0);
+            i.set_transform(t);
+        }
+        bvh.refit();
+        let rec = bvh.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.p.y - 5.4).abs() < 1e-9);
+        assert!((bvh.bounding_box().unwrap().maximum.y - 5.4).abs() < 1e-9);
+    }
+}
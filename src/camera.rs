@@ -1,17 +1,150 @@
+use crate::distribution;
+use crate::lens;
 use crate::ray;
+use crate::texture;
+use crate::utils;
 use crate::vector3;
-pub struct Camera {
+use std::f64::consts::PI;
+
+pub trait CameraModel {
+    /// Ray through the image at `x` across from the left and `t` up from the
+    /// bottom, both in [0, 1]. `None` where the image isn't covered, such as
+    /// outside a fisheye's image circle.
+    fn get_ray(&self, x: f64, t: f64) -> Option<ray::Ray>;
+}
+
+pub enum Camera {
+    Perspective(PerspectiveCamera),
+    Orthographic(OrthographicCamera),
+    Fisheye(FisheyeCamera),
+    Equirectangular(EquirectangularCamera),
+    Realistic(lens::RealisticCamera),
+    Stereo(StereoCamera),
+    CubeMap(CubeMapCamera),
+}
+
+impl CameraModel for Camera {
+    fn get_ray(&self, x: f64, t: f64) -> Option<ray::Ray> {
+        match self {
+            Camera::Perspective(c) => c.get_ray(x, t),
+            Camera::Orthographic(c) => c.get_ray(x, t),
+            Camera::Fisheye(c) => c.get_ray(x, t),
+            Camera::Equirectangular(c) => c.get_ray(x, t),
+            Camera::Realistic(c) => c.get_ray(x, t),
+            Camera::Stereo(c) => c.get_ray(x, t),
+            Camera::CubeMap(c) => c.get_ray(x, t),
+        }
+    }
+}
+
+/// Right, up and backward unit vectors of a camera at `lookfrom` facing `lookat`.
+fn basis(
+    lookfrom: vector3::Point,
+    lookat: vector3::Point,
+    vup: vector3::Vec3,
+) -> (vector3::Vec3, vector3::Vec3, vector3::Vec3) {
+    let w = vector3::Vec3::unit_vector(lookfrom - lookat);
+    let u = vector3::Vec3::unit_vector(vector3::cross(vup, w));
+    let v = vector3::cross(w, u);
+    (u, v, w)
+}
+
+/// Shape of the lens opening, which out-of-focus highlights take on.
+#[derive(Clone)]
+pub enum Aperture {
+    Circle,
+    /// Regular polygon formed by `blades` straight blades, turned by `rotation` degrees
+    Polygon {
+        blades: u32,
+        rotation: f64,
+    },
+    /// Opening drawn as an image, bright where light gets through. Made with
+    /// `Aperture::from_image`.
+    Image(distribution::Distribution2D),
+}
+
+impl Aperture {
+    /// Aperture shaped like `image`, stretched over the lens's bounding square.
+    pub fn from_image(image: &texture::ImageTexture) -> Aperture {
+        let (w, h) = (image.width(), image.height());
+        let mut func = Vec::with_capacity(w * h);
+        for j in 0..h {
+            for i in 0..w {
+                func.push(image.texel(i as i64, j as i64).luminance());
+            }
+        }
+        Aperture::Image(distribution::Distribution2D::new(&func, w, h))
+    }
+
+    /// Point spread evenly over the opening, within the unit disk (or square, for images).
+    pub fn sample(&self) -> (f64, f64) {
+        match self {
+            Aperture::Circle => {
+                let p = vector3::Vec3::random_in_unit_disk();
+                (p.x, p.y)
+            }
+            Aperture::Polygon { blades, rotation } => {
+                // Pick one of the triangles fanning out from the centre, then a
+                // point in it
+                let n = (*blades).max(3) as f64;
+                let k = (utils::random_double(0.0, 1.0) * n).floor();
+                let a0 = rotation.to_radians() + 2.0 * PI * k / n;
+                let a1 = a0 + 2.0 * PI / n;
+                let su = utils::random_double(0.0, 1.0).sqrt();
+                let b = utils::random_double(0.0, 1.0);
+                let (p0, p1) = ((a0.cos(), a0.sin()), (a1.cos(), a1.sin()));
+                (
+                    su * ((1.0 - b) * p0.0 + b * p1.0),
+                    su * ((1.0 - b) * p0.1 + b * p1.1),
+                )
+            }
+            Aperture::Image(d) => {
+                let ([x, t], _) = d.sample([
+                    utils::random_double(0.0, 1.0),
+                    utils::random_double(0.0, 1.0),
+                ]);
+                (2.0 * x - 1.0, 1.0 - 2.0 * t)
+            }
+        }
+    }
+}
+
+/// Camera settings in photographic terms. Lengths are in millimetres.
+#[derive(Copy, Clone, Debug)]
+pub struct Lens {
+    pub focal_length: f64,
+    pub f_stop: f64,
+    /// 24 for a full-frame sensor in landscape
+    pub sensor_height: f64,
+    /// Scene units in a metre, to size the aperture
+    pub units_per_meter: f64,
+}
+
+/// Pinhole or thin-lens camera, with `vfov` the vertical field of view in degrees.
+pub struct PerspectiveCamera {
     origin: vector3::Point,
     lower_left_corner: vector3::Point,
     horizontal: vector3::Vec3,
     vertical: vector3::Vec3,
     u: vector3::Vec3,
     v: vector3::Vec3,
-    w: vector3::Vec3,
     lens_radius: f64,
+    aperture: Aperture,
+    cats_eye: f64,
 }
 
-impl Camera {
+impl PerspectiveCamera {
+    /// The same camera with its eye moved `shift` along the image's horizontal
+    /// but looking through the same window at the focus distance, as one eye of
+    /// an off-axis stereo pair.
+    pub fn shifted(&self, shift: f64) -> PerspectiveCamera {
+        PerspectiveCamera {
+            origin: self.origin + self.u * shift,
+            aperture: self.aperture.clone(),
+            ..*self
+        }
+    }
+
     pub fn new(
         lookfrom: vector3::Point,
         lookat: vector3::Point,
@@ -20,37 +153,548 @@ impl Camera {
         aspect_ratio: f64,
         aperture: f64,
         focus_dist: f64,
-    ) -> Camera {
+    ) -> PerspectiveCamera {
         let theta = vfov.to_radians();
         let h = (theta / 2.0).tan();
         let viewport_height = 2.0 * h;
         let viewport_width = aspect_ratio * viewport_height;
 
-        let w = vector3::Vec3::unit_vector(lookfrom - lookat);
-        let u = vector3::Vec3::unit_vector(vector3::cross(vup, w));
-        let v = vector3::cross(w, u);
+        let (u, v, w) = basis(lookfrom, lookat, vup);
 
         let horizontal = u * viewport_width * focus_dist;
         let vertical = v * viewport_height * focus_dist;
-        Camera {
-            horizontal: horizontal,
-            vertical: vertical,
+        PerspectiveCamera {
+            horizontal,
+            vertical,
             origin: lookfrom,
             lower_left_corner: lookfrom - horizontal / 2.0 - vertical / 2.0 - w * focus_dist,
-            u: u,
-            v: v,
-            w: w,
+            u,
+            v,
             lens_radius: aperture / 2.0,
+            aperture: Aperture::Circle,
+            cats_eye: 0.0,
         }
     }
 
-    pub fn get_ray(&self, x: f64, t: f64) -> ray::Ray {
-        let rd = vector3::Vec3::random_in_unit_disk() * self.lens_radius;
-        let offset = self.u * rd.x() + self.v * rd.y();
+    /// Camera with the field of view and depth of field of `lens`, focused
+    /// `focus_dist` scene units away.
+    pub fn from_lens(
+        lookfrom: vector3::Point,
+        lookat: vector3::Point,
+        vup: vector3::Vec3,
+        lens: Lens,
+        aspect_ratio: f64,
+        focus_dist: f64,
+    ) -> PerspectiveCamera {
+        let vfov = 2.0 * (0.5 * lens.sensor_height / lens.focal_length).atan();
+        // The entrance pupil is the focal length over the f-number across
+        let aperture = lens.focal_length / lens.f_stop * 1e-3 * lens.units_per_meter;
+        PerspectiveCamera::new(
+            lookfrom,
+            lookat,
+            vup,
+            vfov.to_degrees(),
+            aspect_ratio,
+            aperture,
+            focus_dist,
+        )
+    }
 
-        return ray::Ray::new(
+    pub fn set_aperture(&mut self, aperture: Aperture) {
+        self.aperture = aperture;
+    }
+
+    /// Clips the aperture toward the edges of the frame, as a lens barrel does,
+    /// so out-of-focus highlights there turn into cat's eyes and the corners
+    /// darken. `strength` is how far, in aperture radii, the clipping circle has
+    /// moved by the corners of the frame.
+    pub fn set_cats_eye(&mut self, strength: f64) {
+        self.cats_eye = strength;
+    }
+}
+
+impl CameraModel for PerspectiveCamera {
+    fn get_ray(&self, x: f64, t: f64) -> Option<ray::Ray> {
+        let (lx, ly) = self.aperture.sample();
+        if self.cats_eye > 0.0 {
+            let shift = self.cats_eye / 2f64.sqrt();
+            let (cx, cy) = ((2.0 * x - 1.0) * shift, (2.0 * t - 1.0) * shift);
+            if (lx - cx).powi(2) + (ly - cy).powi(2) > 1.0 {
+                return None;
+            }
+        }
+        let offset = self.u * (lx * self.lens_radius) + self.v * (ly * self.lens_radius);
+
+        Some(ray::Ray::new(
             self.origin + offset,
             self.lower_left_corner + self.horizontal * x + self.vertical * t - self.origin - offset,
+        ))
+    }
+}
+
+/// Parallel projection onto a view `view_height` units tall, centered on `lookfrom`.
+pub struct OrthographicCamera {
+    lower_left_corner: vector3::Point,
+    horizontal: vector3::Vec3,
+    vertical: vector3::Vec3,
+    direction: vector3::Vec3,
+}
+
+impl OrthographicCamera {
+    pub fn new(
+        lookfrom: vector3::Point,
+        lookat: vector3::Point,
+        vup: vector3::Vec3,
+        view_height: f64,
+        aspect_ratio: f64,
+    ) -> OrthographicCamera {
+        let (u, v, w) = basis(lookfrom, lookat, vup);
+        let horizontal = u * (view_height * aspect_ratio);
+        let vertical = v * view_height;
+        OrthographicCamera {
+            lower_left_corner: lookfrom - horizontal / 2.0 - vertical / 2.0,
+            horizontal,
+            vertical,
+            direction: w * -1.0,
+        }
+    }
+}
+
+impl CameraModel for OrthographicCamera {
+    fn get_ray(&self, x: f64, t: f64) -> Option<ray::Ray> {
+        Some(ray::Ray::new(
+            self.lower_left_corner + self.horizontal * x + self.vertical * t,
+            self.direction,
+        ))
+    }
+}
+
+/// Equidistant fisheye: distance from the image centre is proportional to the
+/// angle from the view direction. The image circle spans the image's height and
+/// `fov` degrees, up to 360.
+pub struct FisheyeCamera {
+    origin: vector3::Point,
+    u: vector3::Vec3,
+    v: vector3::Vec3,
+    w: vector3::Vec3,
+    half_fov: f64,
+    aspect_ratio: f64,
+}
+
+impl FisheyeCamera {
+    pub fn new(
+        lookfrom: vector3::Point,
+        lookat: vector3::Point,
+        vup: vector3::Vec3,
+        fov: f64,
+        aspect_ratio: f64,
+    ) -> FisheyeCamera {
+        let (u, v, w) = basis(lookfrom, lookat, vup);
+        FisheyeCamera {
+            origin: lookfrom,
+            u,
+            v,
+            w,
+            half_fov: 0.5 * fov.min(360.0).to_radians(),
+            aspect_ratio,
+        }
+    }
+}
+
+impl CameraModel for FisheyeCamera {
+    fn get_ray(&self, x: f64, t: f64) -> Option<ray::Ray> {
+        let px = (2.0 * x - 1.0) * self.aspect_ratio;
+        let py = 2.0 * t - 1.0;
+        let r = (px * px + py * py).sqrt();
+        if r > 1.0 {
+            return None;
+        }
+        let theta = r * self.half_fov;
+        let phi = py.atan2(px);
+        let dir = (self.u * phi.cos() + self.v * phi.sin()) * theta.sin() - self.w * theta.cos();
+        Some(ray::Ray::new(self.origin, dir))
+    }
+}
+
+/// Full 360° by 180° panorama in latitude-longitude layout, as used for VR and
+/// environment maps. The centre of the image looks toward `lookat` and the top
+/// row straight along `vup`.
+pub struct EquirectangularCamera {
+    origin: vector3::Point,
+    u: vector3::Vec3,
+    v: vector3::Vec3,
+    w: vector3::Vec3,
+    /// How far right of the centre each ray starts, across its own direction
+    eye_offset: f64,
+}
+
+impl EquirectangularCamera {
+    pub fn new(
+        lookfrom: vector3::Point,
+        lookat: vector3::Point,
+        vup: vector3::Vec3,
+    ) -> EquirectangularCamera {
+        let (u, v, w) = basis(lookfrom, lookat, vup);
+        EquirectangularCamera {
+            origin: lookfrom,
+            u,
+            v,
+            w,
+            eye_offset: 0.0,
+        }
+    }
+
+    /// One eye of an omni-directional stereo panorama: every ray starts
+    /// `eye_offset` to the right of `lookfrom`, across the direction it looks in,
+    /// as if the viewer turned their head to face it. Negative for the left eye.
+    pub fn ods(
+        lookfrom: vector3::Point,
+        lookat: vector3::Point,
+        vup: vector3::Vec3,
+        eye_offset: f64,
+    ) -> EquirectangularCamera {
+        EquirectangularCamera {
+            eye_offset,
+            ..EquirectangularCamera::new(lookfrom, lookat, vup)
+        }
+    }
+}
+
+impl CameraModel for EquirectangularCamera {
+    fn get_ray(&self, x: f64, t: f64) -> Option<ray::Ray> {
+        let longitude = 2.0 * PI * (x - 0.5);
+        let latitude = PI * (t - 0.5);
+        let dir = (self.u * longitude.sin() - self.w * longitude.cos()) * latitude.cos()
+            + self.v * latitude.sin();
+        let right = self.u * longitude.cos() + self.w * longitude.sin();
+        Some(ray::Ray::new(self.origin + right * self.eye_offset, dir))
+    }
+}
+
+/// How a pair of images shares one picture.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StereoLayout {
+    /// Left eye in the left half
+    SideBySide,
+    /// Left eye in the top half
+    OverUnder,
+}
+
+/// Two cameras rendered into one picture, one per eye.
+pub struct StereoCamera {
+    left: Box<Camera>,
+    right: Box<Camera>,
+    layout: StereoLayout,
+}
+
+impl StereoCamera {
+    pub fn new(left: Camera, right: Camera, layout: StereoLayout) -> StereoCamera {
+        StereoCamera {
+            left: Box::new(left),
+            right: Box::new(right),
+            layout,
+        }
+    }
+
+    /// Eyes `eye_separation` apart either side of `camera`, sharing its window
+    /// so objects at its focus distance appear at the depth of the screen.
+    pub fn off_axis(
+        camera: &PerspectiveCamera,
+        eye_separation: f64,
+        layout: StereoLayout,
+    ) -> StereoCamera {
+        StereoCamera::new(
+            Camera::Perspective(camera.shifted(-0.5 * eye_separation)),
+            Camera::Perspective(camera.shifted(0.5 * eye_separation)),
+            layout,
+        )
+    }
+
+    /// Omni-directional stereo: a full 360° panorama for each eye.
+    pub fn omnidirectional(
+        lookfrom: vector3::Point,
+        lookat: vector3::Point,
+        vup: vector3::Vec3,
+        eye_separation: f64,
+        layout: StereoLayout,
+    ) -> StereoCamera {
+        let eye = |offset| {
+            Camera::Equirectangular(EquirectangularCamera::ods(lookfrom, lookat, vup, offset))
+        };
+        StereoCamera::new(
+            eye(-0.5 * eye_separation),
+            eye(0.5 * eye_separation),
+            layout,
+        )
+    }
+}
+
+impl CameraModel for StereoCamera {
+    fn get_ray(&self, x: f64, t: f64) -> Option<ray::Ray> {
+        match self.layout {
+            StereoLayout::SideBySide if x < 0.5 => self.left.get_ray(2.0 * x, t),
+            StereoLayout::SideBySide => self.right.get_ray(2.0 * x - 1.0, t),
+            StereoLayout::OverUnder if t >= 0.5 => self.left.get_ray(x, 2.0 * t - 1.0),
+            StereoLayout::OverUnder => self.right.get_ray(x, 2.0 * t),
+        }
+    }
+}
+
+/// How the six faces of a cube map are arranged in the picture.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CubeLayout {
+    /// Four by three: left, front, right and back across the middle, with up
+    /// above and down below the front. The other cells are left empty.
+    Cross,
+    /// Six by one, in the order +x, -x, +y, -y, +z, -z of the camera's frame
+    /// (right, left, up, down, back, front)
+    Strip,
+}
+
+/// Six 90° views from one point, one along each axis of the camera's frame,
+/// each upright and continuous with its neighbours in the cross layout.
+pub struct CubeMapCamera {
+    origin: vector3::Point,
+    u: vector3::Vec3,
+    v: vector3::Vec3,
+    w: vector3::Vec3,
+    layout: CubeLayout,
+}
+
+impl CubeMapCamera {
+    pub fn new(
+        lookfrom: vector3::Point,
+        lookat: vector3::Point,
+        vup: vector3::Vec3,
+        layout: CubeLayout,
+    ) -> CubeMapCamera {
+        let (u, v, w) = basis(lookfrom, lookat, vup);
+        CubeMapCamera {
+            origin: lookfrom,
+            u,
+            v,
+            w,
+            layout,
+        }
+    }
+
+    /// Forward, right and up of a face, numbered as in the strip layout.
+    fn face(&self, i: usize) -> (vector3::Vec3, vector3::Vec3, vector3::Vec3) {
+        let (u, v, w) = (self.u, self.v, self.w);
+        match i {
+            0 => (u, w, v),
+            1 => (u * -1.0, w * -1.0, v),
+            2 => (v, u, w),
+            3 => (v * -1.0, u, w * -1.0),
+            4 => (w, u * -1.0, v),
+            _ => (w * -1.0, u, v),
+        }
+    }
+}
+
+impl CameraModel for CubeMapCamera {
+    fn get_ray(&self, x: f64, t: f64) -> Option<ray::Ray> {
+        let (columns, rows) = match self.layout {
+            CubeLayout::Cross => (4.0, 3.0),
+            CubeLayout::Strip => (6.0, 1.0),
+        };
+        let (col, row) = (
+            ((x * columns) as usize).min(columns as usize - 1),
+            ((t * rows) as usize).min(rows as usize - 1),
         );
+        let face = match self.layout {
+            CubeLayout::Strip => col,
+            // Rows count up from the bottom
+            CubeLayout::Cross => match (col, row) {
+                (1, 2) => 2,
+                (0, 1) => 1,
+                (1, 1) => 5,
+                (2, 1) => 0,
+                (3, 1) => 4,
+                (1, 0) => 3,
+                _ => return None,
+            },
+        };
+        let a = 2.0 * (x * columns - col as f64) - 1.0;
+        let b = 2.0 * (t * rows - row as f64) - 1.0;
+        let (forward, right, up) = self.face(face);
+        Some(ray::Ray::new(self.origin, forward + right * a + up * b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: vector3::Vec3, b: vector3::Vec3) -> bool {
+        (a - b).length() < 1e-9
+    }
+
+    /// At the origin looking down -z with +y up.
+    fn view() -> (vector3::Point, vector3::Point, vector3::Vec3) {
+        (
+            vector3::Point::new(0.0, 0.0, 0.0),
+            vector3::Point::new(0.0, 0.0, -1.0),
+            vector3::Vec3::new(0.0, 1.0, 0.0),
+        )
+    }
+
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let (from, at, up) = view();
+        let cam = OrthographicCamera::new(from, at, up, 2.0, 2.0);
+        let a = cam.get_ray(0.0, 0.0).unwrap();
+        let b = cam.get_ray(1.0, 1.0).unwrap();
+        assert!(close(a.dir, b.dir) && close(a.dir, vector3::Vec3::new(0.0, 0.0, -1.0)));
+        assert!(close(a.origin, vector3::Point::new(-2.0, -1.0, 0.0)));
+        assert!(close(b.origin, vector3::Point::new(2.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn fisheye_angle_grows_with_radius() {
+        let (from, at, up) = view();
+        let cam = FisheyeCamera::new(from, at, up, 180.0, 1.0);
+        let centre = cam.get_ray(0.5, 0.5).unwrap();
+        assert!(close(
+            centre.dir.unit_vector(),
+            vector3::Vec3::new(0.0, 0.0, -1.0)
+        ));
+        // Edge of the circle is 90 degrees off axis, halfway is 45
+        let edge = cam.get_ray(1.0, 0.5).unwrap();
+        assert!(close(
+            edge.dir.unit_vector(),
+            vector3::Vec3::new(1.0, 0.0, 0.0)
+        ));
+        let half = cam.get_ray(0.5, 0.75).unwrap().dir.unit_vector();
+        assert!((half.z + (PI / 4.0).cos()).abs() < 1e-9 && half.y > 0.0);
+        assert!(cam.get_ray(0.0, 0.0).is_none());
+    }
+
+    #[test]
+    fn equirectangular_covers_the_sphere() {
+        let (from, at, up) = view();
+        let cam = EquirectangularCamera::new(from, at, up);
+        let dir = |x, t| cam.get_ray(x, t).unwrap().dir.unit_vector();
+        assert!(close(dir(0.5, 0.5), vector3::Vec3::new(0.0, 0.0, -1.0)));
+        assert!(close(dir(0.75, 0.5), vector3::Vec3::new(1.0, 0.0, 0.0)));
+        assert!(close(dir(0.0, 0.5), vector3::Vec3::new(0.0, 0.0, 1.0)));
+        assert!(close(dir(0.3, 1.0), vector3::Vec3::new(0.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn polygonal_aperture_stays_inside_its_blades() {
+        let aperture = Aperture::Polygon {
+            blades: 6,
+            rotation: 0.0,
+        };
+        // A hexagon with a corner on +x has flat sides at distance cos(30) from the centre
+        let apothem = (PI / 6.0).cos();
+        let mut reach: f64 = 0.0;
+        for _ in 0..2000 {
+            let (x, y) = aperture.sample();
+            for k in 0..6 {
+                let a = PI / 6.0 + PI / 3.0 * k as f64;
+                assert!(x * a.cos() + y * a.sin() <= apothem + 1e-9);
+            }
+            reach = reach.max((x * x + y * y).sqrt());
+        }
+        assert!(reach > 0.9);
+    }
+
+    #[test]
+    fn lens_settings_set_view_and_depth_of_field() {
+        let (from, at, up) = view();
+        let lens = Lens {
+            focal_length: 50.0,
+            f_stop: 2.0,
+            sensor_height: 24.0,
+            units_per_meter: 1.0,
+        };
+        let cam = PerspectiveCamera::from_lens(from, at, up, lens, 1.5, 1.0);
+        // 50mm on full frame sees about 27 degrees vertically through a 25mm pupil
+        let half_height = 0.5 * cam.vertical.length();
+        assert!((half_height - 0.24).abs() < 1e-9);
+        assert!((cam.lens_radius - 0.0125).abs() < 1e-12);
+    }
+
+    #[test]
+    fn cats_eye_only_clips_off_centre() {
+        let (from, at, up) = view();
+        let mut cam = PerspectiveCamera::new(from, at, up, 40.0, 1.0, 0.5, 1.0);
+        cam.set_cats_eye(1.0);
+        let kept = |x, t| (0..1000).filter(|_| cam.get_ray(x, t).is_some()).count();
+        assert_eq!(kept(0.5, 0.5), 1000);
+        let corner = kept(1.0, 1.0);
+        // Two unit circles a radius apart overlap by about 39%
+        assert!(corner > 300 && corner < 480, "{}", corner);
+    }
+
+    #[test]
+    fn off_axis_eyes_converge_at_the_focus_distance() {
+        let (from, at, up) = view();
+        let base = PerspectiveCamera::new(from, at, up, 40.0, 1.0, 0.0, 3.0);
+        let rig = StereoCamera::off_axis(&base, 0.064, StereoLayout::SideBySide);
+        let left = rig.get_ray(0.25, 0.5).unwrap();
+        let right = rig.get_ray(0.75, 0.5).unwrap();
+        assert!(close(left.origin, vector3::Point::new(-0.032, 0.0, 0.0)));
+        assert!(close(right.origin, vector3::Point::new(0.032, 0.0, 0.0)));
+        // Both centre rays reach the same point on the screen plane
+        let meet = vector3::Point::new(0.0, 0.0, -3.0);
+        assert!(close(left.at(3.0 / -left.dir.z), meet));
+        assert!(close(right.at(3.0 / -right.dir.z), meet));
+
+        let over_under = StereoCamera::off_axis(&base, 0.064, StereoLayout::OverUnder);
+        assert!(over_under.get_ray(0.5, 0.75).unwrap().origin.x < 0.0);
+    }
+
+    #[test]
+    fn ods_eyes_sit_across_each_direction() {
+        let (from, at, up) = view();
+        let rig = StereoCamera::omnidirectional(from, at, up, 0.064, StereoLayout::OverUnder);
+        for x in [0.1, 0.4, 0.5, 0.9] {
+            let l = rig.get_ray(x, 0.75).unwrap();
+            let r = rig.get_ray(x, 0.25).unwrap();
+            assert!(close(l.dir, r.dir));
+            assert!((vector3::dot(l.origin, l.dir)).abs() < 1e-12);
+            assert!(((r.origin - l.origin).length() - 0.064).abs() < 1e-12);
+            // The right eye is to the right when looking along the ray
+            assert!(vector3::dot(vector3::cross(l.dir, up), r.origin - l.origin) > 0.0);
+        }
+    }
+
+    #[test]
+    fn cube_map_faces() {
+        let (from, at, up) = view();
+        let cross = CubeMapCamera::new(from, at, up, CubeLayout::Cross);
+        let centre = |x: f64, t: f64| cross.get_ray(x, t).unwrap().dir.unit_vector();
+        assert!(close(
+            centre(0.375, 0.5),
+            vector3::Vec3::new(0.0, 0.0, -1.0)
+        ));
+        assert!(close(centre(0.625, 0.5), vector3::Vec3::new(1.0, 0.0, 0.0)));
+        assert!(close(
+            centre(0.125, 0.5),
+            vector3::Vec3::new(-1.0, 0.0, 0.0)
+        ));
+        assert!(close(centre(0.875, 0.5), vector3::Vec3::new(0.0, 0.0, 1.0)));
+        assert!(close(
+            centre(0.375, 5.0 / 6.0),
+            vector3::Vec3::new(0.0, 1.0, 0.0)
+        ));
+        assert!(close(
+            centre(0.375, 1.0 / 6.0),
+            vector3::Vec3::new(0.0, -1.0, 0.0)
+        ));
+        assert!(cross.get_ray(0.1, 0.9).is_none());
+        // Up's bottom edge meets front's top edge
+        let seam = cross.get_ray(0.375, 2.0 / 3.0 + 1e-12).unwrap().dir;
+        assert!(close(
+            seam.unit_vector(),
+            vector3::Vec3::new(0.0, 1.0, -1.0).unit_vector()
+        ));
+
+        let strip = CubeMapCamera::new(from, at, up, CubeLayout::Strip);
+        let d = strip.get_ray(5.5 / 6.0, 0.5).unwrap().dir;
+        assert!(close(d.unit_vector(), vector3::Vec3::new(0.0, 0.0, -1.0)));
     }
 }
@@ -0,0 +1,168 @@
+use crate::aabb;
+use crate::hittable;
+use crate::material;
+use crate::onb;
+use crate::ray;
+use crate::utils;
+use crate::vector3;
+use std::sync::{Arc, Mutex};
+
+/// Cylinder from `p0` to `p1` with hemispherical ends.
+#[derive(Clone)]
+pub struct Capsule {
+    p0: vector3::Point,
+    frame: onb::Onb,
+    radius: f64,
+    height: f64,
+    material: Arc<Mutex<material::Material>>,
+}
+
+impl Capsule {
+    pub fn new(
+        p0: vector3::Point,
+        p1: vector3::Point,
+        radius: f64,
+        mat: Arc<Mutex<material::Material>>,
+    ) -> Capsule {
+        let axis = p1 - p0;
+        Capsule {
+            p0,
+            frame: onb::Onb::build_from_w(axis),
+            radius,
+            height: axis.length(),
+            material: mat,
+        }
+    }
+}
+
+impl hittable::Hittable for Capsule {
+    fn hit(&self, r: &ray::Ray, t_min: f64, t_max: f64) -> Option<hittable::HitRecord> {
+        let o = self.frame.to_local(r.origin - self.p0);
+        let d = self.frame.to_local(r.dir);
+        let r2 = self.radius * self.radius;
+        let mut closest = t_max;
+        // (t, local hit point, local outward normal)
+        let mut found: Option<(f64, vector3::Vec3, vector3::Vec3)> = None;
+
+        // Side, limited to the straight section
+        let a = d.x * d.x + d.y * d.y;
+        if a > 0.0 {
+            let half_b = o.x * d.x + o.y * d.y;
+            let c = o.x * o.x + o.y * o.y - r2;
+            let discriminant = half_b * half_b - a * c;
+            if discriminant >= 0.0 {
+                let sqrtd = discriminant.sqrt();
+                for root in [(-half_b - sqrtd) / a, (-half_b + sqrtd) / a] {
+                    if root < t_min || closest < root {
+                        continue;
+                    }
+                    let p = o + d * root;
+                    if p.z >= 0.0 && p.z <= self.height {
+                        let normal = vector3::Vec3::new(p.x, p.y, 0.0) / self.radius;
+                        found = Some((root, p, normal));
+                        closest = root;
+                        break;
+                    }
+                }
+            }
+        }
+
+        // End caps, each limited to the hemisphere facing away from the other end
+        let a = d.length_squared();
+        for (cz, outside) in [(0.0, -1.0), (self.height, 1.0)] {
+            let oc = o - vector3::Vec3::new(0.0, 0.0, cz);
+            let half_b = vector3::dot(oc, d);
+            let c = oc.length_squared() - r2;
+            let discriminant = half_b * half_b - a * c;
+            if discriminant < 0.0 {
+                continue;
+            }
+            let sqrtd = discriminant.sqrt();
+            for root in [(-half_b - sqrtd) / a, (-half_b + sqrtd) / a] {
+                if root < t_min || closest < root {
+                    continue;
+                }
+                let p = o + d * root;
+                if (p.z - cz) * outside >= 0.0 {
+                    let normal = (p - vector3::Vec3::new(0.0, 0.0, cz)) / self.radius;
+                    found = Some((root, p, normal));
+                    closest = root;
+                    break;
+                }
+            }
+        }
+
+        let (root, p, local_normal) = found?;
+        let mut hit_record = hittable::HitRecord {
+            p: r.at(root),
+            t: root,
+            u: utils::azimuth(p.x, p.y),
+            v: (p.z + self.radius) / (self.height + 2.0 * self.radius),
+            tangent: self.frame.local(utils::azimuth_tangent(p)),
+            normal: vector3::Vec3::new(0.0, 0.0, 0.0),
+            front_face: false,
+            material: self.material.clone(),
+        };
+        let outward_normal = self.frame.local(local_normal);
+        hit_record.set_face_normal(r, &outward_normal);
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        let p1 = self.p0 + self.frame.w * self.height;
+        let e = vector3::Vec3::new(self.radius, self.radius, self.radius);
+        Some(aabb::surrounding_box(
+            aabb::Aabb::new(self.p0 - e, self.p0 + e),
+            aabb::Aabb::new(p1 - e, p1 + e),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittable;
+    use crate::test_utils::{assert_vec, mat};
+
+    fn capsule() -> Capsule {
+        Capsule::new(
+            vector3::Point::new(0.0, 0.0, 0.0),
+            vector3::Point::new(2.0, 0.0, 0.0),
+            0.5,
+            mat(),
+        )
+    }
+
+    #[test]
+    fn hits_side() {
+        let r = ray::Ray::new(
+            vector3::Point::new(1.0, 5.0, 0.0),
+            vector3::Vec3::new(0.0, -1.0, 0.0),
+        );
+        let rec = capsule().hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 4.5).abs() < 1e-6);
+        assert_vec(rec.normal, 0.0, 1.0, 0.0);
+    }
+
+    #[test]
+    fn hits_rounded_end() {
+        let r = ray::Ray::new(
+            vector3::Point::new(5.0, 0.0, 0.0),
+            vector3::Vec3::new(-1.0, 0.0, 0.0),
+        );
+        let rec = capsule().hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 2.5).abs() < 1e-6);
+        assert_vec(rec.normal, 1.0, 0.0, 0.0);
+    }
+
+    #[test]
+    fn inside_hits_far_wall() {
+        let r = ray::Ray::new(
+            vector3::Point::new(1.0, 0.0, 0.0),
+            vector3::Vec3::new(-1.0, 0.0, 0.0),
+        );
+        let rec = capsule().hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 1.5).abs() < 1e-6);
+        assert!(!rec.front_face);
+    }
+}
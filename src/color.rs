@@ -1,7 +1,6 @@
 use crate::utils;
 use crate::vector3;
 use cast::u8;
-use std::u8;
 impl vector3::Color {
     pub fn get_color(self, samples_per_pixel: i32) -> image::Rgb<u8> {
         let ir: u8 = u8(255.0 * utils::clamp(self.x / samples_per_pixel as f64)).unwrap();
@@ -10,4 +9,17 @@ impl vector3::Color {
 
         image::Rgb([ir, ig, ib])
     }
+
+    /// Linear sRGB from CIE XYZ (D65 white).
+    pub fn from_xyz(x: f64, y: f64, z: f64) -> vector3::Color {
+        vector3::Color::new(
+            3.2406 * x - 1.5372 * y - 0.4986 * z,
+            -0.9689 * x + 1.8758 * y + 0.0415 * z,
+            0.0557 * x - 0.2040 * y + 1.0570 * z,
+        )
+    }
+
+    pub fn luminance(self) -> f64 {
+        0.2126 * self.x + 0.7152 * self.y + 0.0722 * self.z
+    }
 }
@@ -0,0 +1,168 @@
+use crate::aabb;
+use crate::hittable;
+use crate::material;
+use crate::onb;
+use crate::ray;
+use crate::utils;
+use crate::vector3;
+use std::sync::{Arc, Mutex};
+
+/// Finite cone with a flat base cap of `radius` at `base`, narrowing to a point at `apex`.
+#[derive(Clone)]
+pub struct Cone {
+    base: vector3::Point,
+    frame: onb::Onb,
+    radius: f64,
+    height: f64,
+    material: Arc<Mutex<material::Material>>,
+}
+
+impl Cone {
+    pub fn new(
+        base: vector3::Point,
+        apex: vector3::Point,
+        radius: f64,
+        mat: Arc<Mutex<material::Material>>,
+    ) -> Cone {
+        let axis = apex - base;
+        Cone {
+            base,
+            frame: onb::Onb::build_from_w(axis),
+            radius,
+            height: axis.length(),
+            material: mat,
+        }
+    }
+}
+
+impl hittable::Hittable for Cone {
+    fn hit(&self, r: &ray::Ray, t_min: f64, t_max: f64) -> Option<hittable::HitRecord> {
+        // In the cone's frame the base is at z = 0 and the apex at z = height, so
+        // the surface is x^2 + y^2 = k^2 (height - z)^2 with k = radius / height.
+        let o = self.frame.to_local(r.origin - self.base);
+        let d = self.frame.to_local(r.dir);
+        let k = self.radius / self.height;
+        let k2 = k * k;
+        let e = self.height - o.z;
+        let mut closest = t_max;
+        // (t, local outward normal, u, v)
+        let mut found: Option<(f64, vector3::Vec3, f64, f64)> = None;
+
+        // Side
+        let a = d.x * d.x + d.y * d.y - k2 * d.z * d.z;
+        let half_b = o.x * d.x + o.y * d.y + k2 * e * d.z;
+        let c = o.x * o.x + o.y * o.y - k2 * e * e;
+        let roots = if a.abs() < 1e-12 {
+            // Ray parallel to the slope: only one intersection.
+            if half_b == 0.0 {
+                vec![]
+            } else {
+                vec![-c / (2.0 * half_b)]
+            }
+        } else {
+            let discriminant = half_b * half_b - a * c;
+            if discriminant < 0.0 {
+                vec![]
+            } else {
+                let sqrtd = discriminant.sqrt();
+                let mut roots = vec![(-half_b - sqrtd) / a, (-half_b + sqrtd) / a];
+                roots.sort_by(|x, y| x.total_cmp(y));
+                roots
+            }
+        };
+        for root in roots {
+            if root < t_min || closest < root {
+                continue;
+            }
+            let p = o + d * root;
+            if p.z >= 0.0 && p.z <= self.height {
+                let rho = (p.x * p.x + p.y * p.y).sqrt();
+                let normal = if rho > 0.0 {
+                    vector3::Vec3::new(p.x / rho, p.y / rho, k).unit_vector()
+                } else {
+                    vector3::Vec3::new(0.0, 0.0, 1.0)
+                };
+                found = Some((root, normal, utils::azimuth(p.x, p.y), p.z / self.height));
+                closest = root;
+                break;
+            }
+        }
+
+        // Base cap
+        if d.z != 0.0 {
+            let root = -o.z / d.z;
+            if root >= t_min && root <= closest {
+                let p = o + d * root;
+                let rho = (p.x * p.x + p.y * p.y).sqrt();
+                if rho <= self.radius {
+                    let normal = vector3::Vec3::new(0.0, 0.0, -1.0);
+                    found = Some((root, normal, utils::azimuth(p.x, p.y), rho / self.radius));
+                }
+            }
+        }
+
+        let (root, local_normal, u, v) = found?;
+        let p = o + d * root;
+        let mut hit_record = hittable::HitRecord {
+            p: r.at(root),
+            t: root,
+            u,
+            v,
+            tangent: self.frame.local(utils::azimuth_tangent(p)),
+            normal: vector3::Vec3::new(0.0, 0.0, 0.0),
+            front_face: false,
+            material: self.material.clone(),
+        };
+        let outward_normal = self.frame.local(local_normal);
+        hit_record.set_face_normal(r, &outward_normal);
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        let apex = self.base + self.frame.w * self.height;
+        Some(aabb::surrounding_box(
+            aabb::Aabb::disk(self.base, self.frame.w, self.radius),
+            aabb::Aabb::new(apex, apex),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittable;
+    use crate::test_utils::{assert_vec, mat};
+
+    fn cone() -> Cone {
+        Cone::new(
+            vector3::Point::new(0.0, 0.0, 0.0),
+            vector3::Point::new(0.0, 1.0, 0.0),
+            1.0,
+            mat(),
+        )
+    }
+
+    #[test]
+    fn hits_side() {
+        // At height 0.5 the radius is 0.5, and the 45 degree slope tilts the normal up.
+        let r = ray::Ray::new(
+            vector3::Point::new(0.0, 0.5, 5.0),
+            vector3::Vec3::new(0.0, 0.0, -1.0),
+        );
+        let rec = cone().hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 4.5).abs() < 1e-6);
+        let s = 0.5_f64.sqrt();
+        assert_vec(rec.normal, 0.0, s, s);
+    }
+
+    #[test]
+    fn hits_base() {
+        let r = ray::Ray::new(
+            vector3::Point::new(0.5, -3.0, 0.0),
+            vector3::Vec3::new(0.0, 1.0, 0.0),
+        );
+        let rec = cone().hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 3.0).abs() < 1e-6);
+        assert_vec(rec.normal, 0.0, -1.0, 0.0);
+    }
+}
@@ -0,0 +1,174 @@
+use crate::aabb;
+use crate::hittable;
+use crate::ray;
+
+#[derive(Copy, Clone)]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    Difference,
+}
+
+impl CsgOp {
+    fn inside(self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOp::Union => in_left || in_right,
+            CsgOp::Intersection => in_left && in_right,
+            CsgOp::Difference => in_left && !in_right,
+        }
+    }
+}
+
+/// Boolean combination of two closed objects. Surfaces keep the material of
+/// the object they came from, so a hole cut by `Difference` takes the cutter's.
+#[derive(Clone)]
+pub struct Csg {
+    left: Box<hittable::HittableObj>,
+    right: Box<hittable::HittableObj>,
+    op: CsgOp,
+}
+
+impl Csg {
+    pub fn new(left: hittable::HittableObj, right: hittable::HittableObj, op: CsgOp) -> Csg {
+        Csg {
+            left: Box::new(left),
+            right: Box::new(right),
+            op,
+        }
+    }
+}
+
+impl hittable::Hittable for Csg {
+    fn hit(&self, r: &ray::Ray, t_min: f64, t_max: f64) -> Option<hittable::HitRecord> {
+        for interval in self.intervals(r) {
+            for (mut rec, entering) in [(interval.enter, true), (interval.exit, false)] {
+                if rec.t > t_max {
+                    return None;
+                }
+                if rec.t >= t_min {
+                    // Component records already face the ray; only the side of the
+                    // combined solid can differ, e.g. the inside of a drilled hole.
+                    rec.front_face = entering;
+                    return Some(rec);
+                }
+            }
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        match self.op {
+            CsgOp::Union => Some(aabb::surrounding_box(
+                self.left.bounding_box()?,
+                self.right.bounding_box()?,
+            )),
+            CsgOp::Intersection | CsgOp::Difference => self.left.bounding_box(),
+        }
+    }
+
+    fn intervals(&self, r: &ray::Ray) -> Vec<hittable::Interval> {
+        // Sweep over every boundary of both operands in order, tracking which
+        // operands the ray is inside and emitting the spans where `op` holds.
+        let mut events: Vec<(hittable::HitRecord, bool)> = Vec::new();
+        for interval in self.left.intervals(r) {
+            events.push((interval.enter, true));
+            events.push((interval.exit, true));
+        }
+        for interval in self.right.intervals(r) {
+            events.push((interval.enter, false));
+            events.push((interval.exit, false));
+        }
+        events.sort_by(|a, b| a.0.t.total_cmp(&b.0.t));
+
+        let mut intervals = Vec::new();
+        let mut in_left = false;
+        let mut in_right = false;
+        let mut enter: Option<hittable::HitRecord> = None;
+        for (rec, from_left) in events {
+            let was_inside = self.op.inside(in_left, in_right);
+            if from_left {
+                in_left = !in_left;
+            } else {
+                in_right = !in_right;
+            }
+            let is_inside = self.op.inside(in_left, in_right);
+
+            if is_inside && !was_inside {
+                enter = Some(rec);
+            } else if was_inside && !is_inside {
+                if let Some(e) = enter.take() {
+                    intervals.push(hittable::Interval {
+                        enter: e,
+                        exit: rec,
+                    });
+                }
+            }
+        }
+        intervals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cylinder;
+    use crate::hittable::Hittable;
+    use crate::sphere;
+    use crate::test_utils::mat;
+    use crate::vector3;
+
+    fn ball() -> hittable::HittableObj {
+        hittable::HittableObj::Sphere(sphere::Sphere::new(
+            vector3::Point::new(0.0, 0.0, 0.0),
+            1.0,
+            mat(),
+        ))
+    }
+
+    fn drill() -> hittable::HittableObj {
+        hittable::HittableObj::Cylinder(cylinder::Cylinder::new(
+            vector3::Point::new(0.0, 0.0, -2.0),
+            vector3::Point::new(0.0, 0.0, 2.0),
+            0.5,
+            mat(),
+        ))
+    }
+
+    #[test]
+    fn difference_drills_hole() {
+        let drilled = Csg::new(ball(), drill(), CsgOp::Difference);
+
+        let down_the_hole = ray::Ray::new(
+            vector3::Point::new(0.0, 0.0, 5.0),
+            vector3::Vec3::new(0.0, 0.0, -1.0),
+        );
+        assert!(drilled.hit(&down_the_hole, 0.001, f64::INFINITY).is_none());
+
+        // Across the hole: in through the ball, out into the hole's wall.
+        let across = ray::Ray::new(
+            vector3::Point::new(5.0, 0.0, 0.0),
+            vector3::Vec3::new(-1.0, 0.0, 0.0),
+        );
+        let intervals = drilled.intervals(&across);
+        assert_eq!(intervals.len(), 2);
+        assert!((intervals[0].enter.t - 4.0).abs() < 1e-6);
+        assert!((intervals[0].exit.t - 4.5).abs() < 1e-6);
+
+        let rec = drilled.hit(&across, 4.2, f64::INFINITY).unwrap();
+        assert!((rec.t - 4.5).abs() < 1e-6);
+        assert!(!rec.front_face);
+        assert!((rec.normal.x - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn intersection_clips_to_overlap() {
+        let lens = Csg::new(ball(), drill(), CsgOp::Intersection);
+        let r = ray::Ray::new(
+            vector3::Point::new(5.0, 0.0, 0.0),
+            vector3::Vec3::new(-1.0, 0.0, 0.0),
+        );
+        let rec = lens.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 4.5).abs() < 1e-6);
+        assert!(rec.front_face);
+    }
+}
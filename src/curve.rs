@@ -0,0 +1,312 @@
+use crate::aabb;
+use crate::hittable;
+use crate::material;
+use crate::onb;
+use crate::ray;
+use crate::vector3;
+use std::fs;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+/// Cubic Bézier curve swept into a ribbon that always faces the ray, with a
+/// width blended linearly from `width0` at the start to `width1` at the end.
+/// Normals are bent across the ribbon so it shades like a thin cylinder.
+/// Intersection follows pbrt's recursive subdivision in ray space.
+#[derive(Clone)]
+pub struct Curve {
+    cp: [vector3::Point; 4],
+    width0: f64,
+    width1: f64,
+    material: Arc<Mutex<material::Material>>,
+}
+
+impl Curve {
+    pub fn new(
+        cp: [vector3::Point; 4],
+        width0: f64,
+        width1: f64,
+        mat: Arc<Mutex<material::Material>>,
+    ) -> Curve {
+        Curve {
+            cp,
+            width0,
+            width1,
+            material: mat,
+        }
+    }
+
+    fn width(&self, u: f64) -> f64 {
+        self.width0 * (1.0 - u) + self.width1 * u
+    }
+
+    fn recursive_intersect(
+        &self,
+        cp: [vector3::Point; 4],
+        u0: f64,
+        u1: f64,
+        depth: u32,
+        z_min: f64,
+        z_max: f64,
+    ) -> Option<(f64, f64, f64)> {
+        if depth > 0 {
+            let halves = split_bezier(cp);
+            let mut closest = z_max;
+            let mut found = None;
+            let u_mid = 0.5 * (u0 + u1);
+            for (half, (a, b)) in halves.iter().zip([(u0, u_mid), (u_mid, u1)]) {
+                // Reject halves whose width-padded bounds miss the ray, which runs
+                // along +z through x = y = 0 in ray space.
+                let pad = 0.5 * self.width(a).max(self.width(b));
+                let lo = half.iter().fold(
+                    vector3::Vec3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+                    |m, p| vector3::Vec3::new(m.x.min(p.x), m.y.min(p.y), m.z.min(p.z)),
+                ) - pad;
+                let hi = half.iter().fold(
+                    vector3::Vec3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+                    |m, p| vector3::Vec3::new(m.x.max(p.x), m.y.max(p.y), m.z.max(p.z)),
+                ) + pad;
+                if lo.x > 0.0
+                    || hi.x < 0.0
+                    || lo.y > 0.0
+                    || hi.y < 0.0
+                    || hi.z < z_min
+                    || lo.z > closest
+                {
+                    continue;
+                }
+                if let Some(hit) = self.recursive_intersect(*half, a, b, depth - 1, z_min, closest)
+                {
+                    closest = hit.0;
+                    found = Some(hit);
+                }
+            }
+            return found;
+        }
+
+        // Leaf: treat the segment as a line and test against its end caps first.
+        let edge = (cp[1].y - cp[0].y) * -cp[0].y + cp[0].x * (cp[0].x - cp[1].x);
+        if edge < 0.0 {
+            return None;
+        }
+        let edge = (cp[2].y - cp[3].y) * -cp[3].y + cp[3].x * (cp[3].x - cp[2].x);
+        if edge < 0.0 {
+            return None;
+        }
+
+        let seg = vector3::Vec3::new(cp[3].x - cp[0].x, cp[3].y - cp[0].y, 0.0);
+        let denom = seg.length_squared();
+        if denom == 0.0 {
+            return None;
+        }
+        let w = (-cp[0].x * seg.x - cp[0].y * seg.y) / denom;
+        let u = (u0 + (u1 - u0) * w).clamp(u0, u1);
+        let hit_width = self.width(u);
+        let (pc, dpcdw) = eval_bezier(cp, w.clamp(0.0, 1.0));
+        let dist2 = pc.x * pc.x + pc.y * pc.y;
+        if dist2 > hit_width * hit_width * 0.25 || pc.z < z_min || pc.z > z_max {
+            return None;
+        }
+
+        // v runs across the ribbon, 0.5 on the centerline
+        let dist = dist2.sqrt();
+        let edge_func = dpcdw.x * -pc.y + pc.x * dpcdw.y;
+        let v = if edge_func > 0.0 {
+            0.5 + dist / hit_width
+        } else {
+            0.5 - dist / hit_width
+        };
+        Some((pc.z, u, v))
+    }
+}
+
+fn lerp(t: f64, a: vector3::Point, b: vector3::Point) -> vector3::Point {
+    a * (1.0 - t) + b * t
+}
+
+fn split_bezier(cp: [vector3::Point; 4]) -> [[vector3::Point; 4]; 2] {
+    let p01 = lerp(0.5, cp[0], cp[1]);
+    let p12 = lerp(0.5, cp[1], cp[2]);
+    let p23 = lerp(0.5, cp[2], cp[3]);
+    let p012 = lerp(0.5, p01, p12);
+    let p123 = lerp(0.5, p12, p23);
+    let mid = lerp(0.5, p012, p123);
+    [[cp[0], p01, p012, mid], [mid, p123, p23, cp[3]]]
+}
+
+/// Point and derivative of the curve at `u`.
+fn eval_bezier(cp: [vector3::Point; 4], u: f64) -> (vector3::Point, vector3::Vec3) {
+    let cp1 = [
+        lerp(u, cp[0], cp[1]),
+        lerp(u, cp[1], cp[2]),
+        lerp(u, cp[2], cp[3]),
+    ];
+    let cp2 = [lerp(u, cp1[0], cp1[1]), lerp(u, cp1[1], cp1[2])];
+    let deriv = if (cp2[1] - cp2[0]).length_squared() > 0.0 {
+        (cp2[1] - cp2[0]) * 3.0
+    } else {
+        // Coincident control points: fall back to the chord
+        cp[3] - cp[0]
+    };
+    (lerp(u, cp2[0], cp2[1]), deriv)
+}
+
+impl hittable::Hittable for Curve {
+    fn hit(&self, r: &ray::Ray, t_min: f64, t_max: f64) -> Option<hittable::HitRecord> {
+        // Ray space: the ray starts at the origin and runs along +z.
+        let frame = onb::Onb::build_from_w(r.dir);
+        let cp = self.cp.map(|p| frame.to_local(p - r.origin));
+        let ray_length = r.dir.length();
+
+        // Subdivide until the segments are close enough to straight lines.
+        let mut l0: f64 = 0.0;
+        for i in 0..2 {
+            let d = cp[i] - cp[i + 1] * 2.0 + cp[i + 2];
+            l0 = l0.max(d.x.abs()).max(d.y.abs()).max(d.z.abs());
+        }
+        let eps = self.width0.max(self.width1) * 0.05;
+        let depth = if l0 > 0.0 && eps > 0.0 {
+            ((2.0_f64.sqrt() * 6.0 * l0 / (8.0 * eps)).log2() / 2.0).clamp(0.0, 10.0) as u32
+        } else {
+            0
+        };
+
+        let (z, u, v) =
+            self.recursive_intersect(cp, 0.0, 1.0, depth, t_min * ray_length, t_max * ray_length)?;
+        let root = z / ray_length;
+
+        // Face the ray, then rotate around the tangent by how far across the
+        // ribbon we are, as if we'd hit a cylinder.
+        let (_, dpdu) = eval_bezier(self.cp, u);
+        let tangent = dpdu.unit_vector();
+        let to_eye = r.dir.unit_vector() * -1.0;
+        let facing = (to_eye - tangent * vector3::dot(to_eye, tangent)).unit_vector();
+        let across = vector3::cross(tangent, facing);
+        let theta = (v - 0.5) * std::f64::consts::PI;
+        let outward_normal = facing * theta.cos() + across * theta.sin();
+
+        let mut hit_record = hittable::HitRecord {
+            p: r.at(root),
+            t: root,
+            u,
+            v,
+            tangent,
+            normal: vector3::Vec3::new(0.0, 0.0, 0.0),
+            front_face: false,
+            material: self.material.clone(),
+        };
+        hit_record.set_face_normal(r, &outward_normal);
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        // The curve stays within the convex hull of its control points.
+        let mut output_box = aabb::Aabb::new(self.cp[0], self.cp[0]);
+        for p in &self.cp[1..] {
+            output_box = aabb::surrounding_box(output_box, aabb::Aabb::new(*p, *p));
+        }
+        Some(output_box.pad(0.5 * self.width0.max(self.width1)))
+    }
+}
+
+/// Reads curves from a text file with one segment per line:
+/// `x0 y0 z0 x1 y1 z1 x2 y2 z2 x3 y3 z3 width0 [width1]`.
+/// Blank lines and lines starting with `#` are skipped.
+pub fn load_curves(path: &str, mat: Arc<Mutex<material::Material>>) -> io::Result<Vec<Curve>> {
+    let contents = fs::read_to_string(path)?;
+    let mut curves = Vec::new();
+    for (line_no, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let values: Vec<f64> = line
+            .split_whitespace()
+            .map(|x| x.parse::<f64>())
+            .collect::<Result<_, _>>()
+            .map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}:{}: {}", path, line_no + 1, e),
+                )
+            })?;
+        if values.len() != 13 && values.len() != 14 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{}:{}: expected 13 or 14 numbers, found {}",
+                    path,
+                    line_no + 1,
+                    values.len()
+                ),
+            ));
+        }
+        let cp = [0, 1, 2, 3]
+            .map(|i| vector3::Point::new(values[3 * i], values[3 * i + 1], values[3 * i + 2]));
+        let width0 = values[12];
+        let width1 = *values.get(13).unwrap_or(&width0);
+        curves.push(Curve::new(cp, width0, width1, mat.clone()));
+    }
+    Ok(curves)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittable;
+    use crate::test_utils::mat;
+
+    #[test]
+    fn hits_straight_strand() {
+        // A straight strand along x, tapering from 0.2 to 0.0
+        let curve = Curve::new(
+            [
+                vector3::Point::new(-1.0, 0.0, 0.0),
+                vector3::Point::new(-1.0 / 3.0, 0.0, 0.0),
+                vector3::Point::new(1.0 / 3.0, 0.0, 0.0),
+                vector3::Point::new(1.0, 0.0, 0.0),
+            ],
+            0.2,
+            0.0,
+            mat(),
+        );
+        let r = ray::Ray::new(
+            vector3::Point::new(-0.5, 0.0, 5.0),
+            vector3::Vec3::new(0.0, 0.0, -1.0),
+        );
+        let rec = curve.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 5.0).abs() < 1e-6);
+        assert!((rec.u - 0.25).abs() < 1e-6);
+        assert!((rec.normal.z - 1.0).abs() < 1e-6);
+        assert!((rec.tangent.x - 1.0).abs() < 1e-6);
+
+        // Width at u = 0.75 is 0.05, so 0.04 off the centerline misses.
+        let r = ray::Ray::new(
+            vector3::Point::new(0.5, 0.04, 5.0),
+            vector3::Vec3::new(0.0, 0.0, -1.0),
+        );
+        assert!(curve.hit(&r, 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn hits_bent_strand() {
+        let curve = Curve::new(
+            [
+                vector3::Point::new(0.0, 0.0, 0.0),
+                vector3::Point::new(0.0, 1.0, 0.0),
+                vector3::Point::new(1.0, 1.0, 0.0),
+                vector3::Point::new(1.0, 2.0, 0.0),
+            ],
+            0.1,
+            0.1,
+            mat(),
+        );
+        // By symmetry the curve passes through (0.5, 1.0) at u = 0.5.
+        let r = ray::Ray::new(
+            vector3::Point::new(0.5, 1.0, -3.0),
+            vector3::Vec3::new(0.0, 0.0, 1.0),
+        );
+        let rec = curve.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 3.0).abs() < 1e-6);
+        assert!((rec.u - 0.5).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,160 @@
+use crate::aabb;
+use crate::hittable;
+use crate::material;
+use crate::onb;
+use crate::ray;
+use crate::utils;
+use crate::vector3;
+use std::sync::{Arc, Mutex};
+
+/// Finite cylinder running from `base` to `top`, closed by two flat caps.
+#[derive(Clone)]
+pub struct Cylinder {
+    base: vector3::Point,
+    frame: onb::Onb,
+    radius: f64,
+    height: f64,
+    material: Arc<Mutex<material::Material>>,
+}
+
+impl Cylinder {
+    pub fn new(
+        base: vector3::Point,
+        top: vector3::Point,
+        radius: f64,
+        mat: Arc<Mutex<material::Material>>,
+    ) -> Cylinder {
+        let axis = top - base;
+        Cylinder {
+            base,
+            frame: onb::Onb::build_from_w(axis),
+            radius,
+            height: axis.length(),
+            material: mat,
+        }
+    }
+}
+
+impl hittable::Hittable for Cylinder {
+    fn hit(&self, r: &ray::Ray, t_min: f64, t_max: f64) -> Option<hittable::HitRecord> {
+        // Work in the cylinder's frame, where the axis is +z and the base sits at the origin.
+        let o = self.frame.to_local(r.origin - self.base);
+        let d = self.frame.to_local(r.dir);
+        let mut closest = t_max;
+        // (t, local outward normal, u, v)
+        let mut found: Option<(f64, vector3::Vec3, f64, f64)> = None;
+
+        // Side
+        let a = d.x * d.x + d.y * d.y;
+        if a > 0.0 {
+            let half_b = o.x * d.x + o.y * d.y;
+            let c = o.x * o.x + o.y * o.y - self.radius * self.radius;
+            let discriminant = half_b * half_b - a * c;
+            if discriminant >= 0.0 {
+                let sqrtd = discriminant.sqrt();
+                for root in [(-half_b - sqrtd) / a, (-half_b + sqrtd) / a] {
+                    if root < t_min || closest < root {
+                        continue;
+                    }
+                    let p = o + d * root;
+                    if p.z >= 0.0 && p.z <= self.height {
+                        let normal = vector3::Vec3::new(p.x, p.y, 0.0) / self.radius;
+                        found = Some((root, normal, utils::azimuth(p.x, p.y), p.z / self.height));
+                        closest = root;
+                        break;
+                    }
+                }
+            }
+        }
+
+        // Caps
+        if d.z != 0.0 {
+            for (z, nz) in [(0.0, -1.0), (self.height, 1.0)] {
+                let root = (z - o.z) / d.z;
+                if root < t_min || closest < root {
+                    continue;
+                }
+                let p = o + d * root;
+                let rho = (p.x * p.x + p.y * p.y).sqrt();
+                if rho <= self.radius {
+                    let normal = vector3::Vec3::new(0.0, 0.0, nz);
+                    found = Some((root, normal, utils::azimuth(p.x, p.y), rho / self.radius));
+                    closest = root;
+                }
+            }
+        }
+
+        let (root, local_normal, u, v) = found?;
+        let p = o + d * root;
+        let mut hit_record = hittable::HitRecord {
+            p: r.at(root),
+            t: root,
+            u,
+            v,
+            tangent: self.frame.local(utils::azimuth_tangent(p)),
+            normal: vector3::Vec3::new(0.0, 0.0, 0.0),
+            front_face: false,
+            material: self.material.clone(),
+        };
+        let outward_normal = self.frame.local(local_normal);
+        hit_record.set_face_normal(r, &outward_normal);
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        let top = self.base + self.frame.w * self.height;
+        Some(aabb::surrounding_box(
+            aabb::Aabb::disk(self.base, self.frame.w, self.radius),
+            aabb::Aabb::disk(top, self.frame.w, self.radius),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittable;
+    use crate::test_utils::{assert_vec, mat};
+
+    fn cylinder() -> Cylinder {
+        Cylinder::new(
+            vector3::Point::new(0.0, 0.0, 0.0),
+            vector3::Point::new(0.0, 2.0, 0.0),
+            1.0,
+            mat(),
+        )
+    }
+
+    #[test]
+    fn hits_side() {
+        let r = ray::Ray::new(
+            vector3::Point::new(0.0, 1.0, 5.0),
+            vector3::Vec3::new(0.0, 0.0, -1.0),
+        );
+        let rec = cylinder().hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-6);
+        assert_vec(rec.normal, 0.0, 0.0, 1.0);
+        assert!((rec.v - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn hits_cap() {
+        let r = ray::Ray::new(
+            vector3::Point::new(0.5, 10.0, 0.0),
+            vector3::Vec3::new(0.0, -1.0, 0.0),
+        );
+        let rec = cylinder().hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 8.0).abs() < 1e-6);
+        assert_vec(rec.normal, 0.0, 1.0, 0.0);
+        assert!(rec.front_face);
+    }
+
+    #[test]
+    fn misses_beyond_height() {
+        let r = ray::Ray::new(
+            vector3::Point::new(0.0, 3.0, 5.0),
+            vector3::Vec3::new(0.0, 0.0, -1.0),
+        );
+        assert!(cylinder().hit(&r, 0.001, f64::INFINITY).is_none());
+    }
+}
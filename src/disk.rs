@@ -0,0 +1,192 @@
+use crate::aabb;
+use crate::hittable;
+use crate::material;
+use crate::onb;
+use crate::ray;
+use crate::utils;
+use crate::vector3;
+use std::sync::{Arc, Mutex};
+
+/// Flat circular disk facing `normal`.
+#[derive(Clone)]
+pub struct Disk {
+    center: vector3::Point,
+    frame: onb::Onb,
+    radius: f64,
+    material: Arc<Mutex<material::Material>>,
+}
+
+/// Flat ring between `inner_radius` and `outer_radius`, facing `normal`.
+#[derive(Clone)]
+pub struct Annulus {
+    center: vector3::Point,
+    frame: onb::Onb,
+    inner_radius: f64,
+    outer_radius: f64,
+    material: Arc<Mutex<material::Material>>,
+}
+
+impl Disk {
+    pub fn new(
+        center: vector3::Point,
+        normal: vector3::Vec3,
+        radius: f64,
+        mat: Arc<Mutex<material::Material>>,
+    ) -> Disk {
+        Disk {
+            center,
+            frame: onb::Onb::build_from_w(normal),
+            radius,
+            material: mat,
+        }
+    }
+}
+
+impl Annulus {
+    pub fn new(
+        center: vector3::Point,
+        normal: vector3::Vec3,
+        inner_radius: f64,
+        outer_radius: f64,
+        mat: Arc<Mutex<material::Material>>,
+    ) -> Annulus {
+        Annulus {
+            center,
+            frame: onb::Onb::build_from_w(normal),
+            inner_radius,
+            outer_radius,
+            material: mat,
+        }
+    }
+}
+
+/// Intersects the plane through `center` spanned by `frame.u` and `frame.v`.
+/// Returns the ray parameter and the hit point in the plane's local coordinates.
+fn hit_plane(
+    center: vector3::Point,
+    frame: &onb::Onb,
+    r: &ray::Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, vector3::Vec3)> {
+    let o = frame.to_local(r.origin - center);
+    let d = frame.to_local(r.dir);
+    if d.z == 0.0 {
+        return None;
+    }
+    let root = -o.z / d.z;
+    if root < t_min || t_max < root {
+        return None;
+    }
+    Some((root, o + d * root))
+}
+
+impl hittable::Hittable for Disk {
+    fn hit(&self, r: &ray::Ray, t_min: f64, t_max: f64) -> Option<hittable::HitRecord> {
+        let (root, p) = hit_plane(self.center, &self.frame, r, t_min, t_max)?;
+        let rho = (p.x * p.x + p.y * p.y).sqrt();
+        if rho > self.radius {
+            return None;
+        }
+        let mut hit_record = hittable::HitRecord {
+            p: r.at(root),
+            t: root,
+            u: utils::azimuth(p.x, p.y),
+            v: rho / self.radius,
+            tangent: self.frame.local(utils::azimuth_tangent(p)),
+            normal: vector3::Vec3::new(0.0, 0.0, 0.0),
+            front_face: false,
+            material: self.material.clone(),
+        };
+        hit_record.set_face_normal(r, &self.frame.w);
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        // Pad so the box never has zero thickness along the normal.
+        Some(aabb::Aabb::disk(self.center, self.frame.w, self.radius).pad(0.0001))
+    }
+}
+
+impl hittable::Hittable for Annulus {
+    fn hit(&self, r: &ray::Ray, t_min: f64, t_max: f64) -> Option<hittable::HitRecord> {
+        let (root, p) = hit_plane(self.center, &self.frame, r, t_min, t_max)?;
+        let rho = (p.x * p.x + p.y * p.y).sqrt();
+        if rho < self.inner_radius || rho > self.outer_radius {
+            return None;
+        }
+        let mut hit_record = hittable::HitRecord {
+            p: r.at(root),
+            t: root,
+            u: utils::azimuth(p.x, p.y),
+            v: (rho - self.inner_radius) / (self.outer_radius - self.inner_radius),
+            tangent: self.frame.local(utils::azimuth_tangent(p)),
+            normal: vector3::Vec3::new(0.0, 0.0, 0.0),
+            front_face: false,
+            material: self.material.clone(),
+        };
+        hit_record.set_face_normal(r, &self.frame.w);
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        Some(aabb::Aabb::disk(self.center, self.frame.w, self.outer_radius).pad(0.0001))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittable;
+    use crate::test_utils::mat;
+
+    #[test]
+    fn disk_hit_and_miss() {
+        let disk = Disk::new(
+            vector3::Point::new(0.0, 0.0, 0.0),
+            vector3::Vec3::new(0.0, 0.0, 1.0),
+            1.0,
+            mat(),
+        );
+        let r = ray::Ray::new(
+            vector3::Point::new(0.5, 0.0, 2.0),
+            vector3::Vec3::new(0.0, 0.0, -1.0),
+        );
+        let rec = disk.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-6);
+        assert!((rec.normal.z - 1.0).abs() < 1e-6);
+        assert!((rec.v - 0.5).abs() < 1e-6);
+
+        let r = ray::Ray::new(
+            vector3::Point::new(1.5, 0.0, 2.0),
+            vector3::Vec3::new(0.0, 0.0, -1.0),
+        );
+        assert!(disk.hit(&r, 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn annulus_hole() {
+        let ring = Annulus::new(
+            vector3::Point::new(0.0, 0.0, 0.0),
+            vector3::Vec3::new(0.0, 0.0, 1.0),
+            0.5,
+            1.0,
+            mat(),
+        );
+        let through_hole = ray::Ray::new(
+            vector3::Point::new(0.25, 0.0, 2.0),
+            vector3::Vec3::new(0.0, 0.0, -1.0),
+        );
+        assert!(ring.hit(&through_hole, 0.001, f64::INFINITY).is_none());
+
+        // Hit from behind: the normal flips to face the ray.
+        let from_below = ray::Ray::new(
+            vector3::Point::new(0.75, 0.0, -3.0),
+            vector3::Vec3::new(0.0, 0.0, 1.0),
+        );
+        let rec = ring.hit(&from_below, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 3.0).abs() < 1e-6);
+        assert!((rec.normal.z + 1.0).abs() < 1e-6);
+        assert!(!rec.front_face);
+    }
+}
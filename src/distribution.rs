@@ -0,0 +1,176 @@
+/// Piecewise-constant density over [0, 1] with one step per entry of `func`.
+#[derive(Clone)]
+pub struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>,
+    integral: f64,
+}
+
+impl Distribution1D {
+    /// Negative values count as their magnitude. If every value is zero, samples
+    /// are spread evenly but every density is zero.
+    pub fn new(func: Vec<f64>) -> Distribution1D {
+        let n = func.len().max(1);
+        let mut func: Vec<f64> = func.iter().map(|f| f.abs()).collect();
+        func.resize(n, 0.0);
+        let mut cdf = vec![0.0; n + 1];
+        for i in 0..n {
+            cdf[i + 1] = cdf[i] + func[i] / n as f64;
+        }
+        let integral = cdf[n];
+        if integral == 0.0 {
+            for (i, c) in cdf.iter_mut().enumerate() {
+                *c = i as f64 / n as f64;
+            }
+        } else {
+            for c in cdf.iter_mut() {
+                *c /= integral;
+            }
+        }
+        Distribution1D {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    /// Integral of `func` over [0, 1].
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    /// Density over [0, 1] anywhere in step `i`.
+    fn density(&self, i: usize) -> f64 {
+        if self.integral == 0.0 {
+            return 0.0;
+        }
+        self.func[i] / self.integral
+    }
+
+    /// Index of the step that `u` in [0, 1) falls into, by the cdf.
+    fn find(&self, u: f64) -> usize {
+        let i = self.cdf.partition_point(|&c| c <= u);
+        i.saturating_sub(1).min(self.count() - 1)
+    }
+
+    /// Returns a point in [0, 1), its density and the step it lies in.
+    pub fn sample_continuous(&self, u: f64) -> (f64, f64, usize) {
+        let i = self.find(u);
+        let width = self.cdf[i + 1] - self.cdf[i];
+        let du = if width > 0.0 {
+            (u - self.cdf[i]) / width
+        } else {
+            0.0
+        };
+        let x = ((i as f64 + du) / self.count() as f64).min(1.0 - f64::EPSILON);
+        (x, self.density(i), i)
+    }
+
+    /// Returns a step index and the probability of picking it.
+    pub fn sample_discrete(&self, u: f64) -> (usize, f64) {
+        let i = self.find(u);
+        (i, self.discrete_pdf(i))
+    }
+
+    pub fn discrete_pdf(&self, i: usize) -> f64 {
+        self.density(i) / self.count() as f64
+    }
+
+    /// Density of `sample_continuous` at `x`.
+    pub fn pdf(&self, x: f64) -> f64 {
+        let i = ((x * self.count() as f64) as usize).min(self.count() - 1);
+        self.density(i)
+    }
+}
+
+/// Piecewise-constant density over the unit square: a marginal distribution over
+/// rows and, for each row, a conditional one along it.
+#[derive(Clone)]
+pub struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    /// `func` holds `nv` rows of `nu` values each.
+    pub fn new(func: &[f64], nu: usize, nv: usize) -> Distribution2D {
+        let conditional: Vec<Distribution1D> = (0..nv)
+            .map(|v| Distribution1D::new(func[v * nu..(v + 1) * nu].to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(conditional.iter().map(|d| d.integral()).collect());
+        Distribution2D {
+            conditional,
+            marginal,
+        }
+    }
+
+    /// Returns a point `[u, v]` in the unit square and its density.
+    pub fn sample(&self, u: [f64; 2]) -> ([f64; 2], f64) {
+        let (v, pdf_v, row) = self.marginal.sample_continuous(u[1]);
+        let (x, pdf_u, _) = self.conditional[row].sample_continuous(u[0]);
+        ([x, v], pdf_u * pdf_v)
+    }
+
+    pub fn pdf(&self, p: [f64; 2]) -> f64 {
+        let nv = self.conditional.len();
+        let row = ((p[1] * nv as f64) as usize).min(nv - 1);
+        self.marginal.pdf(p[1]) * self.conditional[row].pdf(p[0])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_dimensional_sampling() {
+        let d = Distribution1D::new(vec![1.0, 3.0, 0.0, 4.0]);
+        assert!((d.integral() - 2.0).abs() < 1e-12);
+        let (x, pdf, i) = d.sample_continuous(0.3);
+        assert_eq!(i, 1);
+        assert!((pdf - 1.5).abs() < 1e-12 && (d.pdf(x) - pdf).abs() < 1e-12);
+        // The empty step is never chosen
+        for k in 0..100 {
+            let (i, p) = d.sample_discrete(k as f64 / 100.0);
+            assert_ne!(i, 2);
+            assert!((p - d.discrete_pdf(i)).abs() < 1e-12);
+        }
+        assert_eq!(d.sample_discrete(0.99).0, 3);
+    }
+
+    #[test]
+    fn two_dimensional_pdf_matches_sample() {
+        let func = [0.0, 1.0, 2.0, 5.0, 0.5, 0.0];
+        let d = Distribution2D::new(&func, 3, 2);
+        for k in 0..50 {
+            let u = [(k as f64 * 0.37) % 1.0, (k as f64 + 0.5) / 50.0];
+            let (p, pdf) = d.sample(u);
+            assert!(pdf > 0.0);
+            assert!((d.pdf(p) - pdf).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn black_row_is_never_sampled() {
+        let func = [0.0, 0.0, 0.0, 1.0, 2.0, 3.0];
+        let d = Distribution2D::new(&func, 3, 2);
+        for k in 0..100 {
+            let u = [(k as f64 * 0.37) % 1.0, k as f64 / 100.0];
+            let ([_, v], pdf) = d.sample(u);
+            assert!(v >= 0.5);
+            assert!(pdf > 0.0);
+        }
+        assert_eq!(d.pdf([0.5, 0.25]), 0.0);
+
+        // Sampling still lands somewhere when there's nothing to favour
+        let black = Distribution1D::new(vec![0.0; 4]);
+        assert_eq!(black.integral(), 0.0);
+        let (x, pdf, _) = black.sample_continuous(0.6);
+        assert!((x - 0.6).abs() < 1e-12);
+        assert_eq!((pdf, black.pdf(x), black.discrete_pdf(2)), (0.0, 0.0, 0.0));
+    }
+}
@@ -0,0 +1,177 @@
+use crate::distribution;
+use crate::light;
+use crate::ray;
+use crate::sphere;
+use crate::texture;
+use crate::vector3;
+use std::f64::consts::PI;
+
+/// Light from every direction, read from an equirectangular (latitude-longitude)
+/// map. The top row looks straight up and the middle column looks along +x, as
+/// with sphere texture coordinates. Directions are importance sampled by the
+/// map's luminance.
+pub struct EnvironmentLight {
+    map: texture::ImageTexture,
+    intensity: f64,
+    /// Rotation around +y, in radians
+    rotation: f64,
+    distribution: distribution::Distribution2D,
+}
+
+impl EnvironmentLight {
+    /// `intensity` scales the map; `rotation` turns it around the up axis, in degrees.
+    pub fn new(map: texture::ImageTexture, intensity: f64, rotation: f64) -> EnvironmentLight {
+        let (w, h) = (map.width(), map.height());
+        // Rows near the poles cover less solid angle
+        let mut func = Vec::with_capacity(w * h);
+        for j in 0..h {
+            let sin_theta = (PI * (j as f64 + 0.5) / h as f64).sin();
+            for i in 0..w {
+                func.push(map.texel(i as i64, j as i64).luminance() * sin_theta);
+            }
+        }
+        EnvironmentLight {
+            distribution: distribution::Distribution2D::new(&func, w, h),
+            map,
+            intensity,
+            rotation: rotation.to_radians(),
+        }
+    }
+
+    /// Loads a Radiance `.hdr` map. OpenEXR isn't supported by the `image` crate
+    /// this renderer uses, so `.exr` files need converting first.
+    pub fn load(path: &str, intensity: f64, rotation: f64) -> image::ImageResult<EnvironmentLight> {
+        if path.to_lowercase().ends_with(".exr") {
+            return Err(image::ImageError::UnsupportedError(format!(
+                "{}: OpenEXR isn't supported, convert it to .hdr",
+                path
+            )));
+        }
+        let map = texture::ImageTexture::load(path, texture::WrapMode::Repeat)?;
+        Ok(EnvironmentLight::new(map, intensity, rotation))
+    }
+
+    /// Turns `w` by `angle` around +y.
+    fn rotate(&self, w: vector3::Vec3, angle: f64) -> vector3::Vec3 {
+        let (sin, cos) = angle.sin_cos();
+        vector3::Vec3::new(cos * w.x + sin * w.z, w.y, -sin * w.x + cos * w.z)
+    }
+
+    /// Map coordinates of a world direction, with `t` running down from the top row.
+    fn direction_to_map(&self, w: vector3::Vec3) -> (f64, f64) {
+        let (u, v) = sphere::Sphere::get_sphere_uv(self.rotate(w.unit_vector(), -self.rotation));
+        (u, 1.0 - v)
+    }
+
+    fn map_to_direction(&self, u: f64, t: f64) -> vector3::Vec3 {
+        self.rotate(map_direction(u, t), self.rotation)
+    }
+
+    /// Radiance of the texel `(u, t)` falls in; constant per texel, like the sampling density.
+    fn lookup(&self, u: f64, t: f64) -> vector3::Color {
+        let i = (u * self.map.width() as f64).floor() as i64;
+        let j = ((t * self.map.height() as f64) as i64).min(self.map.height() as i64 - 1);
+        self.map.texel(i, j) * self.intensity
+    }
+
+    /// Converts a density over the map to one over solid angle.
+    fn solid_angle_pdf(map_pdf: f64, t: f64) -> f64 {
+        let sin_theta = (PI * t).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        map_pdf / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+/// Direction seen at horizontal position `u` and height `t` (from the top) of an
+/// unrotated equirectangular map.
+pub fn map_direction(u: f64, t: f64) -> vector3::Vec3 {
+    let theta = PI * t;
+    let phi = 2.0 * PI * u - PI;
+    vector3::Vec3::new(
+        theta.sin() * phi.cos(),
+        theta.cos(),
+        -theta.sin() * phi.sin(),
+    )
+}
+
+impl light::LightTrait for EnvironmentLight {
+    fn sample_li(&self, _p: vector3::Point, u: [f64; 2]) -> Option<light::LightSample> {
+        let ([mu, mt], map_pdf) = self.distribution.sample(u);
+        let pdf = EnvironmentLight::solid_angle_pdf(map_pdf, mt);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(light::LightSample {
+            wi: self.map_to_direction(mu, mt),
+            dist: f64::INFINITY,
+            radiance: self.lookup(mu, mt),
+            pdf,
+        })
+    }
+
+    fn pdf_li(&self, _p: vector3::Point, wi: vector3::Vec3) -> f64 {
+        let (u, t) = self.direction_to_map(wi);
+        EnvironmentLight::solid_angle_pdf(self.distribution.pdf([u, t]), t)
+    }
+
+    fn le(&self, r: &ray::Ray) -> vector3::Color {
+        let (u, t) = self.direction_to_map(r.dir);
+        self.lookup(u, t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light::LightTrait;
+
+    /// Dim map with one bright texel.
+    fn env() -> EnvironmentLight {
+        let (w, h) = (16, 8);
+        let mut data = vec![vector3::Color::new(0.1, 0.1, 0.1); w * h];
+        data[2 * w + 5] = vector3::Color::new(50.0, 40.0, 30.0);
+        let map = texture::ImageTexture::new(w, h, data, texture::WrapMode::Repeat);
+        EnvironmentLight::new(map, 2.0, 30.0)
+    }
+
+    #[test]
+    fn samples_agree_with_pdf_and_radiance() {
+        let light = env();
+        let p = vector3::Point::new(0.0, 0.0, 0.0);
+        let mut bright = 0;
+        for k in 0..200 {
+            let u = [(k as f64 * 0.618) % 1.0, (k as f64 + 0.5) / 200.0];
+            let ls = light.sample_li(p, u).unwrap();
+            let pdf = light.pdf_li(p, ls.wi);
+            assert!((pdf - ls.pdf).abs() < 1e-6 * pdf, "{} != {}", pdf, ls.pdf);
+            let le = light.le(&ray::Ray::new(p, ls.wi));
+            assert!((le.x - ls.radiance.x).abs() < 1e-9);
+            if ls.radiance.x > 10.0 {
+                bright += 1;
+            }
+        }
+        // Most samples land on the bright texel
+        assert!(bright > 100, "{} bright samples", bright);
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        let light = env();
+        let p = vector3::Point::new(0.0, 0.0, 0.0);
+        let n = 400;
+        let mut sum = 0.0;
+        for i in 0..n {
+            for j in 0..n {
+                let cos_theta = -1.0 + 2.0 * (i as f64 + 0.5) / n as f64;
+                let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+                let phi = 2.0 * PI * (j as f64 + 0.5) / n as f64;
+                let w = vector3::Vec3::new(sin_theta * phi.cos(), cos_theta, sin_theta * phi.sin());
+                sum += light.pdf_li(p, w);
+            }
+        }
+        let integral = sum * 4.0 * PI / (n * n) as f64;
+        assert!((integral - 1.0).abs() < 0.02, "integral = {}", integral);
+    }
+}
@@ -0,0 +1,396 @@
+use crate::aabb;
+use crate::hittable;
+use crate::material;
+use crate::ray;
+use crate::vector3;
+use std::sync::{Arc, Mutex};
+
+/// One level of the min-max mipmap: the height range covered by each block of cells.
+struct MinMaxLevel {
+    width: usize,
+    depth: usize,
+    ranges: Vec<(f64, f64)>,
+}
+
+/// Terrain given by a grid of height samples over the XZ plane, starting at `origin`
+/// and spanning `size_x` by `size_z`. Each grid cell is split into two triangles
+/// shaded with normals interpolated from the samples.
+#[derive(Clone)]
+pub struct Heightfield {
+    origin: vector3::Point,
+    nx: usize,
+    nz: usize,
+    dx: f64,
+    dz: f64,
+    heights: Arc<Vec<f64>>,
+    normals: Arc<Vec<vector3::Vec3>>,
+    mips: Arc<Vec<MinMaxLevel>>,
+    material: Arc<Mutex<material::Material>>,
+}
+
+impl Heightfield {
+    /// `heights` holds `nx * nz` samples in world units, row by row along x.
+    /// Panics with fewer than two samples along either side.
+    pub fn new(
+        origin: vector3::Point,
+        size_x: f64,
+        size_z: f64,
+        nx: usize,
+        nz: usize,
+        heights: Vec<f64>,
+        mat: Arc<Mutex<material::Material>>,
+    ) -> Heightfield {
+        assert!(nx >= 2 && nz >= 2 && heights.len() == nx * nz);
+        let dx = size_x / (nx - 1) as f64;
+        let dz = size_z / (nz - 1) as f64;
+        let normals = vertex_normals(&heights, nx, nz, dx, dz);
+        let mips = build_mips(&heights, nx, nz);
+        Heightfield {
+            origin,
+            nx,
+            nz,
+            dx,
+            dz,
+            heights: Arc::new(heights),
+            normals: Arc::new(normals),
+            mips: Arc::new(mips),
+            material: mat,
+        }
+    }
+
+    /// Samples `f(u, v)` with `u, v` in `[0, 1]` on an `nx * nz` grid.
+    pub fn from_fn<F: Fn(f64, f64) -> f64>(
+        origin: vector3::Point,
+        size_x: f64,
+        size_z: f64,
+        nx: usize,
+        nz: usize,
+        f: F,
+        mat: Arc<Mutex<material::Material>>,
+    ) -> Heightfield {
+        let mut heights = Vec::with_capacity(nx * nz);
+        for j in 0..nz {
+            for i in 0..nx {
+                heights.push(f(i as f64 / (nx - 1) as f64, j as f64 / (nz - 1) as f64));
+            }
+        }
+        Heightfield::new(origin, size_x, size_z, nx, nz, heights, mat)
+    }
+
+    /// One sample per pixel of a grayscale image, with white raised to `max_height`.
+    pub fn from_image(
+        path: &str,
+        origin: vector3::Point,
+        size_x: f64,
+        size_z: f64,
+        max_height: f64,
+        mat: Arc<Mutex<material::Material>>,
+    ) -> image::ImageResult<Heightfield> {
+        let img = image::open(path)?.to_luma();
+        let (w, h) = img.dimensions();
+        if w < 2 || h < 2 {
+            // Too small to make a single cell from
+            return Err(image::ImageError::DimensionError);
+        }
+        let heights = img
+            .pixels()
+            .map(|p| p.data[0] as f64 / 255.0 * max_height)
+            .collect();
+        Ok(Heightfield::new(
+            origin, size_x, size_z, w as usize, h as usize, heights, mat,
+        ))
+    }
+
+    fn vertex(&self, i: usize, j: usize) -> vector3::Point {
+        self.origin
+            + vector3::Vec3::new(
+                i as f64 * self.dx,
+                self.heights[i + j * self.nx],
+                j as f64 * self.dz,
+            )
+    }
+
+    fn node_box(&self, level: usize, ci: usize, cj: usize) -> aabb::Aabb {
+        let mip = &self.mips[level];
+        let (lo, hi) = mip.ranges[ci + cj * mip.width];
+        let i0 = ci << level;
+        let j0 = cj << level;
+        let i1 = ((ci + 1) << level).min(self.nx - 1);
+        let j1 = ((cj + 1) << level).min(self.nz - 1);
+        aabb::Aabb::new(
+            self.origin + vector3::Vec3::new(i0 as f64 * self.dx, lo, j0 as f64 * self.dz),
+            self.origin + vector3::Vec3::new(i1 as f64 * self.dx, hi, j1 as f64 * self.dz),
+        )
+        .pad(1e-9)
+    }
+
+    /// Descends the min-max mipmap, skipping every block whose height range the
+    /// ray misses, and visiting the children of the rest nearest first.
+    fn hit_node(
+        &self,
+        level: usize,
+        ci: usize,
+        cj: usize,
+        r: &ray::Ray,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<(f64, usize, usize, f64, f64, bool)> {
+        self.node_box(level, ci, cj).intersect(r, t_min, t_max)?;
+        if level == 0 {
+            return self.hit_cell(ci, cj, r, t_min, t_max);
+        }
+
+        let child = &self.mips[level - 1];
+        let mut children = Vec::with_capacity(4);
+        for (a, b) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let (i, j) = (2 * ci + a, 2 * cj + b);
+            if i < child.width && j < child.depth {
+                if let Some((t0, _)) = self.node_box(level - 1, i, j).intersect(r, t_min, t_max) {
+                    children.push((t0, i, j));
+                }
+            }
+        }
+        children.sort_by(|x, y| x.0.total_cmp(&y.0));
+
+        let mut closest = t_max;
+        let mut found = None;
+        for (t0, i, j) in children {
+            if t0 > closest {
+                break;
+            }
+            if let Some(hit) = self.hit_node(level - 1, i, j, r, t_min, closest) {
+                closest = hit.0;
+                found = Some(hit);
+            }
+        }
+        found
+    }
+
+    /// Returns `(t, i, j, b1, b2, upper)`: the cell, which of its triangles was hit
+    /// and the barycentric coordinates within it.
+    fn hit_cell(
+        &self,
+        i: usize,
+        j: usize,
+        r: &ray::Ray,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<(f64, usize, usize, f64, f64, bool)> {
+        let v00 = self.vertex(i, j);
+        let v10 = self.vertex(i + 1, j);
+        let v01 = self.vertex(i, j + 1);
+        let v11 = self.vertex(i + 1, j + 1);
+        let mut closest = t_max;
+        let mut found = None;
+        for (upper, v1, v2) in [(false, v10, v11), (true, v11, v01)] {
+            if let Some((t, b1, b2)) = hit_triangle(r, v00, v1, v2, t_min, closest) {
+                closest = t;
+                found = Some((t, i, j, b1, b2, upper));
+            }
+        }
+        found
+    }
+}
+
+fn hit_triangle(
+    r: &ray::Ray,
+    v0: vector3::Point,
+    v1: vector3::Point,
+    v2: vector3::Point,
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, f64, f64)> {
+    // Möller–Trumbore
+    let e1 = v1 - v0;
+    let e2 = v2 - v0;
+    let pvec = vector3::cross(r.dir, e2);
+    let det = vector3::dot(e1, pvec);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+    let tvec = r.origin - v0;
+    let b1 = vector3::dot(tvec, pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+    let qvec = vector3::cross(tvec, e1);
+    let b2 = vector3::dot(r.dir, qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+    let t = vector3::dot(e2, qvec) * inv_det;
+    if t < t_min || t_max < t {
+        return None;
+    }
+    Some((t, b1, b2))
+}
+
+fn vertex_normals(heights: &[f64], nx: usize, nz: usize, dx: f64, dz: f64) -> Vec<vector3::Vec3> {
+    let h = |i: usize, j: usize| heights[i + j * nx];
+    let mut normals = Vec::with_capacity(nx * nz);
+    for j in 0..nz {
+        for i in 0..nx {
+            // Central differences, one-sided along the edges
+            let (i0, i1) = (i.saturating_sub(1), (i + 1).min(nx - 1));
+            let (j0, j1) = (j.saturating_sub(1), (j + 1).min(nz - 1));
+            let dhdx = (h(i1, j) - h(i0, j)) / ((i1 - i0) as f64 * dx);
+            let dhdz = (h(i, j1) - h(i, j0)) / ((j1 - j0) as f64 * dz);
+            normals.push(vector3::Vec3::new(-dhdx, 1.0, -dhdz).unit_vector());
+        }
+    }
+    normals
+}
+
+fn build_mips(heights: &[f64], nx: usize, nz: usize) -> Vec<MinMaxLevel> {
+    let h = |i: usize, j: usize| heights[i + j * nx];
+    let (width, depth) = (nx - 1, nz - 1);
+    let mut ranges = Vec::with_capacity(width * depth);
+    for j in 0..depth {
+        for i in 0..width {
+            let corners = [h(i, j), h(i + 1, j), h(i, j + 1), h(i + 1, j + 1)];
+            let lo = corners.iter().cloned().fold(f64::INFINITY, f64::min);
+            let hi = corners.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+            ranges.push((lo, hi));
+        }
+    }
+    let mut mips = vec![MinMaxLevel {
+        width,
+        depth,
+        ranges,
+    }];
+
+    while mips.last().is_some_and(|m| m.width > 1 || m.depth > 1) {
+        let prev = mips.last().unwrap();
+        let (width, depth) = (prev.width.div_ceil(2), prev.depth.div_ceil(2));
+        let mut ranges = Vec::with_capacity(width * depth);
+        for j in 0..depth {
+            for i in 0..width {
+                let mut range = (f64::INFINITY, f64::NEG_INFINITY);
+                for (a, b) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let (ci, cj) = (2 * i + a, 2 * j + b);
+                    if ci < prev.width && cj < prev.depth {
+                        let (lo, hi) = prev.ranges[ci + cj * prev.width];
+                        range = (range.0.min(lo), range.1.max(hi));
+                    }
+                }
+                ranges.push(range);
+            }
+        }
+        mips.push(MinMaxLevel {
+            width,
+            depth,
+            ranges,
+        });
+    }
+    mips
+}
+
+impl hittable::Hittable for Heightfield {
+    fn hit(&self, r: &ray::Ray, t_min: f64, t_max: f64) -> Option<hittable::HitRecord> {
+        let top = self.mips.len() - 1;
+        let (root, i, j, b1, b2, upper) = self.hit_node(top, 0, 0, r, t_min, t_max)?;
+
+        // Interpolate the sample normals across the triangle that was hit
+        let n = |i: usize, j: usize| self.normals[i + j * self.nx];
+        let (n1, n2) = if upper {
+            (n(i + 1, j + 1), n(i, j + 1))
+        } else {
+            (n(i + 1, j), n(i + 1, j + 1))
+        };
+        let outward_normal = (n(i, j) * (1.0 - b1 - b2) + n1 * b1 + n2 * b2).unit_vector();
+
+        let p = r.at(root);
+        let mut hit_record = hittable::HitRecord {
+            p,
+            t: root,
+            u: (p.x - self.origin.x) / (self.dx * (self.nx - 1) as f64),
+            v: (p.z - self.origin.z) / (self.dz * (self.nz - 1) as f64),
+            // u runs along +x, so its tangent is +x tilted into the surface
+            tangent: (vector3::Vec3::new(1.0, 0.0, 0.0) - outward_normal * outward_normal.x)
+                .unit_vector(),
+            normal: vector3::Vec3::new(0.0, 0.0, 0.0),
+            front_face: false,
+            material: self.material.clone(),
+        };
+        hit_record.set_face_normal(r, &outward_normal);
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        Some(self.node_box(self.mips.len() - 1, 0, 0).pad(0.0001))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittable;
+    use crate::test_utils::mat;
+
+    #[test]
+    fn hits_ramp() {
+        // Height rises linearly with x: y = x / 2 over a 4 x 4 patch.
+        let field = Heightfield::from_fn(
+            vector3::Point::new(0.0, 0.0, 0.0),
+            4.0,
+            4.0,
+            33,
+            17,
+            |u, _| u * 2.0,
+            mat(),
+        );
+        let r = ray::Ray::new(
+            vector3::Point::new(3.0, 10.0, 1.3),
+            vector3::Vec3::new(0.0, -1.0, 0.0),
+        );
+        let rec = field.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 8.5).abs() < 1e-6);
+        let expected = vector3::Vec3::new(-0.5, 1.0, 0.0).unit_vector();
+        assert!((rec.normal - expected).length() < 1e-6);
+        assert!((rec.u - 0.75).abs() < 1e-6);
+    }
+
+    #[test]
+    fn grazing_ray_passes_over_peak() {
+        // A single bump in the middle of otherwise flat ground.
+        let field = Heightfield::from_fn(
+            vector3::Point::new(-1.0, 0.0, -1.0),
+            2.0,
+            2.0,
+            65,
+            65,
+            |u, v| (1.0 - ((u - 0.5).powi(2) + (v - 0.5).powi(2)) * 16.0).max(0.0),
+            mat(),
+        );
+        let over = ray::Ray::new(
+            vector3::Point::new(-3.0, 1.01, 0.0),
+            vector3::Vec3::new(1.0, 0.0, 0.0),
+        );
+        assert!(field.hit(&over, 0.001, f64::INFINITY).is_none());
+        let into = ray::Ray::new(
+            vector3::Point::new(-3.0, 0.5, 0.0),
+            vector3::Vec3::new(1.0, 0.0, 0.0),
+        );
+        let rec = field.hit(&into, 0.001, f64::INFINITY).unwrap();
+        // With u - 0.5 = x / 2 the bump is 1 - 4 x^2, which is 0.5 at x = -sqrt(1/8).
+        assert!((rec.p.x + (1.0f64 / 8.0).sqrt()).abs() < 1e-2);
+    }
+
+    #[test]
+    fn image_too_small_is_an_error() {
+        let path = std::env::temp_dir().join("heightfield_test_strip.png");
+        let strip: image::GrayImage = image::ImageBuffer::from_pixel(1, 4, image::Luma([128]));
+        strip.save(&path).unwrap();
+        let field = Heightfield::from_image(
+            path.to_str().unwrap(),
+            vector3::Point::new(0.0, 0.0, 0.0),
+            1.0,
+            1.0,
+            1.0,
+            mat(),
+        );
+        std::fs::remove_file(path).unwrap();
+        assert!(matches!(field, Err(image::ImageError::DimensionError)));
+    }
+}
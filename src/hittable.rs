@@ -1,6 +1,19 @@
+use crate::aabb;
+use crate::capsule;
+use crate::cone;
+use crate::csg;
+use crate::curve;
+use crate::cylinder;
+use crate::disk;
+use crate::heightfield;
 use crate::material;
+use crate::quad;
 use crate::ray;
+use crate::sdf;
 use crate::sphere;
+use crate::torus;
+use crate::transform;
+use crate::triangle;
 use crate::vector3;
 use std::sync::{Arc, Mutex};
 
@@ -9,6 +22,10 @@ pub struct HitRecord {
     pub p: vector3::Point,
     pub normal: vector3::Vec3,
     pub t: f64,
+    pub u: f64,
+    pub v: f64,
+    /// Unit direction of increasing `u`, or zero where the shape doesn't define one.
+    pub tangent: vector3::Vec3,
     pub front_face: bool,
     pub material: Arc<Mutex<material::Material>>,
 }
@@ -24,8 +41,66 @@ impl HitRecord {
     }
 }
 
+/// A stretch of the ray that lies inside a solid, from where it enters to where it leaves.
+#[derive(Clone)]
+pub struct Interval {
+    pub enter: HitRecord,
+    pub exit: HitRecord,
+}
+
 pub trait Hittable {
     fn hit(&self, r: &ray::Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+    fn bounding_box(&self) -> Option<aabb::Aabb>;
+
+    /// Every interval along the whole (infinite) ray that lies inside the object,
+    /// sorted by `t`. Only meaningful for closed objects.
+    fn intervals(&self, r: &ray::Ray) -> Vec<Interval> {
+        collect_intervals(self, r)
+    }
+
+    /// Samples a point on the surface as seen from `origin`, for use as a light.
+    /// Returns the hit record of the ray from `origin` through that point and the
+    /// density per unit solid angle at `origin`. Shapes that can't be sampled give `None`.
+    fn sample(&self, _origin: vector3::Point, _u: [f64; 2]) -> Option<(HitRecord, f64)> {
+        None
+    }
+
+    /// Solid angle density with which `sample` picks the direction `dir` from `origin`.
+    fn pdf_value(&self, _origin: vector3::Point, _dir: vector3::Vec3) -> f64 {
+        0.0
+    }
+}
+
+/// Converts the density of uniformly picking `rec.p` on a surface of `area` into
+/// a density per unit solid angle seen from `origin`.
+pub fn solid_angle_pdf(origin: vector3::Point, rec: &HitRecord, area: f64) -> f64 {
+    let d = rec.p - origin;
+    let cosine = vector3::dot(rec.normal, d).abs() / d.length();
+    if cosine == 0.0 {
+        return 0.0;
+    }
+    d.length_squared() / (cosine * area)
+}
+
+/// Walks the ray with repeated `hit` calls and pairs each front-face crossing
+/// with the back-face crossing that follows it.
+pub fn collect_intervals<H: Hittable + ?Sized>(object: &H, r: &ray::Ray) -> Vec<Interval> {
+    let mut intervals = Vec::new();
+    let mut enter: Option<HitRecord> = None;
+    let mut t = f64::NEG_INFINITY;
+
+    while let Some(hit) = object.hit(r, t, f64::INFINITY) {
+        t = hit.t + 1e-6 * hit.t.abs().max(1.0);
+        if hit.front_face {
+            enter = Some(hit);
+        } else if let Some(e) = enter.take() {
+            intervals.push(Interval {
+                enter: e,
+                exit: hit,
+            });
+        }
+    }
+    intervals
 }
 #[derive(Clone)]
 pub struct HittableList {
@@ -35,21 +110,109 @@ pub struct HittableList {
 #[derive(Clone)]
 pub enum HittableObj {
     Sphere(sphere::Sphere),
+    Cylinder(cylinder::Cylinder),
+    Cone(cone::Cone),
+    Disk(disk::Disk),
+    Annulus(disk::Annulus),
+    Torus(torus::Torus),
+    Capsule(capsule::Capsule),
+    Csg(csg::Csg),
+    Sdf(sdf::Sdf),
+    Heightfield(heightfield::Heightfield),
+    Curve(curve::Curve),
+    Quad(quad::Quad),
+    Triangle(triangle::Triangle),
+    Instance(transform::Instance),
 }
 
 impl Hittable for HittableObj {
     fn hit(&self, r: &ray::Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
         match self {
             HittableObj::Sphere(x) => x.hit(r, t_min, t_max),
+            HittableObj::Cylinder(x) => x.hit(r, t_min, t_max),
+            HittableObj::Cone(x) => x.hit(r, t_min, t_max),
+            HittableObj::Disk(x) => x.hit(r, t_min, t_max),
+            HittableObj::Annulus(x) => x.hit(r, t_min, t_max),
+            HittableObj::Torus(x) => x.hit(r, t_min, t_max),
+            HittableObj::Capsule(x) => x.hit(r, t_min, t_max),
+            HittableObj::Csg(x) => x.hit(r, t_min, t_max),
+            HittableObj::Sdf(x) => x.hit(r, t_min, t_max),
+            HittableObj::Heightfield(x) => x.hit(r, t_min, t_max),
+            HittableObj::Curve(x) => x.hit(r, t_min, t_max),
+            HittableObj::Quad(x) => x.hit(r, t_min, t_max),
+            HittableObj::Triangle(x) => x.hit(r, t_min, t_max),
+            HittableObj::Instance(x) => x.hit(r, t_min, t_max),
+        }
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        match self {
+            HittableObj::Sphere(x) => x.bounding_box(),
+            HittableObj::Cylinder(x) => x.bounding_box(),
+            HittableObj::Cone(x) => x.bounding_box(),
+            HittableObj::Disk(x) => x.bounding_box(),
+            HittableObj::Annulus(x) => x.bounding_box(),
+            HittableObj::Torus(x) => x.bounding_box(),
+            HittableObj::Capsule(x) => x.bounding_box(),
+            HittableObj::Csg(x) => x.bounding_box(),
+            HittableObj::Sdf(x) => x.bounding_box(),
+            HittableObj::Heightfield(x) => x.bounding_box(),
+            HittableObj::Curve(x) => x.bounding_box(),
+            HittableObj::Quad(x) => x.bounding_box(),
+            HittableObj::Triangle(x) => x.bounding_box(),
+            HittableObj::Instance(x) => x.bounding_box(),
+        }
+    }
+
+    fn intervals(&self, r: &ray::Ray) -> Vec<Interval> {
+        match self {
+            HittableObj::Sphere(x) => x.intervals(r),
+            HittableObj::Cylinder(x) => x.intervals(r),
+            HittableObj::Cone(x) => x.intervals(r),
+            HittableObj::Disk(x) => x.intervals(r),
+            HittableObj::Annulus(x) => x.intervals(r),
+            HittableObj::Torus(x) => x.intervals(r),
+            HittableObj::Capsule(x) => x.intervals(r),
+            HittableObj::Csg(x) => x.intervals(r),
+            HittableObj::Sdf(x) => x.intervals(r),
+            HittableObj::Heightfield(x) => x.intervals(r),
+            HittableObj::Curve(x) => x.intervals(r),
+            HittableObj::Quad(x) => x.intervals(r),
+            HittableObj::Triangle(x) => x.intervals(r),
+            HittableObj::Instance(x) => x.intervals(r),
+        }
+    }
+
+    fn sample(&self, origin: vector3::Point, u: [f64; 2]) -> Option<(HitRecord, f64)> {
+        match self {
+            HittableObj::Sphere(x) => x.sample(origin, u),
+            HittableObj::Quad(x) => x.sample(origin, u),
+            HittableObj::Triangle(x) => x.sample(origin, u),
+            _ => None,
+        }
+    }
+
+    fn pdf_value(&self, origin: vector3::Point, dir: vector3::Vec3) -> f64 {
+        match self {
+            HittableObj::Sphere(x) => x.pdf_value(origin, dir),
+            HittableObj::Quad(x) => x.pdf_value(origin, dir),
+            HittableObj::Triangle(x) => x.pdf_value(origin, dir),
+            _ => 0.0,
         }
     }
 }
 
+impl Default for HittableList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl HittableList {
     pub fn new() -> HittableList {
-        return HittableList {
+        HittableList {
             objects: Vec::new(),
-        };
+        }
     }
     pub fn clear(mut self) -> HittableList {
         self.objects.clear();
@@ -58,6 +221,10 @@ impl HittableList {
     pub fn add(&mut self, object: HittableObj) {
         self.objects.push(object);
     }
+
+    pub fn objects(&self) -> &[HittableObj] {
+        &self.objects
+    }
 }
 
 impl Hittable for HittableList {
@@ -65,12 +232,24 @@ impl Hittable for HittableList {
         let mut hit_record = None;
         let mut closest_so_far = t_max;
 
-        for object in self.objects.clone() {
+        for object in &self.objects {
             if let Some(hit) = object.hit(r, t_min, closest_so_far) {
-                hit_record = Some(hit.clone());
                 closest_so_far = hit.t;
+                hit_record = Some(hit);
             }
         }
         hit_record
     }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        let mut output_box: Option<aabb::Aabb> = None;
+        for object in &self.objects {
+            let temp_box = object.bounding_box()?;
+            output_box = Some(match output_box {
+                Some(b) => aabb::surrounding_box(b, temp_box),
+                None => temp_box,
+            });
+        }
+        output_box
+    }
 }
@@ -0,0 +1,345 @@
+use crate::aabb;
+use crate::light;
+use crate::light_sampler;
+use crate::onb;
+use crate::vector3;
+use std::f64::consts::PI;
+use std::fs;
+use std::io;
+
+/// Luminous intensity of a light fixture by direction, read from an IESNA
+/// LM-63 photometric file. Only type C photometry is supported, which covers
+/// nearly every architectural fixture: vertical angles run from 0 at the
+/// fixture's nadir to 180 straight up behind it, and horizontal angles run
+/// around the nadir.
+#[derive(Clone, Debug)]
+pub struct IesProfile {
+    /// Vertical angles in degrees, ascending
+    vertical: Vec<f64>,
+    /// Horizontal angles in degrees, ascending
+    horizontal: Vec<f64>,
+    /// Candela for each horizontal angle, then each vertical angle
+    candela: Vec<Vec<f64>>,
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Value at `x` of the piecewise linear function through `xs` and `ys`, or
+/// `None` outside `xs`.
+fn interpolate(xs: &[f64], ys: &[f64], x: f64) -> Option<f64> {
+    if x < xs[0] || x > xs[xs.len() - 1] {
+        return None;
+    }
+    if xs.len() == 1 {
+        return Some(ys[0]);
+    }
+    let i = xs.partition_point(|&a| a <= x).clamp(1, xs.len() - 1);
+    let (x0, x1) = (xs[i - 1], xs[i]);
+    let t = if x1 > x0 { (x - x0) / (x1 - x0) } else { 0.0 };
+    Some(ys[i - 1] + t * (ys[i] - ys[i - 1]))
+}
+
+impl IesProfile {
+    pub fn load(path: &str) -> io::Result<IesProfile> {
+        IesProfile::parse(&fs::read_to_string(path)?)
+            .map_err(|e| invalid(format!("{}: {}", path, e)))
+    }
+
+    /// Parses the text of an LM-63 file (1986, 1991, 1995 or 2002).
+    pub fn parse(text: &str) -> io::Result<IesProfile> {
+        // Everything before the TILT line is the header and keywords
+        let mut lines = text.lines();
+        let tilt = loop {
+            match lines.next() {
+                Some(line) if line.trim_start().starts_with("TILT=") => {
+                    break line.trim_start()["TILT=".len()..].trim().to_string()
+                }
+                Some(_) => continue,
+                None => return Err(invalid("no TILT line".to_string())),
+            }
+        };
+        let rest: Vec<&str> = lines.collect();
+        let mut numbers = Vec::new();
+        for token in rest
+            .iter()
+            .flat_map(|l| l.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|t| !t.is_empty())
+        {
+            numbers.push(
+                token
+                    .parse::<f64>()
+                    .map_err(|e| invalid(format!("bad number {:?}: {}", token, e)))?,
+            );
+        }
+        let mut values = numbers.into_iter();
+        let mut next = |what: &str| {
+            values
+                .next()
+                .ok_or_else(|| invalid(format!("file ends before {}", what)))
+        };
+
+        // Tilt tables describe how output changes as the lamp leans; lamps are
+        // assumed to be mounted as photographed, so the table is skipped.
+        if tilt == "INCLUDE" {
+            next("lamp geometry")?;
+            let pairs = next("tilt angle count")? as usize;
+            for _ in 0..2 * pairs {
+                next("tilt table")?;
+            }
+        }
+
+        let _lamps = next("number of lamps")?;
+        let _lumens_per_lamp = next("lumens per lamp")?;
+        let multiplier = next("candela multiplier")?;
+        let nv = next("vertical angle count")? as usize;
+        let nh = next("horizontal angle count")? as usize;
+        let photometric_type = next("photometric type")?;
+        for what in ["units", "width", "length", "height"] {
+            next(what)?;
+        }
+        let ballast = next("ballast factor")?;
+        let lamp_factor = next("ballast-lamp factor")?;
+        let _watts = next("input watts")?;
+        if photometric_type != 1.0 {
+            return Err(invalid(format!(
+                "photometric type {} isn't supported, only type C (1)",
+                photometric_type
+            )));
+        }
+        if nv == 0 || nh == 0 {
+            return Err(invalid("no angles".to_string()));
+        }
+
+        let vertical = (0..nv)
+            .map(|_| next("vertical angles"))
+            .collect::<io::Result<Vec<f64>>>()?;
+        let horizontal = (0..nh)
+            .map(|_| next("horizontal angles"))
+            .collect::<io::Result<Vec<f64>>>()?;
+        let scale = multiplier * ballast * lamp_factor;
+        let mut candela = Vec::with_capacity(nh);
+        for _ in 0..nh {
+            candela.push(
+                (0..nv)
+                    .map(|_| next("candela values").map(|c| c * scale))
+                    .collect::<io::Result<Vec<f64>>>()?,
+            );
+        }
+        let ascending = |a: &[f64]| a.windows(2).all(|w| w[0] < w[1]);
+        if !ascending(&vertical) || !ascending(&horizontal) {
+            return Err(invalid("angles must increase".to_string()));
+        }
+        Ok(IesProfile {
+            vertical,
+            horizontal,
+            candela,
+        })
+    }
+
+    /// Candela at `theta` degrees from the nadir and `phi` degrees around it,
+    /// interpolated between the tabulated angles. Zero outside the measured range.
+    pub fn candela(&self, theta: f64, phi: f64) -> f64 {
+        // Fold `phi` into the tabulated range using the symmetry the last
+        // horizontal angle implies: none, a quadrant, a half or the full circle.
+        let last = self.horizontal[self.horizontal.len() - 1];
+        let mut phi = phi.rem_euclid(360.0);
+        if last <= 180.0 && phi > 180.0 {
+            phi = 360.0 - phi;
+        }
+        if last <= 90.0 && phi > 90.0 {
+            phi = 180.0 - phi;
+        }
+        let first = self.horizontal[0];
+        // A half table may instead run from 90 to 270, mirrored across that plane
+        if first >= 90.0 && (phi < first || phi > last) {
+            phi = (180.0 - phi).rem_euclid(360.0);
+        }
+
+        let at = |row: &[f64]| interpolate(&self.vertical, row, theta).unwrap_or(0.0);
+        if self.horizontal.len() == 1 {
+            return at(&self.candela[0]);
+        }
+        let by_row: Vec<f64> = self.candela.iter().map(|row| at(row)).collect();
+        interpolate(&self.horizontal, &by_row, phi.clamp(first, last)).unwrap_or(0.0)
+    }
+
+    /// Total luminous flux in lumens, integrated over the sphere.
+    pub fn flux(&self) -> f64 {
+        let (n_theta, n_phi) = (180, 72);
+        let mut sum = 0.0;
+        for i in 0..n_theta {
+            let theta = 180.0 * (i as f64 + 0.5) / n_theta as f64;
+            let sin_theta = theta.to_radians().sin();
+            for j in 0..n_phi {
+                let phi = 360.0 * (j as f64 + 0.5) / n_phi as f64;
+                sum += self.candela(theta, phi) * sin_theta;
+            }
+        }
+        sum * (PI / n_theta as f64) * (2.0 * PI / n_phi as f64)
+    }
+}
+
+/// Point light whose intensity follows a measured IES profile, so one type
+/// covers downlights, spots, wall washers and bare lamps alike. Candela are
+/// converted at 683 lm/W, as with `material::Power::Lumens`.
+pub struct PhotometricLight {
+    position: vector3::Point,
+    /// `w` is the fixture's nadir and `u` horizontal angle 0
+    frame: onb::Onb,
+    profile: IesProfile,
+    /// Colour of the light, scaled to unit luminance, times the overall scale
+    tint: vector3::Color,
+}
+
+impl PhotometricLight {
+    /// `aim` is where the fixture's nadir points, for example straight down for
+    /// a ceiling downlight, and `rotation` turns the fixture around it in degrees.
+    /// `scale` multiplies the profile's candela.
+    pub fn new(
+        position: vector3::Point,
+        aim: vector3::Vec3,
+        rotation: f64,
+        profile: IesProfile,
+        tint: vector3::Color,
+        scale: f64,
+    ) -> PhotometricLight {
+        let base = onb::Onb::build_from_w(aim);
+        let (sin, cos) = rotation.to_radians().sin_cos();
+        let u = base.u * cos + base.v * sin;
+        PhotometricLight {
+            position,
+            frame: onb::Onb::build_from_wu(aim, u),
+            profile,
+            tint: tint / tint.luminance().max(1e-12) * (scale / 683.0),
+        }
+    }
+
+    /// Radiant intensity sent out along the unit direction `w`.
+    fn intensity(&self, w: vector3::Vec3) -> vector3::Color {
+        let l = self.frame.to_local(w);
+        let theta = l.z.clamp(-1.0, 1.0).acos().to_degrees();
+        let phi = l.y.atan2(l.x).to_degrees();
+        self.tint * self.profile.candela(theta, phi)
+    }
+}
+
+impl light::LightTrait for PhotometricLight {
+    fn sample_li(&self, p: vector3::Point, _u: [f64; 2]) -> Option<light::LightSample> {
+        let d = self.position - p;
+        let dist = d.length();
+        if dist == 0.0 {
+            return None;
+        }
+        let wi = d / dist;
+        let radiance = self.intensity(wi * -1.0) / (dist * dist);
+        if radiance.luminance() <= 0.0 {
+            return None;
+        }
+        Some(light::LightSample {
+            wi,
+            dist,
+            radiance,
+            pdf: 1.0,
+        })
+    }
+
+    fn pdf_li(&self, _p: vector3::Point, _wi: vector3::Vec3) -> f64 {
+        0.0
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+
+    fn bounds(&self) -> Option<light_sampler::LightBounds> {
+        Some(light_sampler::LightBounds {
+            bounds: aabb::Aabb::new(self.position, self.position),
+            phi: self.tint.luminance() * self.profile.flux(),
+            w: self.frame.w,
+            cos_theta_o: -1.0,
+            cos_theta_e: (0.5 * PI).cos(),
+            two_sided: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light::LightTrait;
+
+    /// Downlight measured in one vertical plane, with a 1995 header and tilt table.
+    const DOWNLIGHT: &str = "IESNA:LM-63-1995
+[TEST] 123
+[MANUFAC] Test
+TILT=INCLUDE
+1
+3
+0 45 90
+1.0 0.9 0.8
+1 1000 2.0 5 1 1 2 0.1 0.1 0.05
+0.5 1.0 20
+0 22.5 45 67.5 90
+0
+500, 400, 300, 100, 0
+";
+
+    #[test]
+    fn parses_and_interpolates() {
+        let p = IesProfile::parse(DOWNLIGHT).unwrap();
+        // Candela multiplier 2 and ballast factor 0.5 cancel out
+        assert_eq!(p.candela(0.0, 0.0), 500.0);
+        assert!((p.candela(11.25, 123.0) - 450.0).abs() < 1e-9);
+        assert!((p.candela(80.0, 300.0) - 100.0 * 10.0 / 22.5).abs() < 1e-9);
+        // Nothing measured above the horizon
+        assert_eq!(p.candela(120.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn folds_symmetric_tables() {
+        let quadrant = "TILT=NONE
+1 -1 1 2 3 1 1 0 0 0
+1 1 10
+0 90
+0 45 90
+100 100 200 200 300 300
+";
+        let p = IesProfile::parse(quadrant).unwrap();
+        assert!((p.candela(0.0, 45.0) - 200.0).abs() < 1e-9);
+        assert!((p.candela(0.0, 135.0) - 200.0).abs() < 1e-9);
+        assert!((p.candela(0.0, 270.0) - 300.0).abs() < 1e-9);
+        assert!((p.candela(0.0, 315.0) - 200.0).abs() < 1e-9);
+        let half = quadrant.replace("0 90\n0 45 90", "0 90\n90 135 270");
+        let p = IesProfile::parse(&half).unwrap();
+        assert!((p.candela(0.0, 45.0) - 200.0).abs() < 1e-9);
+        assert!((p.candela(0.0, 300.0) - p.candela(0.0, 240.0)).abs() < 1e-9);
+        assert!(IesProfile::parse("TILT=NONE\n1 -1 1 2").is_err());
+        assert!(IesProfile::parse("TILT=NONE\n1 -1 1 1 1 3 1 0 0 0 1 1 1 0 0 5").is_err());
+    }
+
+    #[test]
+    fn light_follows_profile() {
+        let profile = IesProfile::parse(DOWNLIGHT).unwrap();
+        let light = PhotometricLight::new(
+            vector3::Point::new(0.0, 3.0, 0.0),
+            vector3::Vec3::new(0.0, -1.0, 0.0),
+            0.0,
+            profile,
+            vector3::Color::new(1.0, 1.0, 1.0),
+            683.0,
+        );
+        let below = light
+            .sample_li(vector3::Point::new(0.0, 0.0, 0.0), [0.5, 0.5])
+            .unwrap();
+        assert!((below.radiance.luminance() - 500.0 / 9.0).abs() < 1e-9);
+        // Level with the fixture is the edge of the beam, and above it is dark
+        assert!(light
+            .sample_li(vector3::Point::new(1.0, 4.0, 0.0), [0.5, 0.5])
+            .is_none());
+        // A lambertian-ish beam confined to the lower hemisphere
+        let flux = light.bounds().unwrap().phi;
+        assert!(flux > 0.0 && flux < 4.0 * PI * 500.0);
+    }
+}
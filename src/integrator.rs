@@ -0,0 +1,360 @@
+use crate::bsdf;
+use crate::hittable;
+use crate::light;
+use crate::light::LightTrait;
+use crate::material;
+use crate::material::MaterialTrait;
+use crate::ray;
+use crate::spectrum;
+use crate::utils;
+use crate::vector3;
+use std::ops;
+
+/// Sky seen by rays that escape the scene when there's no environment light.
+pub fn background(r: &ray::Ray) -> vector3::Color {
+    let unit_direction: vector3::Vec3 = r.dir.unit_vector();
+    let t = 0.5 * (unit_direction.y() + 1.0);
+    vector3::Color::new(1.0, 1.0, 1.0) * (1.0 - t) + vector3::Color::new(0.5, 0.7, 1.0) * t
+}
+
+/// Weight for a sample from a strategy with density `f_pdf` when a strategy with
+/// density `g_pdf` could have produced it too (Veach's power heuristic, beta = 2).
+pub fn power_heuristic(f_pdf: f64, g_pdf: f64) -> f64 {
+    let (f, g) = (f_pdf * f_pdf, g_pdf * g_pdf);
+    if f.is_infinite() {
+        return 1.0;
+    }
+    if f + g == 0.0 {
+        return 0.0;
+    }
+    f / (f + g)
+}
+
+/// The wavelengths a path carries light at: RGB's three primaries, or a set of
+/// sampled ones. Everything the path meets is given in RGB and upsampled.
+trait Wavelengths {
+    type Radiance: Copy
+        + ops::Add<Output = Self::Radiance>
+        + ops::Mul<Output = Self::Radiance>
+        + ops::Mul<f64, Output = Self::Radiance>
+        + ops::Div<f64, Output = Self::Radiance>;
+
+    fn constant(&self, x: f64) -> Self::Radiance;
+    fn upsample(&self, c: vector3::Color) -> Self::Radiance;
+    fn max_value(radiance: &Self::Radiance) -> f64;
+    /// Scattering at a hit on `material`, which may narrow the wavelengths down.
+    fn bsdf(
+        &mut self,
+        material: &material::Material,
+        rec: &hittable::HitRecord,
+    ) -> Option<bsdf::Bsdf>;
+}
+
+struct Rgb;
+
+impl Wavelengths for Rgb {
+    type Radiance = vector3::Color;
+
+    fn constant(&self, x: f64) -> vector3::Color {
+        vector3::Color::new(x, x, x)
+    }
+
+    fn upsample(&self, c: vector3::Color) -> vector3::Color {
+        c
+    }
+
+    fn max_value(radiance: &vector3::Color) -> f64 {
+        radiance.x.max(radiance.y).max(radiance.z)
+    }
+
+    fn bsdf(
+        &mut self,
+        material: &material::Material,
+        rec: &hittable::HitRecord,
+    ) -> Option<bsdf::Bsdf> {
+        material.bsdf(rec)
+    }
+}
+
+impl Wavelengths for spectrum::SampledWavelengths {
+    type Radiance = spectrum::SampledSpectrum;
+
+    fn constant(&self, x: f64) -> spectrum::SampledSpectrum {
+        spectrum::SampledSpectrum::constant(x)
+    }
+
+    fn upsample(&self, c: vector3::Color) -> spectrum::SampledSpectrum {
+        spectrum::SampledSpectrum::from_rgb(c, self)
+    }
+
+    fn max_value(radiance: &spectrum::SampledSpectrum) -> f64 {
+        radiance.max_value()
+    }
+
+    /// A dispersive surface drops all but the hero wavelength.
+    fn bsdf(
+        &mut self,
+        material: &material::Material,
+        rec: &hittable::HitRecord,
+    ) -> Option<bsdf::Bsdf> {
+        if material.is_dispersive() {
+            self.terminate_secondary();
+        }
+        material.bsdf_at(rec, self.lambda(0))
+    }
+}
+
+/// Radiance along `r`. At every non-specular bounce one light is sampled directly
+/// and a shadow ray is cast; emission found by following the BSDF is weighted
+/// against that with multiple importance sampling.
+pub fn path_trace(
+    r: &ray::Ray,
+    world: &dyn hittable::Hittable,
+    lights: &light::LightList,
+    max_depth: i32,
+) -> vector3::Color {
+    trace(r, world, lights, max_depth, &mut Rgb)
+}
+
+/// `path_trace` carrying the spectrum at a set of wavelengths instead of RGB.
+/// RGB reflectances and emission are upsampled to spectra at each event. A
+/// dispersive surface drops all but the hero wavelength from `lambda`.
+pub fn path_trace_spectral(
+    r: &ray::Ray,
+    world: &dyn hittable::Hittable,
+    lights: &light::LightList,
+    max_depth: i32,
+    lambda: &mut spectrum::SampledWavelengths,
+) -> spectrum::SampledSpectrum {
+    trace(r, world, lights, max_depth, lambda)
+}
+
+fn trace<W: Wavelengths>(
+    r: &ray::Ray,
+    world: &dyn hittable::Hittable,
+    lights: &light::LightList,
+    max_depth: i32,
+    lambda: &mut W,
+) -> W::Radiance {
+    let mut radiance = lambda.constant(0.0);
+    let mut throughput = lambda.constant(1.0);
+    let mut ray = *r;
+    // Where the last bounce happened, the normal light sampling used there and the
+    // BSDF density of the direction it chose, or `None` if that bounce was
+    // specular and light sampling couldn't see it.
+    let mut last_bounce: Option<(vector3::Point, vector3::Vec3, f64)> = None;
+
+    for depth in 0..max_depth {
+        let rec = match first_hit(world, &ray, depth == 0) {
+            Some(rec) => rec,
+            None => {
+                let weight = match last_bounce {
+                    Some((p, n, bsdf_pdf)) => {
+                        power_heuristic(bsdf_pdf, lights.pdf(p, n, ray.dir.unit_vector()))
+                    }
+                    None => 1.0,
+                };
+                if !lights.has_environment() {
+                    radiance = radiance + throughput * lambda.upsample(background(&ray));
+                }
+                radiance = radiance + throughput * lambda.upsample(lights.le(&ray)) * weight;
+                break;
+            }
+        };
+
+        let (emitted, bsdf) = {
+            let material = rec.material.lock().unwrap();
+            (material.emitted(&rec), lambda.bsdf(&material, &rec))
+        };
+        let wo = ray.dir.unit_vector() * -1.0;
+
+        if emitted.luminance() > 0.0 {
+            let weight = match last_bounce {
+                Some((p, n, bsdf_pdf)) => power_heuristic(bsdf_pdf, lights.pdf(p, n, wo * -1.0)),
+                None => 1.0,
+            };
+            radiance = radiance + throughput * lambda.upsample(emitted) * weight;
+        }
+
+        let bsdf = match bsdf {
+            Some(bsdf) => bsdf,
+            None => break,
+        };
+
+        let n = shading_normal(&rec, &bsdf);
+        if !bsdf.flags().is_specular() {
+            if let Some((f, li)) = sample_light(world, lights, &rec, n, &bsdf, wo) {
+                radiance = radiance + throughput * lambda.upsample(f) * lambda.upsample(li);
+            }
+        }
+
+        let u = [
+            utils::random_double(0.0, 1.0),
+            utils::random_double(0.0, 1.0),
+        ];
+        let s = match bsdf.sample(wo, u) {
+            Some(s) => s,
+            None => break,
+        };
+        throughput = throughput * lambda.upsample(s.f * (bsdf.cos_theta(s.wi).abs() / s.pdf));
+        last_bounce = if s.flags.is_specular() {
+            None
+        } else {
+            Some((rec.p, n, s.pdf))
+        };
+        ray = ray::Ray::new(rec.p, s.wi);
+
+        // Russian roulette once paths have had a few bounces to pick up light
+        if depth > 3 {
+            let q = (1.0 - W::max_value(&throughput)).max(0.05);
+            if utils::random_double(0.0, 1.0) < q {
+                break;
+            }
+            throughput = throughput / (1.0 - q);
+        }
+    }
+    radiance
+}
+
+/// Closest surface along `r`, passing through any hidden from the camera if `r`
+/// is a camera ray.
+fn first_hit(
+    world: &dyn hittable::Hittable,
+    r: &ray::Ray,
+    camera_ray: bool,
+) -> Option<hittable::HitRecord> {
+    let mut t_min = 0.001;
+    loop {
+        let rec = world.hit(r, t_min, f64::INFINITY)?;
+        if !camera_ray || rec.material.lock().unwrap().visible_to_camera() {
+            return Some(rec);
+        }
+        t_min = rec.t + 0.001;
+    }
+}
+
+/// Normal that light selection should favour at `rec`: none for surfaces that
+/// also transmit, since they're lit from both sides.
+fn shading_normal(rec: &hittable::HitRecord, bsdf: &bsdf::Bsdf) -> vector3::Vec3 {
+    if bsdf.flags().is_transmission() {
+        vector3::Vec3::new(0.0, 0.0, 0.0)
+    } else {
+        rec.normal
+    }
+}
+
+/// Light reflected toward `wo` from one sampled light, weighted against the BSDF
+/// having found the same direction. Returns the weighted BSDF factor and the
+/// light's radiance separately, to be multiplied together.
+fn sample_light(
+    world: &dyn hittable::Hittable,
+    lights: &light::LightList,
+    rec: &hittable::HitRecord,
+    n: vector3::Vec3,
+    bsdf: &bsdf::Bsdf,
+    wo: vector3::Vec3,
+) -> Option<(vector3::Color, vector3::Color)> {
+    let (light, choice_pdf) = lights.choose(rec.p, n, utils::random_double(0.0, 1.0))?;
+    let u = [
+        utils::random_double(0.0, 1.0),
+        utils::random_double(0.0, 1.0),
+    ];
+    let ls = match light.sample_li(rec.p, u) {
+        Some(ls) if ls.pdf > 0.0 && ls.radiance.luminance() > 0.0 => ls,
+        _ => return None,
+    };
+    let f = bsdf.eval(wo, ls.wi) * bsdf.cos_theta(ls.wi).abs();
+    if f.luminance() <= 0.0 {
+        return None;
+    }
+    let shadow = ray::Ray::new(rec.p, ls.wi);
+    if world.hit(&shadow, 0.001, ls.dist * (1.0 - 1e-4)).is_some() {
+        return None;
+    }
+    let light_pdf = choice_pdf * ls.pdf;
+    let weight = if light.is_delta() {
+        1.0
+    } else {
+        power_heuristic(light_pdf, bsdf.pdf(wo, ls.wi))
+    };
+    Some((f * (weight / light_pdf), ls.radiance))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn power_heuristic_weights_sum_to_one() {
+        let (a, b) = (0.7, 2.3);
+        assert!((power_heuristic(a, b) + power_heuristic(b, a) - 1.0).abs() < 1e-12);
+        assert_eq!(power_heuristic(f64::INFINITY, 1.0), 1.0);
+        assert_eq!(power_heuristic(0.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn hidden_emitters_only_light_the_scene() {
+        use crate::material;
+        use crate::quad;
+        use std::sync::{Arc, Mutex};
+
+        let r = ray::Ray::new(
+            vector3::Point::new(0.5, 0.5, 2.0),
+            vector3::Vec3::new(0.0, 0.0, -1.0),
+        );
+        for visible in [true, false] {
+            let mut emitter = material::DiffuseLight::new(vector3::Color::new(5.0, 5.0, 5.0));
+            emitter.set_visible_to_camera(visible);
+            let mat = Arc::new(Mutex::new(material::Material::DiffuseLight(emitter)));
+            let q = quad::Quad::new(
+                vector3::Point::new(0.0, 0.0, 0.0),
+                vector3::Vec3::new(1.0, 0.0, 0.0),
+                vector3::Vec3::new(0.0, 1.0, 0.0),
+                mat,
+            );
+            let mut world = hittable::HittableList::new();
+            world.add(hittable::HittableObj::Quad(q));
+            let c = path_trace(&r, &world, &light::LightList::new(), 4);
+            let expected = if visible { 5.0 } else { background(&r).x };
+            assert!(
+                (c.x - expected).abs() < 1e-9,
+                "visible {}: {}",
+                visible,
+                c.x
+            );
+        }
+    }
+
+    #[test]
+    fn spectral_matches_rgb_for_direct_emission() {
+        use crate::material;
+        use crate::quad;
+        use std::sync::{Arc, Mutex};
+
+        let glow = vector3::Color::new(4.0, 2.0, 1.0);
+        let mat = Arc::new(Mutex::new(material::Material::DiffuseLight(
+            material::DiffuseLight::new(glow),
+        )));
+        let mut world = hittable::HittableList::new();
+        world.add(hittable::HittableObj::Quad(quad::Quad::new(
+            vector3::Point::new(0.0, 0.0, 0.0),
+            vector3::Vec3::new(1.0, 0.0, 0.0),
+            vector3::Vec3::new(0.0, 1.0, 0.0),
+            mat,
+        )));
+        let lights = light::LightList::new();
+        let r = ray::Ray::new(
+            vector3::Point::new(0.5, 0.5, 2.0),
+            vector3::Vec3::new(0.0, 0.0, -1.0),
+        );
+        let n = 4000;
+        let mut sum = vector3::Color::new(0.0, 0.0, 0.0);
+        for k in 0..n {
+            let mut lambda =
+                spectrum::SampledWavelengths::sample_visible((k as f64 + 0.5) / n as f64);
+            sum = sum + path_trace_spectral(&r, &world, &lights, 4, &mut lambda).to_rgb(&lambda);
+        }
+        let mean = sum / n as f64;
+        assert!((mean - glow).length() < 0.05 * glow.length());
+    }
+}
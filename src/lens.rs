@@ -0,0 +1,565 @@
+use crate::camera;
+use crate::ray;
+use crate::utils;
+use crate::vector3;
+use std::fs;
+use std::io;
+
+/// One surface of a lens prescription, in metres.
+#[derive(Copy, Clone, Debug)]
+pub struct LensElement {
+    /// Radius of curvature, positive when the centre is toward the film, or
+    /// zero for the aperture stop
+    pub curvature_radius: f64,
+    /// Distance along the axis to the next surface toward the film
+    pub thickness: f64,
+    /// Index of refraction behind the surface, with 0 meaning air
+    pub eta: f64,
+    pub aperture_radius: f64,
+}
+
+impl LensElement {
+    fn is_stop(&self) -> bool {
+        self.curvature_radius == 0.0
+    }
+
+    fn medium(&self) -> f64 {
+        if self.eta == 0.0 {
+            1.0
+        } else {
+            self.eta
+        }
+    }
+}
+
+/// A series of spherical lens surfaces in front of the film, as in pbrt's
+/// RealisticCamera. Positions are in lens space: the film is at `z = 0`, the
+/// optical axis is `z`, and the lens lies toward negative `z`.
+#[derive(Clone, Debug)]
+pub struct LensSystem {
+    /// Ordered from the front (scene side) to the film
+    elements: Vec<LensElement>,
+}
+
+/// Where a ray meets a sphere of `radius` centred on the axis at `z_center`,
+/// picking the intersection on the side of the surface the lens uses.
+fn intersect_spherical(radius: f64, z_center: f64, r: &ray::Ray) -> Option<(f64, vector3::Vec3)> {
+    let o = r.origin - vector3::Vec3::new(0.0, 0.0, z_center);
+    let a = r.dir.length_squared();
+    let b = 2.0 * vector3::dot(r.dir, o);
+    let c = o.length_squared() - radius * radius;
+    let disc = b * b - 4.0 * a * c;
+    if disc < 0.0 {
+        return None;
+    }
+    let q = -0.5 * (b + b.signum() * disc.sqrt());
+    let (t0, t1) = (q / a, c / q);
+    let (near, far) = (t0.min(t1), t0.max(t1));
+    let t = if (r.dir.z > 0.0) ^ (radius < 0.0) {
+        near
+    } else {
+        far
+    };
+    if t < 0.0 {
+        return None;
+    }
+    let n = (o + r.dir * t).unit_vector();
+    // Face the normal back against the ray
+    let n = if vector3::dot(n, r.dir) > 0.0 {
+        n * -1.0
+    } else {
+        n
+    };
+    Some((t, n))
+}
+
+/// Bends the unit direction `wi` (pointing away from the surface) through the
+/// interface with normal `n` on its side, or `None` on total internal reflection.
+fn refract(wi: vector3::Vec3, n: vector3::Vec3, eta: f64) -> Option<vector3::Vec3> {
+    let cos_i = vector3::dot(n, wi);
+    let sin2_t = eta * eta * (1.0 - cos_i * cos_i).max(0.0);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(wi * -eta + n * (eta * cos_i - cos_t))
+}
+
+/// Flips between camera space, where `z` points into the scene, and lens space.
+fn flip(r: &ray::Ray) -> ray::Ray {
+    ray::Ray::new(
+        vector3::Point::new(r.origin.x, r.origin.y, -r.origin.z),
+        vector3::Vec3::new(r.dir.x, r.dir.y, -r.dir.z),
+    )
+}
+
+impl LensSystem {
+    /// Reads a prescription with one surface per line, front element first:
+    /// `radius thickness eta aperture_diameter`, all in millimetres. Blank lines
+    /// and lines starting with `#` are skipped.
+    pub fn load(path: &str) -> io::Result<LensSystem> {
+        LensSystem::parse(&fs::read_to_string(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, e)))
+    }
+
+    pub fn parse(text: &str) -> io::Result<LensSystem> {
+        let mut elements = Vec::new();
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |msg: String| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}: {}", line_no + 1, msg),
+                )
+            };
+            let values: Vec<f64> = line
+                .split_whitespace()
+                .map(|x| x.parse::<f64>())
+                .collect::<Result<_, _>>()
+                .map_err(|e| invalid(e.to_string()))?;
+            if values.len() != 4 {
+                return Err(invalid(format!(
+                    "expected 4 numbers, found {}",
+                    values.len()
+                )));
+            }
+            elements.push(LensElement {
+                curvature_radius: values[0] * 1e-3,
+                thickness: values[1] * 1e-3,
+                eta: values[2],
+                aperture_radius: values[3] * 0.5e-3,
+            });
+        }
+        if elements.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "no lens surfaces".to_string(),
+            ));
+        }
+        Ok(LensSystem { elements })
+    }
+
+    /// Distance from the film to the front surface.
+    fn front_z(&self) -> f64 {
+        self.elements.iter().map(|e| e.thickness).sum()
+    }
+
+    /// Distance from the film to the rear surface.
+    fn rear_z(&self) -> f64 {
+        self.elements[self.elements.len() - 1].thickness
+    }
+
+    fn rear_radius(&self) -> f64 {
+        self.elements[self.elements.len() - 1].aperture_radius
+    }
+
+    /// Narrows the aperture stop to `diameter` millimetres, if that's smaller.
+    pub fn stop_down(&mut self, diameter: f64) {
+        for e in self.elements.iter_mut().filter(|e| e.is_stop()) {
+            e.aperture_radius = e.aperture_radius.min(0.5e-3 * diameter);
+        }
+    }
+
+    /// Follows a camera-space ray from the film out through the lens, or
+    /// `None` if an element blocks it.
+    pub fn trace_from_film(&self, r: &ray::Ray) -> Option<ray::Ray> {
+        let mut r = flip(r);
+        let mut z = 0.0;
+        for i in (0..self.elements.len()).rev() {
+            let e = &self.elements[i];
+            z -= e.thickness;
+            let (t, n) = if e.is_stop() {
+                if r.dir.z >= 0.0 {
+                    return None;
+                }
+                (
+                    (z - r.origin.z) / r.dir.z,
+                    vector3::Vec3::new(0.0, 0.0, 0.0),
+                )
+            } else {
+                intersect_spherical(e.curvature_radius, z + e.curvature_radius, &r)?
+            };
+            let p = r.at(t);
+            if p.x * p.x + p.y * p.y > e.aperture_radius * e.aperture_radius {
+                return None;
+            }
+            r.origin = p;
+            if !e.is_stop() {
+                let eta_t = if i > 0 {
+                    self.elements[i - 1].medium()
+                } else {
+                    1.0
+                };
+                r.dir = refract(r.dir.unit_vector() * -1.0, n, e.medium() / eta_t)?;
+            }
+        }
+        Some(flip(&r))
+    }
+
+    /// Follows a camera-space ray from the scene in through the lens to the film side.
+    pub fn trace_from_scene(&self, r: &ray::Ray) -> Option<ray::Ray> {
+        let mut r = flip(r);
+        let mut z = -self.front_z();
+        for i in 0..self.elements.len() {
+            let e = &self.elements[i];
+            let (t, n) = if e.is_stop() {
+                (
+                    (z - r.origin.z) / r.dir.z,
+                    vector3::Vec3::new(0.0, 0.0, 0.0),
+                )
+            } else {
+                intersect_spherical(e.curvature_radius, z + e.curvature_radius, &r)?
+            };
+            let p = r.at(t);
+            if p.x * p.x + p.y * p.y > e.aperture_radius * e.aperture_radius {
+                return None;
+            }
+            r.origin = p;
+            if !e.is_stop() {
+                let eta_i = if i > 0 {
+                    self.elements[i - 1].medium()
+                } else {
+                    1.0
+                };
+                r.dir = refract(r.dir.unit_vector() * -1.0, n, eta_i / e.medium())?;
+            }
+            z += e.thickness;
+        }
+        Some(flip(&r))
+    }
+
+    /// Principal plane and focal point along the axis, from a ray parallel to
+    /// the axis going in and the same ray coming out.
+    fn cardinal_points(r_in: &ray::Ray, r_out: &ray::Ray) -> (f64, f64) {
+        let tf = -r_out.origin.x / r_out.dir.x;
+        let fz = -r_out.at(tf).z;
+        let tp = (r_in.origin.x - r_out.origin.x) / r_out.dir.x;
+        let pz = -r_out.at(tp).z;
+        (pz, fz)
+    }
+
+    /// Thick lens approximation: principal planes and focal points on the scene
+    /// and film sides, found by tracing rays parallel to the axis through it.
+    fn thick_lens(&self, height: f64) -> Option<([f64; 2], [f64; 2])> {
+        let from_scene = ray::Ray::new(
+            vector3::Point::new(height, 0.0, self.front_z() + 1.0),
+            vector3::Vec3::new(0.0, 0.0, -1.0),
+        );
+        let (pz0, fz0) =
+            LensSystem::cardinal_points(&from_scene, &self.trace_from_scene(&from_scene)?);
+        let from_film = ray::Ray::new(
+            vector3::Point::new(height, 0.0, self.rear_z() - 1.0),
+            vector3::Vec3::new(0.0, 0.0, 1.0),
+        );
+        let (pz1, fz1) =
+            LensSystem::cardinal_points(&from_film, &self.trace_from_film(&from_film)?);
+        Some(([pz0, pz1], [fz0, fz1]))
+    }
+
+    /// Moves the film so objects `distance` metres away are sharp, as focusing a
+    /// real lens does. Returns whether the lens can focus that close.
+    pub fn focus(&mut self, distance: f64, height: f64) -> bool {
+        let ([pz0, pz1], [fz0, _]) = match self.thick_lens(height) {
+            Some(x) => x,
+            None => return false,
+        };
+        let f = fz0 - pz0;
+        let z = -distance;
+        let c = (pz1 - z - pz0) * (pz1 - z - 4.0 * f - pz0);
+        if c <= 0.0 {
+            return false;
+        }
+        let delta = 0.5 * (pz1 - z + pz0 - c.sqrt());
+        let last = self.elements.len() - 1;
+        self.elements[last].thickness += delta;
+        true
+    }
+
+    /// Effective focal length, in metres.
+    pub fn focal_length(&self, height: f64) -> Option<f64> {
+        let ([pz0, _], [fz0, _]) = self.thick_lens(height)?;
+        Some(fz0 - pz0)
+    }
+
+    /// Box on the rear element's plane holding every point that rays from film
+    /// points between `x0` and `x1` along the x axis get through the lens from.
+    fn bound_exit_pupil(&self, x0: f64, x1: f64) -> [f64; 4] {
+        let n = 4096;
+        let r = 1.5 * self.rear_radius();
+        let mut bounds = [f64::INFINITY, f64::INFINITY, -f64::INFINITY, -f64::INFINITY];
+        let inside =
+            |b: &[f64; 4], x: f64, y: f64| x >= b[0] && y >= b[1] && x <= b[2] && y <= b[3];
+        for i in 0..n {
+            let p_film =
+                vector3::Point::new(x0 + (x1 - x0) * (i as f64 + 0.5) / n as f64, 0.0, 0.0);
+            let (u0, u1) = (radical_inverse(2, i), radical_inverse(3, i));
+            let (x, y) = (-r + 2.0 * r * u0, -r + 2.0 * r * u1);
+            let p_rear = vector3::Point::new(x, y, self.rear_z());
+            if inside(&bounds, x, y)
+                || self
+                    .trace_from_film(&ray::Ray::new(p_film, p_rear - p_film))
+                    .is_some()
+            {
+                bounds = [
+                    bounds[0].min(x),
+                    bounds[1].min(y),
+                    bounds[2].max(x),
+                    bounds[3].max(y),
+                ];
+            }
+        }
+        if bounds[0] > bounds[2] {
+            return [-r, -r, r, r];
+        }
+        // Allow for the gaps between samples
+        let pad = 2.0 * 2.0 * r * 2f64.sqrt() / (n as f64).sqrt();
+        [
+            bounds[0] - pad,
+            bounds[1] - pad,
+            bounds[2] + pad,
+            bounds[3] + pad,
+        ]
+    }
+}
+
+/// Van der Corput sequence in `base`.
+fn radical_inverse(base: usize, mut i: usize) -> f64 {
+    let inv = 1.0 / base as f64;
+    let (mut result, mut f) = (0.0, inv);
+    while i > 0 {
+        result += (i % base) as f64 * f;
+        i /= base;
+        f *= inv;
+    }
+    result
+}
+
+/// Camera that traces every ray through a real lens prescription, giving its
+/// distortion, vignetting and change of view while focusing. Rays are aimed at
+/// the part of the rear element that light can actually leave through, found
+/// ahead of time for rings of the film.
+pub struct RealisticCamera {
+    lens: LensSystem,
+    origin: vector3::Point,
+    u: vector3::Vec3,
+    v: vector3::Vec3,
+    w: vector3::Vec3,
+    /// Film size, in metres
+    film_width: f64,
+    film_height: f64,
+    units_per_meter: f64,
+    /// Exit pupil bounds for each ring, out to the film's corner
+    exit_pupils: Vec<[f64; 4]>,
+    /// Largest of their areas, so ray weights stay below one
+    max_pupil_area: f64,
+}
+
+impl RealisticCamera {
+    /// `aperture_diameter` (millimetres) stops the lens down and `focus_dist` is
+    /// in scene units. The film is `film_diagonal` millimetres corner to corner.
+    /// Fails if the lens can't focus as close as `focus_dist`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        lookfrom: vector3::Point,
+        lookat: vector3::Point,
+        vup: vector3::Vec3,
+        mut lens: LensSystem,
+        aperture_diameter: f64,
+        focus_dist: f64,
+        film_diagonal: f64,
+        aspect_ratio: f64,
+        units_per_meter: f64,
+    ) -> io::Result<RealisticCamera> {
+        let diagonal = film_diagonal * 1e-3;
+        lens.stop_down(aperture_diameter);
+        let distance = focus_dist / units_per_meter;
+        if !lens.focus(distance, 1e-3 * diagonal) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("lens can't focus at {} m", distance),
+            ));
+        }
+
+        let film_height = diagonal / (1.0 + aspect_ratio * aspect_ratio).sqrt();
+        let rings = 16;
+        let exit_pupils: Vec<[f64; 4]> = (0..rings)
+            .map(|i| {
+                let r0 = i as f64 / rings as f64 * 0.5 * diagonal;
+                let r1 = (i + 1) as f64 / rings as f64 * 0.5 * diagonal;
+                lens.bound_exit_pupil(r0, r1)
+            })
+            .collect();
+        let max_pupil_area = exit_pupils
+            .iter()
+            .map(|b| (b[2] - b[0]) * (b[3] - b[1]))
+            .fold(0.0, f64::max);
+
+        let w = (lookfrom - lookat).unit_vector();
+        let u = vector3::cross(vup, w).unit_vector();
+        let v = vector3::cross(w, u);
+        Ok(RealisticCamera {
+            lens,
+            origin: lookfrom,
+            u,
+            v,
+            w,
+            film_width: film_height * aspect_ratio,
+            film_height,
+            units_per_meter,
+            exit_pupils,
+            max_pupil_area,
+        })
+    }
+
+    /// Point on the rear element's plane to aim at from film point `(x, y)`,
+    /// and the area of the bounds it was drawn from.
+    fn sample_exit_pupil(&self, x: f64, y: f64, u: [f64; 2]) -> (vector3::Point, f64) {
+        let r = (x * x + y * y).sqrt();
+        let half_diagonal = 0.5 * (self.film_width.powi(2) + self.film_height.powi(2)).sqrt();
+        let i = ((r / half_diagonal * self.exit_pupils.len() as f64) as usize)
+            .min(self.exit_pupils.len() - 1);
+        let b = self.exit_pupils[i];
+        let (px, py) = (b[0] + (b[2] - b[0]) * u[0], b[1] + (b[3] - b[1]) * u[1]);
+        // The bounds were found along +x, so turn them to face this point
+        let (sin, cos) = if r > 0.0 { (y / r, x / r) } else { (0.0, 1.0) };
+        (
+            vector3::Point::new(cos * px - sin * py, sin * px + cos * py, self.lens.rear_z()),
+            (b[2] - b[0]) * (b[3] - b[1]),
+        )
+    }
+}
+
+impl camera::CameraModel for RealisticCamera {
+    fn get_ray(&self, x: f64, t: f64) -> Option<ray::Ray> {
+        // The lens flips the image, so the top left of the picture is lit from
+        // the bottom right of the film
+        let p_film = vector3::Point::new(
+            (0.5 - x) * self.film_width,
+            (0.5 - t) * self.film_height,
+            0.0,
+        );
+        let u = [
+            utils::random_double(0.0, 1.0),
+            utils::random_double(0.0, 1.0),
+        ];
+        let (p_rear, area) = self.sample_exit_pupil(p_film.x, p_film.y, u);
+        let r_film = ray::Ray::new(p_film, p_rear - p_film);
+        let r = self.lens.trace_from_film(&r_film)?;
+
+        // Keep the ray with a chance equal to its weight, so the picture darkens
+        // toward the corners as the real film would
+        let cos_theta = r_film.dir.unit_vector().z;
+        let weight = cos_theta.powi(4) * area / self.max_pupil_area;
+        if utils::random_double(0.0, 1.0) >= weight {
+            return None;
+        }
+
+        let to_world = |a: vector3::Vec3| self.u * a.x + self.v * a.y - self.w * a.z;
+        Some(ray::Ray::new(
+            self.origin + to_world(r.origin) * self.units_per_meter,
+            to_world(r.dir).unit_vector(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::CameraModel;
+
+    /// Double Gauss 50mm f/2, from pbrt's scenes (US patent 2,673,491).
+    const DGAUSS: &str = "# D-GAUSS F/2 22deg HFOV
+# radius thickness eta aperture
+29.475 3.76 1.67 25.2
+84.83 0.12 1 25.2
+19.275 4.025 1.67 23
+40.77 3.275 1.699 23
+12.75 5.705 1 18
+0 4.5 0 17.1
+-14.495 1.18 1.603 17
+40.77 6.065 1.658 20
+-20.385 0.19 1 20
+437.065 3.22 1.717 20
+-39.73 0 1 20
+";
+
+    #[test]
+    fn parses_prescriptions() {
+        let lens = LensSystem::parse(DGAUSS).unwrap();
+        assert_eq!(lens.elements.len(), 11);
+        assert!(lens.elements[5].is_stop());
+        assert!((lens.elements[0].aperture_radius - 0.0126).abs() < 1e-12);
+        assert!(LensSystem::parse("1 2 3").is_err());
+    }
+
+    #[test]
+    fn double_gauss_is_about_fifty_millimetres() {
+        let lens = LensSystem::parse(DGAUSS).unwrap();
+        let f = lens.focal_length(1e-5).unwrap();
+        assert!((f - 0.05).abs() < 0.002, "f = {}", f);
+    }
+
+    #[test]
+    fn focused_points_image_sharply() {
+        let mut lens = LensSystem::parse(DGAUSS).unwrap();
+        assert!(lens.focus(2.0, 1e-5));
+        // Rays from a point on the axis 2m away meet again at the film
+        for h in [0.5e-3, 1e-3, 2e-3] {
+            let o = vector3::Point::new(0.0, 0.0, lens.front_z() + 2.0);
+            let target = vector3::Point::new(h, 0.0, lens.front_z());
+            let r = lens
+                .trace_from_scene(&ray::Ray::new(o, target - o))
+                .unwrap();
+            let t = -r.origin.z / r.dir.z;
+            assert!(r.at(t).x.abs() < 2e-5, "height {}: {}", h, r.at(t).x);
+        }
+    }
+
+    #[test]
+    fn camera_looks_toward_lookat_and_vignettes() {
+        let lens = LensSystem::parse(DGAUSS).unwrap();
+        let cam = RealisticCamera::new(
+            vector3::Point::new(0.0, 0.0, 0.0),
+            vector3::Point::new(0.0, 0.0, -1.0),
+            vector3::Vec3::new(0.0, 1.0, 0.0),
+            lens,
+            25.0,
+            5.0,
+            43.3,
+            1.5,
+            1.0,
+        )
+        .unwrap();
+        let kept = |x, t| {
+            (0..2000)
+                .filter_map(|_| cam.get_ray(x, t))
+                .map(|r| r.dir)
+                .collect::<Vec<_>>()
+        };
+        let centre = kept(0.5, 0.5);
+        assert!(centre.iter().all(|d| d.z < -0.99));
+        // The top right of the picture looks up and to the right
+        let corner = kept(1.0, 1.0);
+        assert!(corner.iter().all(|d| d.x > 0.0 && d.y > 0.0));
+        assert!(corner.len() < centre.len());
+    }
+
+    #[test]
+    fn camera_refuses_focus_closer_than_the_lens_allows() {
+        let cam = RealisticCamera::new(
+            vector3::Point::new(0.0, 0.0, 0.0),
+            vector3::Point::new(0.0, 0.0, -1.0),
+            vector3::Vec3::new(0.0, 1.0, 0.0),
+            LensSystem::parse(DGAUSS).unwrap(),
+            25.0,
+            0.05,
+            43.3,
+            1.5,
+            1.0,
+        );
+        assert_eq!(cam.err().unwrap().kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,649 @@
+use crate::aabb;
+use crate::environment;
+use crate::hittable;
+use crate::hittable::Hittable;
+use crate::ies;
+use crate::light_sampler;
+use crate::material::MaterialTrait;
+use crate::onb;
+use crate::ray;
+use crate::vector3;
+use std::f64::consts::PI;
+use std::sync::OnceLock;
+
+/// Light arriving at a point from one sampled direction.
+pub struct LightSample {
+    /// Unit direction toward the light
+    pub wi: vector3::Vec3,
+    /// Distance to the sampled point, for the shadow ray
+    pub dist: f64,
+    pub radiance: vector3::Color,
+    /// Density per unit solid angle
+    pub pdf: f64,
+}
+
+pub trait LightTrait {
+    /// Samples a direction from `p` toward the light. Delta lights give a `pdf` of 1.
+    fn sample_li(&self, p: vector3::Point, u: [f64; 2]) -> Option<LightSample>;
+    /// Solid angle density with which `sample_li` picks `wi` from `p`. Always zero for
+    /// delta lights, which no other direction sampling can find.
+    fn pdf_li(&self, p: vector3::Point, wi: vector3::Vec3) -> f64;
+
+    /// Lights that only exist at a single point or in a single direction.
+    fn is_delta(&self) -> bool {
+        false
+    }
+
+    /// Radiance from lights at infinity along a ray that leaves the scene.
+    fn le(&self, _r: &ray::Ray) -> vector3::Color {
+        vector3::Color::new(0.0, 0.0, 0.0)
+    }
+
+    /// Where the light is and how it shines, for choosing between lights.
+    /// `None` for lights at infinity.
+    fn bounds(&self) -> Option<light_sampler::LightBounds> {
+        None
+    }
+}
+
+pub enum Light {
+    Area(AreaLight),
+    Point(PointLight),
+    Spot(SpotLight),
+    Directional(DirectionalLight),
+    Environment(environment::EnvironmentLight),
+    Photometric(ies::PhotometricLight),
+}
+
+impl LightTrait for Light {
+    fn sample_li(&self, p: vector3::Point, u: [f64; 2]) -> Option<LightSample> {
+        match self {
+            Light::Area(x) => x.sample_li(p, u),
+            Light::Point(x) => x.sample_li(p, u),
+            Light::Spot(x) => x.sample_li(p, u),
+            Light::Directional(x) => x.sample_li(p, u),
+            Light::Environment(x) => x.sample_li(p, u),
+            Light::Photometric(x) => x.sample_li(p, u),
+        }
+    }
+
+    fn pdf_li(&self, p: vector3::Point, wi: vector3::Vec3) -> f64 {
+        match self {
+            Light::Area(x) => x.pdf_li(p, wi),
+            Light::Point(x) => x.pdf_li(p, wi),
+            Light::Spot(x) => x.pdf_li(p, wi),
+            Light::Directional(x) => x.pdf_li(p, wi),
+            Light::Environment(x) => x.pdf_li(p, wi),
+            Light::Photometric(x) => x.pdf_li(p, wi),
+        }
+    }
+
+    fn is_delta(&self) -> bool {
+        match self {
+            Light::Area(x) => x.is_delta(),
+            Light::Point(x) => x.is_delta(),
+            Light::Spot(x) => x.is_delta(),
+            Light::Directional(x) => x.is_delta(),
+            Light::Environment(x) => x.is_delta(),
+            Light::Photometric(x) => x.is_delta(),
+        }
+    }
+
+    fn le(&self, r: &ray::Ray) -> vector3::Color {
+        match self {
+            Light::Area(x) => x.le(r),
+            Light::Point(x) => x.le(r),
+            Light::Spot(x) => x.le(r),
+            Light::Directional(x) => x.le(r),
+            Light::Environment(x) => x.le(r),
+            Light::Photometric(x) => x.le(r),
+        }
+    }
+
+    fn bounds(&self) -> Option<light_sampler::LightBounds> {
+        match self {
+            Light::Area(x) => x.bounds(),
+            Light::Point(x) => x.bounds(),
+            Light::Spot(x) => x.bounds(),
+            Light::Directional(x) => x.bounds(),
+            Light::Environment(x) => x.bounds(),
+            Light::Photometric(x) => x.bounds(),
+        }
+    }
+}
+
+/// An emissive shape that can be sampled directly: a sphere, quad or triangle.
+/// The emission comes from the shape's material, so the shape should also be in
+/// the world for camera and BSDF rays to see it. Emission textures, one- or
+/// two-sided emission and camera visibility are all set on the material.
+pub struct AreaLight {
+    shape: hittable::HittableObj,
+}
+
+impl AreaLight {
+    pub fn new(shape: hittable::HittableObj) -> AreaLight {
+        AreaLight { shape }
+    }
+
+    pub fn shape(&self) -> &hittable::HittableObj {
+        &self.shape
+    }
+
+    /// Bounds of an emissive sphere, quad or triangle, with its power from the
+    /// material's average emission. Materials that can't give one, such as those
+    /// with procedural textures, are averaged over a grid of points instead, with
+    /// flat shapes looked at from both sides to tell whether they emit from either.
+    fn shape_bounds(shape: &hittable::HittableObj) -> Option<light_sampler::LightBounds> {
+        let bounds = shape.bounding_box()?;
+        let (area, normal) = match shape {
+            hittable::HittableObj::Sphere(s) => (s.area(), None),
+            hittable::HittableObj::Quad(q) => (q.area(), Some(q.normal())),
+            hittable::HittableObj::Triangle(t) => (t.area(), Some(t.normal())),
+            _ => return None,
+        };
+        let center = (bounds.minimum + bounds.maximum) * 0.5;
+        let size = (bounds.maximum - bounds.minimum).length();
+        // Average luminance seen from far enough along `dir` to see the whole side
+        let seen_from = |dir: vector3::Vec3| {
+            let eye = center + dir * (4.0 * size);
+            let n = 4;
+            let mut luminance = 0.0;
+            for i in 0..n {
+                for j in 0..n {
+                    let u = [(i as f64 + 0.5) / n as f64, (j as f64 + 0.5) / n as f64];
+                    if let Some((rec, _)) = shape.sample(eye, u) {
+                        luminance += rec.material.lock().unwrap().emitted(&rec).luminance();
+                    }
+                }
+            }
+            luminance / (n * n) as f64
+        };
+
+        let up = vector3::Vec3::new(0.0, 1.0, 0.0);
+        // Look along the normal, since a flat shape seen edge on can't be sampled
+        let (rec, _) = shape.sample(center + normal.unwrap_or(up) * (4.0 * size), [0.5, 0.5])?;
+        let average = rec.material.lock().unwrap().average_emitted();
+        let (w, cos_theta_o, front, back) = match (normal, average) {
+            (Some(n), Some((front, back))) => (n, 1.0, front.luminance(), back.luminance()),
+            (Some(n), None) => (n, 1.0, seen_from(n), seen_from(n * -1.0)),
+            // A sphere's normals point every way
+            (None, Some((front, _))) => (up, -1.0, front.luminance(), 0.0),
+            (None, None) => (up, -1.0, seen_from(up), 0.0),
+        };
+        let w = if front == 0.0 && back > 0.0 {
+            w * -1.0
+        } else {
+            w
+        };
+        Some(light_sampler::LightBounds {
+            bounds,
+            phi: PI * area * (front + back),
+            w,
+            cos_theta_o,
+            cos_theta_e: (0.5 * PI).cos(),
+            two_sided: front > 0.0 && back > 0.0,
+        })
+    }
+}
+
+impl LightTrait for AreaLight {
+    fn sample_li(&self, p: vector3::Point, u: [f64; 2]) -> Option<LightSample> {
+        let (rec, pdf) = self.shape.sample(p, u)?;
+        let d = rec.p - p;
+        let dist = d.length();
+        if pdf <= 0.0 || dist == 0.0 {
+            return None;
+        }
+        let radiance = rec.material.lock().unwrap().emitted(&rec);
+        Some(LightSample {
+            wi: d / dist,
+            dist,
+            radiance,
+            pdf,
+        })
+    }
+
+    fn pdf_li(&self, p: vector3::Point, wi: vector3::Vec3) -> f64 {
+        self.shape.pdf_value(p, wi)
+    }
+
+    /// Worked out afresh each time, so a light sampler built after the material
+    /// changes follows its new power.
+    fn bounds(&self) -> Option<light_sampler::LightBounds> {
+        AreaLight::shape_bounds(&self.shape)
+    }
+}
+
+/// Light radiating `intensity` evenly in every direction from one point.
+pub struct PointLight {
+    position: vector3::Point,
+    intensity: vector3::Color,
+}
+
+impl PointLight {
+    pub fn new(position: vector3::Point, intensity: vector3::Color) -> PointLight {
+        PointLight {
+            position,
+            intensity,
+        }
+    }
+}
+
+/// Direction and distance from `p` to the light at `position`.
+fn toward(p: vector3::Point, position: vector3::Point) -> Option<(vector3::Vec3, f64)> {
+    let d = position - p;
+    let dist = d.length();
+    if dist == 0.0 {
+        return None;
+    }
+    Some((d / dist, dist))
+}
+
+impl LightTrait for PointLight {
+    fn sample_li(&self, p: vector3::Point, _u: [f64; 2]) -> Option<LightSample> {
+        let (wi, dist) = toward(p, self.position)?;
+        Some(LightSample {
+            wi,
+            dist,
+            radiance: self.intensity / (dist * dist),
+            pdf: 1.0,
+        })
+    }
+
+    fn pdf_li(&self, _p: vector3::Point, _wi: vector3::Vec3) -> f64 {
+        0.0
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+
+    fn bounds(&self) -> Option<light_sampler::LightBounds> {
+        Some(light_sampler::LightBounds {
+            bounds: aabb::Aabb::new(self.position, self.position),
+            phi: 4.0 * PI * self.intensity.luminance(),
+            w: vector3::Vec3::new(0.0, 1.0, 0.0),
+            cos_theta_o: -1.0,
+            cos_theta_e: (0.5 * PI).cos(),
+            two_sided: false,
+        })
+    }
+}
+
+/// Point light that only shines within a cone. Full `intensity` inside
+/// `falloff_start` degrees of the axis, fading smoothly to nothing at `cone_angle`.
+pub struct SpotLight {
+    position: vector3::Point,
+    axis: vector3::Vec3,
+    intensity: vector3::Color,
+    cos_total: f64,
+    cos_falloff_start: f64,
+}
+
+impl SpotLight {
+    pub fn new(
+        position: vector3::Point,
+        target: vector3::Point,
+        intensity: vector3::Color,
+        cone_angle: f64,
+        falloff_start: f64,
+    ) -> SpotLight {
+        SpotLight {
+            position,
+            axis: (target - position).unit_vector(),
+            intensity,
+            cos_total: cone_angle.to_radians().cos(),
+            cos_falloff_start: falloff_start.min(cone_angle).to_radians().cos(),
+        }
+    }
+
+    /// Fraction of the intensity sent out along `w`.
+    fn falloff(&self, w: vector3::Vec3) -> f64 {
+        let cos_theta = vector3::dot(w, self.axis);
+        if cos_theta >= self.cos_falloff_start {
+            return 1.0;
+        }
+        if cos_theta <= self.cos_total {
+            return 0.0;
+        }
+        let t = (cos_theta - self.cos_total) / (self.cos_falloff_start - self.cos_total);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl LightTrait for SpotLight {
+    fn sample_li(&self, p: vector3::Point, _u: [f64; 2]) -> Option<LightSample> {
+        let (wi, dist) = toward(p, self.position)?;
+        let falloff = self.falloff(wi * -1.0);
+        if falloff == 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            wi,
+            dist,
+            radiance: self.intensity * (falloff / (dist * dist)),
+            pdf: 1.0,
+        })
+    }
+
+    fn pdf_li(&self, _p: vector3::Point, _wi: vector3::Vec3) -> f64 {
+        0.0
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+
+    fn bounds(&self) -> Option<light_sampler::LightBounds> {
+        // Treated as a point light whose normals fill the full-strength cone,
+        // shining out as far as the edge of the cone.
+        let theta_o = self.cos_falloff_start.acos();
+        Some(light_sampler::LightBounds {
+            bounds: aabb::Aabb::new(self.position, self.position),
+            phi: 4.0 * PI * self.intensity.luminance(),
+            w: self.axis,
+            cos_theta_o: self.cos_falloff_start,
+            cos_theta_e: (self.cos_total.acos() - theta_o).cos(),
+            two_sided: false,
+        })
+    }
+}
+
+/// Distant light such as the sun, giving `irradiance` on a surface facing it.
+/// With an angular diameter above zero it's a disk in the sky and casts soft
+/// shadows; the irradiance stays the same whatever the size.
+pub struct DirectionalLight {
+    frame: onb::Onb,
+    radiance: vector3::Color,
+    /// `1 - cos` of the disk's angular radius, zero for a delta light
+    one_minus_cos_max: f64,
+}
+
+impl DirectionalLight {
+    /// `to_light` points from the scene toward the light; `angular_diameter` is in degrees.
+    pub fn new(
+        to_light: vector3::Vec3,
+        irradiance: vector3::Color,
+        angular_diameter: f64,
+    ) -> DirectionalLight {
+        let theta_max = 0.5 * angular_diameter.to_radians();
+        let sin_half = (0.5 * theta_max).sin();
+        let one_minus_cos_max = 2.0 * sin_half * sin_half;
+        let radiance = if one_minus_cos_max > 0.0 {
+            irradiance / (PI * theta_max.sin().powi(2))
+        } else {
+            irradiance
+        };
+        DirectionalLight {
+            frame: onb::Onb::build_from_w(to_light),
+            radiance,
+            one_minus_cos_max,
+        }
+    }
+
+    fn in_disk(&self, w: vector3::Vec3) -> bool {
+        1.0 - vector3::dot(w.unit_vector(), self.frame.w) < self.one_minus_cos_max
+    }
+}
+
+impl LightTrait for DirectionalLight {
+    fn sample_li(&self, _p: vector3::Point, u: [f64; 2]) -> Option<LightSample> {
+        if self.is_delta() {
+            return Some(LightSample {
+                wi: self.frame.w,
+                dist: f64::INFINITY,
+                radiance: self.radiance,
+                pdf: 1.0,
+            });
+        }
+        let cos_theta = 1.0 - u[0] * self.one_minus_cos_max;
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * u[1];
+        Some(LightSample {
+            wi: self.frame.local(vector3::Vec3::new(
+                sin_theta * phi.cos(),
+                sin_theta * phi.sin(),
+                cos_theta,
+            )),
+            dist: f64::INFINITY,
+            radiance: self.radiance,
+            pdf: 1.0 / (2.0 * PI * self.one_minus_cos_max),
+        })
+    }
+
+    fn pdf_li(&self, _p: vector3::Point, wi: vector3::Vec3) -> f64 {
+        if self.is_delta() || !self.in_disk(wi) {
+            return 0.0;
+        }
+        1.0 / (2.0 * PI * self.one_minus_cos_max)
+    }
+
+    fn is_delta(&self) -> bool {
+        self.one_minus_cos_max == 0.0
+    }
+
+    fn le(&self, r: &ray::Ray) -> vector3::Color {
+        if self.is_delta() || !self.in_disk(r.dir) {
+            return vector3::Color::new(0.0, 0.0, 0.0);
+        }
+        self.radiance
+    }
+}
+
+/// The lights the integrator samples directly. Which one to sample at a point is
+/// left to a `light_sampler::LightSampler`, built the first time it's needed.
+#[derive(Default)]
+pub struct LightList {
+    lights: Vec<Light>,
+    strategy: light_sampler::Strategy,
+    sampler: OnceLock<light_sampler::LightSampler>,
+}
+
+impl LightList {
+    pub fn new() -> LightList {
+        LightList::default()
+    }
+
+    pub fn add(&mut self, light: Light) {
+        self.lights.push(light);
+        self.sampler = OnceLock::new();
+    }
+
+    pub fn set_strategy(&mut self, strategy: light_sampler::Strategy) {
+        self.strategy = strategy;
+        self.sampler = OnceLock::new();
+    }
+
+    /// Has the light sampler built again on next use, for after editing the
+    /// material of an area light.
+    pub fn refresh(&mut self) {
+        self.sampler = OnceLock::new();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    fn sampler(&self) -> &light_sampler::LightSampler {
+        self.sampler
+            .get_or_init(|| light_sampler::LightSampler::new(&self.lights, self.strategy))
+    }
+
+    /// Picks a light to sample from a surface at `p` with normal `n` (zero if it
+    /// can be lit from both sides) with `u` in [0, 1), returning it and the
+    /// chance it was picked.
+    pub fn choose(&self, p: vector3::Point, n: vector3::Vec3, u: f64) -> Option<(&Light, f64)> {
+        if self.lights.is_empty() {
+            return None;
+        }
+        let (i, pmf) = self.sampler().sample(p, n, u)?;
+        Some((&self.lights[i], pmf))
+    }
+
+    /// Whether an environment light replaces the default sky.
+    pub fn has_environment(&self) -> bool {
+        self.lights
+            .iter()
+            .any(|l| matches!(l, Light::Environment(_)))
+    }
+
+    /// Radiance along an escaping ray from every light at infinity.
+    pub fn le(&self, r: &ray::Ray) -> vector3::Color {
+        self.lights
+            .iter()
+            .fold(vector3::Color::new(0.0, 0.0, 0.0), |c, l| c + l.le(r))
+    }
+
+    /// Density with which light sampling from `p` with normal `n` finds `wi`:
+    /// through any light at infinity, or through the first area light along it.
+    pub fn pdf(&self, p: vector3::Point, n: vector3::Vec3, wi: vector3::Vec3) -> f64 {
+        if self.lights.is_empty() {
+            return 0.0;
+        }
+        let sampler = self.sampler();
+        let mut pdf: f64 = sampler
+            .infinite()
+            .iter()
+            .map(|&i| sampler.pmf(p, n, i) * self.lights[i].pdf_li(p, wi))
+            .sum();
+        if let Some(i) = sampler.hit(&self.lights, &ray::Ray::new(p, wi)) {
+            pdf += sampler.pmf(p, n, i) * self.lights[i].pdf_li(p, wi);
+        }
+        pdf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn point_light_falls_off_with_distance_squared() {
+        let light = PointLight::new(
+            vector3::Point::new(0.0, 4.0, 0.0),
+            vector3::Color::new(8.0, 8.0, 8.0),
+        );
+        let ls = light
+            .sample_li(vector3::Point::new(0.0, 0.0, 0.0), [0.5, 0.5])
+            .unwrap();
+        assert!((ls.radiance.x - 0.5).abs() < 1e-12);
+        assert!((ls.dist - 4.0).abs() < 1e-12 && (ls.wi.y - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn spot_light_cone() {
+        let light = SpotLight::new(
+            vector3::Point::new(0.0, 1.0, 0.0),
+            vector3::Point::new(0.0, 0.0, 0.0),
+            vector3::Color::new(1.0, 1.0, 1.0),
+            30.0,
+            20.0,
+        );
+        let at = |x: f64| light.sample_li(vector3::Point::new(x, 0.0, 0.0), [0.5, 0.5]);
+        // 10 degrees off axis is at full strength, 40 degrees is outside the cone,
+        // and 25 degrees is partly faded.
+        let inside = at(10f64.to_radians().tan()).unwrap();
+        assert!((inside.radiance.x * inside.dist * inside.dist - 1.0).abs() < 1e-9);
+        assert!(at(40f64.to_radians().tan()).is_none());
+        let edge = at(25f64.to_radians().tan()).unwrap();
+        let f = edge.radiance.x * edge.dist * edge.dist;
+        assert!(f > 0.0 && f < 1.0);
+    }
+
+    #[test]
+    fn sun_disk_keeps_irradiance() {
+        let to_light = vector3::Vec3::new(0.0, 1.0, 0.0);
+        let e = vector3::Color::new(3.0, 3.0, 3.0);
+        let p = vector3::Point::new(0.0, 0.0, 0.0);
+        for diameter in [0.0, 0.53, 10.0] {
+            let sun = DirectionalLight::new(to_light, e, diameter);
+            // Irradiance on a surface facing the sun, estimated by sampling the disk
+            let n = 64;
+            let mut sum = 0.0;
+            for i in 0..n {
+                let u = [(i as f64 + 0.5) / n as f64, (i as f64 * 0.618) % 1.0];
+                let ls = sun.sample_li(p, u).unwrap();
+                sum += ls.radiance.x * ls.wi.y / ls.pdf;
+                if !sun.is_delta() {
+                    assert!((sun.pdf_li(p, ls.wi) - ls.pdf).abs() < 1e-6 * ls.pdf);
+                    assert!(sun.le(&ray::Ray::new(p, ls.wi)).x > 0.0);
+                }
+            }
+            assert!((sum / n as f64 - 3.0).abs() < 0.02, "diameter {}", diameter);
+        }
+    }
+
+    #[test]
+    fn area_light_power_counts_both_sides() {
+        use crate::material;
+        use crate::quad;
+        use std::sync::{Arc, Mutex};
+
+        for two_sided in [false, true] {
+            let mut emit = material::DiffuseLight::new(vector3::Color::new(2.0, 2.0, 2.0));
+            emit.set_two_sided(two_sided);
+            let q = quad::Quad::new(
+                vector3::Point::new(0.0, 0.0, 0.0),
+                vector3::Vec3::new(3.0, 0.0, 0.0),
+                vector3::Vec3::new(0.0, 0.0, 1.0),
+                Arc::new(Mutex::new(material::Material::DiffuseLight(emit))),
+            );
+            let light = AreaLight::new(hittable::HittableObj::Quad(q));
+            let b = light.bounds().unwrap();
+            let sides = if two_sided { 2.0 } else { 1.0 };
+            assert!((b.phi - PI * 3.0 * 2.0 * sides).abs() < 1e-9);
+            assert_eq!(b.two_sided, two_sided);
+        }
+    }
+
+    #[test]
+    fn area_light_power_follows_its_texture_and_edits() {
+        use crate::material;
+        use crate::quad;
+        use crate::texture;
+        use std::sync::{Arc, Mutex};
+
+        // A fine checker of lit and dark squares, finer than any sparse grid of samples
+        let checker = Arc::new(texture::Texture::UvChecker(texture::UvCheckerTexture::new(
+            8.0,
+            8.0,
+            texture::solid(vector3::Color::new(0.0, 0.0, 0.0)),
+            texture::solid(vector3::Color::new(4.0, 4.0, 4.0)),
+        )));
+        let emit = Arc::new(Mutex::new(material::Material::DiffuseLight(
+            material::DiffuseLight::from_texture(checker),
+        )));
+        let q = quad::Quad::new(
+            vector3::Point::new(0.0, 0.0, 0.0),
+            vector3::Vec3::new(1.0, 0.0, 0.0),
+            vector3::Vec3::new(0.0, 0.0, 1.0),
+            emit.clone(),
+        );
+        let light = AreaLight::new(hittable::HittableObj::Quad(q));
+        assert!((light.bounds().unwrap().phi - PI * 2.0).abs() < 1e-9);
+
+        if let material::Material::DiffuseLight(x) = &mut *emit.lock().unwrap() {
+            x.set_scale(3.0);
+        }
+        assert!((light.bounds().unwrap().phi - PI * 6.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn vertical_area_light_has_bounds() {
+        use crate::material;
+        use crate::quad;
+        use std::sync::{Arc, Mutex};
+
+        let emit = material::DiffuseLight::new(vector3::Color::new(2.0, 2.0, 2.0));
+        let q = quad::Quad::new(
+            vector3::Point::new(0.0, 0.0, 0.0),
+            vector3::Vec3::new(1.0, 0.0, 0.0),
+            vector3::Vec3::new(0.0, 1.0, 0.0),
+            Arc::new(Mutex::new(material::Material::DiffuseLight(emit))),
+        );
+        let light = AreaLight::new(hittable::HittableObj::Quad(q));
+        let b = light.bounds().unwrap();
+        assert!((b.phi - PI * 2.0).abs() < 1e-9);
+        assert!(b.w.y.abs() < 1e-9);
+    }
+}
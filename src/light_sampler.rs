@@ -0,0 +1,665 @@
+use crate::aabb;
+use crate::distribution;
+use crate::hittable::Hittable;
+use crate::light;
+use crate::light::LightTrait;
+use crate::ray;
+use crate::vector3;
+use std::f64::consts::PI;
+
+/// How the integrator picks which light to sample at a shading point.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Strategy {
+    /// Every light equally often
+    Uniform,
+    /// In proportion to each light's emitted power, wherever the shading point is
+    Power,
+    /// By each light's estimated contribution at the shading point, found by
+    /// walking a bounding volume hierarchy over the lights
+    #[default]
+    Bvh,
+}
+
+/// Where a light is, which way it shines and how much it emits, loosely enough
+/// to cover a whole cluster of lights (Conty Estevez and Kulla 2018).
+#[derive(Copy, Clone)]
+pub struct LightBounds {
+    pub bounds: aabb::Aabb,
+    /// Emitted power, as luminance
+    pub phi: f64,
+    /// Axis of the cone holding every surface normal of the emitters
+    pub w: vector3::Vec3,
+    /// Cosine of that cone's half angle
+    pub cos_theta_o: f64,
+    /// Cosine of how far past its normal each emitter still shines
+    pub cos_theta_e: f64,
+    /// Whether emitters shine out of both sides
+    pub two_sided: bool,
+}
+
+/// `cos(max(0, a - b))` from the sines and cosines of `a` and `b`.
+fn cos_sub_clamped(sin_a: f64, cos_a: f64, sin_b: f64, cos_b: f64) -> f64 {
+    if cos_a > cos_b {
+        return 1.0;
+    }
+    cos_a * cos_b + sin_a * sin_b
+}
+
+/// `sin(max(0, a - b))` from the sines and cosines of `a` and `b`.
+fn sin_sub_clamped(sin_a: f64, cos_a: f64, sin_b: f64, cos_b: f64) -> f64 {
+    if cos_a > cos_b {
+        return 0.0;
+    }
+    sin_a * cos_b - cos_a * sin_b
+}
+
+fn sin_from_cos(cos: f64) -> f64 {
+    (1.0 - cos * cos).max(0.0).sqrt()
+}
+
+/// Turns `v` by `angle` radians around the unit `axis`.
+fn rotate(v: vector3::Vec3, axis: vector3::Vec3, angle: f64) -> vector3::Vec3 {
+    let (sin, cos) = angle.sin_cos();
+    v * cos + vector3::cross(axis, v) * sin + axis * (vector3::dot(axis, v) * (1.0 - cos))
+}
+
+/// Smallest cone holding two cones, each given by its axis and the cosine of
+/// its half angle.
+fn union_cones(
+    w_a: vector3::Vec3,
+    cos_a: f64,
+    w_b: vector3::Vec3,
+    cos_b: f64,
+) -> (vector3::Vec3, f64) {
+    let whole = (w_a, -1.0);
+    let theta_a = cos_a.clamp(-1.0, 1.0).acos();
+    let theta_b = cos_b.clamp(-1.0, 1.0).acos();
+    let theta_d = vector3::dot(w_a, w_b).clamp(-1.0, 1.0).acos();
+    if (theta_d + theta_b).min(PI) <= theta_a {
+        return (w_a, cos_a);
+    }
+    if (theta_d + theta_a).min(PI) <= theta_b {
+        return (w_b, cos_b);
+    }
+    let theta_o = 0.5 * (theta_a + theta_d + theta_b);
+    if theta_o >= PI {
+        return whole;
+    }
+    let axis = vector3::cross(w_a, w_b);
+    if axis.length_squared() == 0.0 {
+        return whole;
+    }
+    (
+        rotate(w_a, axis.unit_vector(), theta_o - theta_a),
+        theta_o.cos(),
+    )
+}
+
+fn centroid(b: &aabb::Aabb) -> vector3::Point {
+    (b.minimum + b.maximum) * 0.5
+}
+
+fn surface_area(b: &aabb::Aabb) -> f64 {
+    let d = b.maximum - b.minimum;
+    2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+}
+
+impl LightBounds {
+    pub fn union(&self, other: &LightBounds) -> LightBounds {
+        let (w, cos_theta_o) = union_cones(self.w, self.cos_theta_o, other.w, other.cos_theta_o);
+        LightBounds {
+            bounds: aabb::surrounding_box(self.bounds, other.bounds),
+            phi: self.phi + other.phi,
+            w,
+            cos_theta_o,
+            cos_theta_e: self.cos_theta_e.min(other.cos_theta_e),
+            two_sided: self.two_sided || other.two_sided,
+        }
+    }
+
+    /// Conservative estimate of the light these emitters could send to a surface
+    /// at `p` with normal `n`. Pass a zero normal for surfaces lit from both sides.
+    pub fn importance(&self, p: vector3::Point, n: vector3::Vec3) -> f64 {
+        let pc = centroid(&self.bounds);
+        let diagonal = self.bounds.maximum - self.bounds.minimum;
+        // Keep points inside a cluster from blowing up the inverse square
+        let d2 = (p - pc).length_squared().max(0.5 * diagonal.length());
+        if d2 == 0.0 {
+            return 0.0;
+        }
+
+        // Angle between the cone axis and the direction to `p`
+        let wi = (p - pc).unit_vector();
+        let mut cos_theta_w = vector3::dot(self.w, wi);
+        if self.two_sided {
+            cos_theta_w = cos_theta_w.abs();
+        }
+        let sin_theta_w = sin_from_cos(cos_theta_w);
+
+        // Half angle the bounds subtend from `p`, everything if `p` is inside them
+        let r2 = 0.25 * diagonal.length_squared();
+        let cos_theta_b = if (p - pc).length_squared() < r2 {
+            -1.0
+        } else {
+            (1.0 - r2 / (p - pc).length_squared()).max(0.0).sqrt()
+        };
+        let sin_theta_b = sin_from_cos(cos_theta_b);
+
+        // Smallest angle any emitter's normal can make with the direction to `p`
+        let sin_theta_o = sin_from_cos(self.cos_theta_o);
+        let cos_theta_x = cos_sub_clamped(sin_theta_w, cos_theta_w, sin_theta_o, self.cos_theta_o);
+        let sin_theta_x = sin_sub_clamped(sin_theta_w, cos_theta_w, sin_theta_o, self.cos_theta_o);
+        let cos_theta_p = cos_sub_clamped(sin_theta_x, cos_theta_x, sin_theta_b, cos_theta_b);
+        if cos_theta_p <= self.cos_theta_e {
+            return 0.0;
+        }
+
+        let mut importance = self.phi * cos_theta_p / d2;
+        if n.length_squared() > 0.0 {
+            let cos_theta_i = vector3::dot(wi, n.unit_vector()).abs();
+            let sin_theta_i = sin_from_cos(cos_theta_i);
+            importance *= cos_sub_clamped(sin_theta_i, cos_theta_i, sin_theta_b, cos_theta_b);
+        }
+        importance.max(0.0)
+    }
+
+    /// Surface area orientation heuristic: the cost of a node holding these
+    /// emitters, with `kr` penalising thin splits along the chosen axis.
+    fn cost(&self, kr: f64) -> f64 {
+        let theta_o = self.cos_theta_o.clamp(-1.0, 1.0).acos();
+        let theta_e = self.cos_theta_e.clamp(-1.0, 1.0).acos();
+        let theta_w = (theta_o + theta_e).min(PI);
+        let sin_theta_o = sin_from_cos(self.cos_theta_o);
+        let m_omega = 2.0 * PI * (1.0 - self.cos_theta_o)
+            + 0.5
+                * PI
+                * (2.0 * theta_w * sin_theta_o
+                    - (theta_o - 2.0 * theta_w).cos()
+                    - 2.0 * theta_o * sin_theta_o
+                    + self.cos_theta_o);
+        self.phi * m_omega * kr * surface_area(&self.bounds)
+    }
+}
+
+enum NodeKind {
+    /// Index into `LightBvh::lights`
+    Leaf(usize),
+    Interior(usize, usize),
+}
+
+struct Node {
+    bounds: LightBounds,
+    parent: Option<usize>,
+    kind: NodeKind,
+}
+
+/// Binary tree over lights with bounds. Sampling walks down from the root,
+/// choosing each child by its importance at the shading point.
+pub struct LightBvh {
+    nodes: Vec<Node>,
+    /// Index of each light in the light list, in the order they were given
+    lights: Vec<usize>,
+    /// Leaf node of each light, in the same order
+    leaves: Vec<usize>,
+}
+
+const BUCKETS: usize = 12;
+
+impl LightBvh {
+    /// `items` pairs each light's index in the light list with its bounds.
+    pub fn new(items: Vec<(usize, LightBounds)>) -> LightBvh {
+        let mut bvh = LightBvh {
+            nodes: Vec::with_capacity(2 * items.len()),
+            lights: items.iter().map(|(i, _)| *i).collect(),
+            leaves: vec![0; items.len()],
+        };
+        // Build over slots into `lights` rather than light indices
+        let mut items: Vec<(usize, LightBounds)> = items
+            .into_iter()
+            .enumerate()
+            .map(|(slot, (_, b))| (slot, b))
+            .collect();
+        if !items.is_empty() {
+            bvh.build(&mut items, None);
+        }
+        bvh
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    fn build(&mut self, items: &mut [(usize, LightBounds)], parent: Option<usize>) -> usize {
+        let index = self.nodes.len();
+        if items.len() == 1 {
+            self.nodes.push(Node {
+                bounds: items[0].1,
+                parent,
+                kind: NodeKind::Leaf(items[0].0),
+            });
+            self.leaves[items[0].0] = index;
+            return index;
+        }
+
+        let bounds = items[1..].iter().fold(items[0].1, |b, (_, l)| b.union(l));
+        let mid = LightBvh::split(items, &bounds);
+        self.nodes.push(Node {
+            bounds,
+            parent,
+            kind: NodeKind::Interior(0, 0),
+        });
+        let (below, above) = items.split_at_mut(mid);
+        let left = self.build(below, Some(index));
+        let right = self.build(above, Some(index));
+        self.nodes[index].kind = NodeKind::Interior(left, right);
+        index
+    }
+
+    /// Reorders `items` so the two halves of the cheapest bucketed split come
+    /// first and last, returning where the second half starts.
+    fn split(items: &mut [(usize, LightBounds)], bounds: &LightBounds) -> usize {
+        let c = |l: &LightBounds| centroid(&l.bounds);
+        let first = c(&items[0].1);
+        let (lo, hi) = items.iter().fold((first, first), |(lo, hi), (_, l)| {
+            let p = c(l);
+            (
+                vector3::Point::new(lo.x.min(p.x), lo.y.min(p.y), lo.z.min(p.z)),
+                vector3::Point::new(hi.x.max(p.x), hi.y.max(p.y), hi.z.max(p.z)),
+            )
+        });
+        let extent = bounds.bounds.maximum - bounds.bounds.minimum;
+        let max_extent = extent.x.max(extent.y).max(extent.z);
+        let bucket_of = |l: &LightBounds, dim: usize| {
+            let t = (c(l)[dim] - lo[dim]) / (hi[dim] - lo[dim]);
+            ((t * BUCKETS as f64) as usize).min(BUCKETS - 1)
+        };
+
+        let mut best: Option<(f64, usize, usize)> = None;
+        for dim in 0..3 {
+            if hi[dim] <= lo[dim] {
+                continue;
+            }
+            let mut buckets: [Option<LightBounds>; BUCKETS] = [None; BUCKETS];
+            for (_, l) in items.iter() {
+                let b = &mut buckets[bucket_of(l, dim)];
+                *b = Some(b.map_or(*l, |x| x.union(l)));
+            }
+            let kr = max_extent / extent[dim].max(1e-12);
+            for split in 0..BUCKETS - 1 {
+                let union = |bs: &[Option<LightBounds>]| {
+                    bs.iter()
+                        .flatten()
+                        .fold(None, |acc: Option<LightBounds>, l| {
+                            Some(acc.map_or(*l, |a| a.union(l)))
+                        })
+                };
+                if let (Some(below), Some(above)) =
+                    (union(&buckets[..=split]), union(&buckets[split + 1..]))
+                {
+                    let cost = below.cost(kr) + above.cost(kr);
+                    if best.is_none_or(|(c, _, _)| cost < c) {
+                        best = Some((cost, dim, split));
+                    }
+                }
+            }
+        }
+
+        match best {
+            Some((_, dim, split)) => {
+                items.sort_by_key(|(_, l)| bucket_of(l, dim) > split);
+                items.partition_point(|(_, l)| bucket_of(l, dim) <= split)
+            }
+            // Every centroid is in the same place, so any split is as good
+            None => items.len() / 2,
+        }
+    }
+
+    /// Chance that each child of `node` is chosen from `p` with normal `n`.
+    fn child_probabilities(&self, node: usize, p: vector3::Point, n: vector3::Vec3) -> [f64; 2] {
+        match self.nodes[node].kind {
+            NodeKind::Interior(left, right) => {
+                let a = self.nodes[left].bounds.importance(p, n);
+                let b = self.nodes[right].bounds.importance(p, n);
+                if a + b == 0.0 {
+                    [0.0, 0.0]
+                } else {
+                    [a / (a + b), b / (a + b)]
+                }
+            }
+            NodeKind::Leaf(_) => [0.0, 0.0],
+        }
+    }
+
+    /// Picks a light with `u` in [0, 1), returning its index in the light list
+    /// and the chance it was picked.
+    pub fn sample(&self, p: vector3::Point, n: vector3::Vec3, mut u: f64) -> Option<(usize, f64)> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let mut node = 0;
+        let mut pmf = 1.0;
+        loop {
+            match self.nodes[node].kind {
+                NodeKind::Leaf(slot) => {
+                    if self.nodes[node].bounds.importance(p, n) <= 0.0 {
+                        return None;
+                    }
+                    return Some((self.lights[slot], pmf));
+                }
+                NodeKind::Interior(left, right) => {
+                    let [p_left, p_right] = self.child_probabilities(node, p, n);
+                    if p_left + p_right == 0.0 {
+                        return None;
+                    }
+                    if u < p_left {
+                        u = (u / p_left).min(1.0 - f64::EPSILON);
+                        pmf *= p_left;
+                        node = left;
+                    } else {
+                        u = ((u - p_left) / p_right).min(1.0 - f64::EPSILON);
+                        pmf *= p_right;
+                        node = right;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Chance that `sample` picks the `slot`th light the tree was built from,
+    /// found by walking from its leaf up to the root.
+    fn pmf(&self, p: vector3::Point, n: vector3::Vec3, slot: usize) -> f64 {
+        let mut node = self.leaves[slot];
+        if self.nodes[node].bounds.importance(p, n) <= 0.0 {
+            return 0.0;
+        }
+        let mut pmf = 1.0;
+        while let Some(parent) = self.nodes[node].parent {
+            let probs = self.child_probabilities(parent, p, n);
+            pmf *= match self.nodes[parent].kind {
+                NodeKind::Interior(left, _) if left == node => probs[0],
+                _ => probs[1],
+            };
+            node = parent;
+        }
+        pmf
+    }
+
+    /// Closest area light hit by `r`, as its index in the light list.
+    fn hit(&self, lights: &[light::Light], r: &ray::Ray) -> Option<usize> {
+        let mut closest: Option<(usize, f64)> = None;
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            let t_max = closest.map_or(f64::INFINITY, |(_, t)| t);
+            if self.nodes[node]
+                .bounds
+                .bounds
+                .intersect(r, 0.001, t_max)
+                .is_none()
+            {
+                continue;
+            }
+            match self.nodes[node].kind {
+                NodeKind::Leaf(slot) => {
+                    let i = self.lights[slot];
+                    if let light::Light::Area(a) = &lights[i] {
+                        if let Some(rec) = a.shape().hit(r, 0.001, t_max) {
+                            closest = Some((i, rec.t));
+                        }
+                    }
+                }
+                NodeKind::Interior(left, right) => {
+                    stack.push(left);
+                    stack.push(right);
+                }
+            }
+        }
+        closest.map(|(i, _)| i)
+    }
+}
+
+/// Lights with bounds, picked by the chosen strategy.
+enum Bounded {
+    Uniform,
+    Power(distribution::Distribution1D),
+    Bvh(LightBvh),
+}
+
+/// Picks a light to sample at a shading point. Lights at infinity have no
+/// bounds and are picked uniformly, each getting the same share as all the
+/// other lights together (or as any one light, with uniform sampling).
+pub struct LightSampler {
+    bounded: Bounded,
+    /// Indices of the lights at infinity
+    infinite: Vec<usize>,
+    /// Indices of the other lights
+    finite: Vec<usize>,
+    /// Position of each light in `infinite` or `finite`
+    slots: Vec<usize>,
+}
+
+impl LightSampler {
+    pub fn new(lights: &[light::Light], strategy: Strategy) -> LightSampler {
+        let bounds: Vec<Option<LightBounds>> = lights.iter().map(|l| l.bounds()).collect();
+        let (mut infinite, mut finite) = (Vec::new(), Vec::new());
+        let mut slots = Vec::with_capacity(lights.len());
+        for (i, b) in bounds.iter().enumerate() {
+            if b.is_none() {
+                slots.push(infinite.len());
+                infinite.push(i);
+            } else {
+                slots.push(finite.len());
+                finite.push(i);
+            }
+        }
+        let bounded = match strategy {
+            Strategy::Uniform => Bounded::Uniform,
+            Strategy::Power => Bounded::Power(distribution::Distribution1D::new(
+                finite.iter().map(|&i| bounds[i].unwrap().phi).collect(),
+            )),
+            Strategy::Bvh => Bounded::Bvh(LightBvh::new(
+                finite.iter().map(|&i| (i, bounds[i].unwrap())).collect(),
+            )),
+        };
+        LightSampler {
+            bounded,
+            infinite,
+            finite,
+            slots,
+        }
+    }
+
+    /// Chance of picking one of the lights at infinity.
+    fn p_infinite(&self) -> f64 {
+        let shares = match self.bounded {
+            Bounded::Uniform => self.infinite.len() + self.finite.len(),
+            _ => self.infinite.len() + usize::from(!self.finite.is_empty()),
+        };
+        if shares == 0 {
+            return 0.0;
+        }
+        self.infinite.len() as f64 / shares as f64
+    }
+
+    /// Picks a light for a surface at `p` with normal `n` (zero if it's lit from
+    /// both sides) using `u` in [0, 1). Returns the light's index and the chance
+    /// it was picked.
+    pub fn sample(&self, p: vector3::Point, n: vector3::Vec3, u: f64) -> Option<(usize, f64)> {
+        let p_inf = self.p_infinite();
+        if u < p_inf {
+            let k = self.infinite.len();
+            let i = ((u / p_inf * k as f64) as usize).min(k - 1);
+            return Some((self.infinite[i], p_inf / k as f64));
+        }
+        if self.finite.is_empty() {
+            return None;
+        }
+        let u = ((u - p_inf) / (1.0 - p_inf)).min(1.0 - f64::EPSILON);
+        let (i, pmf) = match &self.bounded {
+            Bounded::Uniform => {
+                let k = self.finite.len();
+                let slot = ((u * k as f64) as usize).min(k - 1);
+                (self.finite[slot], 1.0 / k as f64)
+            }
+            Bounded::Power(d) => {
+                let (slot, pmf) = d.sample_discrete(u);
+                (self.finite[slot], pmf)
+            }
+            Bounded::Bvh(bvh) => bvh.sample(p, n, u)?,
+        };
+        if pmf <= 0.0 {
+            return None;
+        }
+        Some((i, pmf * (1.0 - p_inf)))
+    }
+
+    /// Chance that `sample` picks light `i` for a surface at `p` with normal `n`.
+    pub fn pmf(&self, p: vector3::Point, n: vector3::Vec3, i: usize) -> f64 {
+        let p_inf = self.p_infinite();
+        let slot = self.slots[i];
+        if self.infinite.get(slot) == Some(&i) {
+            return p_inf / self.infinite.len() as f64;
+        }
+        let pmf = match &self.bounded {
+            Bounded::Uniform => 1.0 / self.finite.len() as f64,
+            Bounded::Power(d) => d.discrete_pdf(slot),
+            Bounded::Bvh(bvh) => bvh.pmf(p, n, slot),
+        };
+        pmf * (1.0 - p_inf)
+    }
+
+    /// Indices of the lights that have no position.
+    pub fn infinite(&self) -> &[usize] {
+        &self.infinite
+    }
+
+    /// Closest area light hit by `r`, as its index.
+    pub fn hit(&self, lights: &[light::Light], r: &ray::Ray) -> Option<usize> {
+        if let Bounded::Bvh(bvh) = &self.bounded {
+            if !bvh.is_empty() {
+                return bvh.hit(lights, r);
+            }
+        }
+        let mut closest: Option<(usize, f64)> = None;
+        for &i in self.finite.iter() {
+            if let light::Light::Area(a) = &lights[i] {
+                let t_max = closest.map_or(f64::INFINITY, |(_, t)| t);
+                if let Some(rec) = a.shape().hit(r, 0.001, t_max) {
+                    closest = Some((i, rec.t));
+                }
+            }
+        }
+        closest.map(|(i, _)| i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable;
+    use crate::material;
+    use crate::quad;
+    use std::sync::{Arc, Mutex};
+
+    /// A row of dim point lights along x with one bright one at the end.
+    fn row() -> Vec<light::Light> {
+        (0..20)
+            .map(|i| {
+                let power = if i == 19 { 50.0 } else { 1.0 };
+                light::Light::Point(light::PointLight::new(
+                    vector3::Point::new(i as f64, 1.0, 0.0),
+                    vector3::Color::new(power, power, power),
+                ))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn cone_union_holds_both_cones() {
+        let a = vector3::Vec3::new(1.0, 0.0, 0.0);
+        let b = vector3::Vec3::new(0.0, 1.0, 0.0);
+        let cos = 10f64.to_radians().cos();
+        let (w, cos_o) = union_cones(a, cos, b, cos);
+        // Both axes plus their spread fit inside the union, and no more than that
+        let half = cos_o.acos().to_degrees();
+        assert!((half - 55.0).abs() < 1e-9, "{}", half);
+        for axis in [a, b] {
+            let angle = vector3::dot(w, axis).acos().to_degrees();
+            assert!(angle + 10.0 <= half + 1e-9);
+        }
+    }
+
+    #[test]
+    fn pmf_matches_sampling() {
+        let lights = row();
+        let p = vector3::Point::new(3.2, 0.0, 0.5);
+        let n = vector3::Vec3::new(0.0, 1.0, 0.0);
+        for strategy in [Strategy::Uniform, Strategy::Power, Strategy::Bvh] {
+            let sampler = LightSampler::new(&lights, strategy);
+            let total: f64 = (0..lights.len()).map(|i| sampler.pmf(p, n, i)).sum();
+            assert!((total - 1.0).abs() < 1e-9, "{:?}: {}", strategy, total);
+            let k = 1000;
+            let mut counts = vec![0; lights.len()];
+            for j in 0..k {
+                let (i, pmf) = sampler.sample(p, n, (j as f64 + 0.5) / k as f64).unwrap();
+                assert!((pmf - sampler.pmf(p, n, i)).abs() < 1e-9);
+                counts[i] += 1;
+            }
+            for (i, &c) in counts.iter().enumerate() {
+                let expected = sampler.pmf(p, n, i) * k as f64;
+                assert!(
+                    (c as f64 - expected).abs() < 2.0,
+                    "{:?} light {}",
+                    strategy,
+                    i
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn bvh_prefers_nearby_lights() {
+        let lights = row();
+        let sampler = LightSampler::new(&lights, Strategy::Bvh);
+        let n = vector3::Vec3::new(0.0, 1.0, 0.0);
+        let near = sampler.pmf(vector3::Point::new(2.0, 0.0, 0.0), n, 2);
+        let far = sampler.pmf(vector3::Point::new(16.0, 0.0, 0.0), n, 2);
+        assert!(near > 0.2 && far < 0.01, "{} {}", near, far);
+        // Power sampling only looks at the bright light
+        let power = LightSampler::new(&lights, Strategy::Power);
+        assert!((power.pmf(vector3::Point::new(2.0, 0.0, 0.0), n, 19) - 50.0 / 69.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn light_list_pdf_matches_area_light_samples() {
+        let mut lights = light::LightList::new();
+        for i in 0..8 {
+            let emit = material::Material::DiffuseLight(material::DiffuseLight::new(
+                vector3::Color::new(4.0, 4.0, 4.0),
+            ));
+            let q = quad::Quad::new(
+                vector3::Point::new(i as f64 * 2.0, 3.0, 0.0),
+                vector3::Vec3::new(1.0, 0.0, 0.0),
+                vector3::Vec3::new(0.0, 0.0, 1.0),
+                Arc::new(Mutex::new(emit)),
+            );
+            lights.add(light::Light::Area(light::AreaLight::new(
+                hittable::HittableObj::Quad(q),
+            )));
+        }
+        let p = vector3::Point::new(4.3, 0.0, 0.4);
+        let n = vector3::Vec3::new(0.0, 1.0, 0.0);
+        for k in 0..100 {
+            let u = (k as f64 + 0.5) / 100.0;
+            let (light, pmf) = lights.choose(p, n, u).unwrap();
+            let ls = light.sample_li(p, [u, (k as f64 * 0.618) % 1.0]).unwrap();
+            let expected = pmf * ls.pdf;
+            let pdf = lights.pdf(p, n, ls.wi);
+            assert!(
+                (pdf - expected).abs() < 1e-6 * expected,
+                "{} != {}",
+                pdf,
+                expected
+            );
+        }
+    }
+}
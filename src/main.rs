@@ -1,36 +1,53 @@
+pub mod aabb;
+pub mod animation;
+pub mod bsdf;
+pub mod bvh;
 pub mod camera;
+pub mod capsule;
 pub mod color;
+pub mod cone;
+pub mod csg;
+pub mod curve;
+pub mod cylinder;
+pub mod disk;
+pub mod distribution;
+pub mod environment;
+pub mod heightfield;
 pub mod hittable;
+pub mod ies;
 pub mod image_encoder;
+pub mod integrator;
+pub mod lens;
+pub mod light;
+pub mod light_sampler;
 pub mod material;
+pub mod microfacet;
+pub mod noise;
+pub mod onb;
+pub mod principled;
+pub mod quad;
 pub mod ray;
+pub mod roots;
+pub mod sdf;
+pub mod sequence_encoder;
+pub mod sky;
+pub mod spectrum;
 pub mod sphere;
+#[cfg(test)]
+mod test_utils;
+pub mod texture;
+pub mod torus;
+pub mod transform;
+pub mod triangle;
 pub mod utils;
 pub mod vector3;
-use crate::hittable::Hittable;
-use crate::material::MaterialTrait;
+use camera::CameraModel;
 use cast::u32;
 use pbr::ProgressBar;
 use rand::Rng;
 use rayon::prelude::*;
 use std::sync::Arc;
 use std::sync::Mutex;
-pub fn ray_color(r: &ray::Ray, world: hittable::HittableList, depth: i32) -> vector3::Color {
-    if depth <= 0 {
-        return vector3::Color::new(0.0, 0.0, 0.0);
-    }
-    if let Some(hit) = world.clone().hit(r, 0.001, 10000000000.0) {
-        let (did_scatter, attenuation, scattered) = &hit.material.lock().unwrap().scatter(&r, &hit);
-        if *did_scatter {
-            return *attenuation * ray_color(scattered, world, depth - 1);
-        } else {
-            return vector3::Color::new(0.0, 0.0, 0.0);
-        }
-    }
-    let unit_direction: vector3::Vec3 = r.dir.unit_vector();
-    let t = 0.5 * (unit_direction.y() + 1.0);
-    return vector3::Color::new(1.0, 1.0, 1.0) * (1.0 - t) + vector3::Color::new(0.5, 0.7, 1.0) * t;
-}
 
 fn main() {
     use std::time::Instant;
@@ -43,8 +60,56 @@ fn main() {
     let samples_per_pixel = 50;
     let max_depth: i32 = 50;
 
+    // `--frames N` renders N frames of the animated scene instead of a still
+    let args: Vec<String> = std::env::args().collect();
+    let frames = args
+        .iter()
+        .position(|a| a == "--frames")
+        .and_then(|i| args.get(i + 1))
+        .map(|n| n.parse::<u32>().expect("--frames takes a number of frames"));
+
+    // ...and `--gif` or `--apng` also assembles them into a looping preview
+    let gif = args.iter().any(|a| a == "--gif");
+    let apng = args.iter().any(|a| a == "--apng");
+    // `--spectral` traces sampled wavelengths rather than RGB
+    let settings = RenderSettings {
+        image_width,
+        image_height,
+        samples_per_pixel,
+        max_depth,
+        spectral: args.iter().any(|a| a == "--spectral"),
+    };
+
+    if let Some(frames) = frames {
+        let fps = 24.0;
+        let (mut scene, lights) = animated_scene(aspect_ratio);
+        let mut sequence = Vec::new();
+        for frame in 1..=frames {
+            let cam = scene.frame((frame - 1) as f64 / fps);
+            let img = render(&cam, scene.world(), &lights, &settings);
+            img.save(format!("frame_{:04}.png", frame)).unwrap();
+            if gif || apng {
+                sequence.push(img);
+            }
+        }
+        if gif {
+            sequence_encoder::write_gif(
+                "animation.gif",
+                &sequence,
+                fps,
+                sequence_encoder::Dither::FloydSteinberg,
+            )
+            .unwrap();
+        }
+        if apng {
+            sequence_encoder::write_apng("animation.apng", &sequence, fps).unwrap();
+        }
+        println!(" {} frames rendered in {:.2?}", frames, now.elapsed());
+        return;
+    }
+
     // World
-    let world = random_scene();
+    let (world, lights) = random_scene();
 
     // Camera
     let lookfrom = vector3::Point::new(13.0, 2.0, 3.0);
@@ -53,7 +118,7 @@ fn main() {
     let dist_to_focus = 10.0;
     let aperture = 0.1;
 
-    let cam = camera::Camera::new(
+    let cam = camera::Camera::Perspective(camera::PerspectiveCamera::new(
         lookfrom,
         lookat,
         vup,
@@ -61,8 +126,36 @@ fn main() {
         aspect_ratio,
         aperture,
         dist_to_focus,
-    );
+    ));
+
+    let img = render(&cam, &world, &lights, &settings);
+    img.save("image.png").unwrap();
+    println!(" Image rendered in {:.2?}", now.elapsed());
+}
+
+/// How every frame is rendered.
+struct RenderSettings {
+    image_width: u32,
+    image_height: u32,
+    samples_per_pixel: i32,
+    max_depth: i32,
+    /// Trace sampled wavelengths rather than RGB
+    spectral: bool,
+}
 
+fn render(
+    cam: &camera::Camera,
+    world: &(dyn hittable::Hittable + Sync),
+    lights: &light::LightList,
+    settings: &RenderSettings,
+) -> image::RgbImage {
+    let RenderSettings {
+        image_width,
+        image_height,
+        samples_per_pixel,
+        max_depth,
+        spectral,
+    } = *settings;
     // Progress bar
     let pb = Mutex::new(ProgressBar::new((image_height * image_width) as u64));
     pb.lock().unwrap().format("╢▌▌░╟");
@@ -81,8 +174,17 @@ fn main() {
         for _s in 0..samples_per_pixel {
             let u = (i as f64 + rng.gen_range(0.0..1.0)) / (image_width - 1) as f64;
             let v = (j as f64 + rng.gen_range(0.0..1.0)) / (image_height - 1) as f64;
-            let r = cam.get_ray(u, v);
-            pixel_color = pixel_color + ray_color(&r, world.clone(), max_depth);
+            if let Some(r) = cam.get_ray(u, v) {
+                let color = if spectral {
+                    let mut lambda =
+                        spectrum::SampledWavelengths::sample_visible(rng.gen_range(0.0..1.0));
+                    integrator::path_trace_spectral(&r, world, lights, max_depth, &mut lambda)
+                        .to_rgb(&lambda)
+                } else {
+                    integrator::path_trace(&r, world, lights, max_depth)
+                };
+                pixel_color = pixel_color + color;
+            }
         }
         *val = pixel_color;
         pb.lock().unwrap().inc();
@@ -98,14 +200,70 @@ fn main() {
         }
     }
 
-    img.save("image.png").unwrap();
-    let elapsed = now.elapsed();
     pb.lock().unwrap().finish_print("Image Rendered :)");
-    println!(" Image rendered in {:.2?}", elapsed);
+    img
+}
+
+/// `random_scene` with the camera circling it and the metal sphere hopping over
+/// the middle one, for a two second loop.
+pub fn animated_scene(aspect_ratio: f64) -> (animation::AnimatedScene, light::LightList) {
+    let (world, lights) = random_scene();
+
+    let mut orbit = animation::Track::new(
+        animation::Interpolation::CatmullRom,
+        0.0,
+        vector3::Point::new(13.0, 2.0, 3.0),
+    );
+    for k in 1..=8 {
+        let angle = (k as f64 / 8.0) * 2.0 * std::f64::consts::PI + 3.0_f64.atan2(13.0);
+        let radius = 178.0_f64.sqrt();
+        orbit.add(
+            k as f64 * 0.25,
+            vector3::Point::new(radius * angle.cos(), 2.0, radius * angle.sin()),
+        );
+    }
+    let camera = animation::CameraTrack::new(
+        orbit,
+        animation::Track::constant(vector3::Point::new(0.0, 0.0, 0.0)),
+        vector3::Vec3::new(0.0, 1.0, 0.0),
+        animation::Track::constant(20.0),
+        aspect_ratio,
+    );
+
+    let ball_material = Arc::new(Mutex::new(material::Material::Metal(material::Metal::new(
+        vector3::Color::new(0.7, 0.6, 0.5),
+    ))));
+    let ball = hittable::HittableObj::Sphere(sphere::Sphere::new(
+        vector3::Point::new(0.0, 0.0, 0.0),
+        0.5,
+        ball_material,
+    ));
+    let mut hop = animation::Track::new(
+        animation::Interpolation::Bezier,
+        0.0,
+        vector3::Vec3::new(-2.0, 0.5, 2.0),
+    );
+    hop.add_bezier(
+        1.0,
+        vector3::Vec3::new(0.0, 3.0, 2.0),
+        vector3::Vec3::new(-0.7, 3.0, 2.0),
+        vector3::Vec3::new(0.7, 3.0, 2.0),
+    );
+    hop.add(2.0, vector3::Vec3::new(2.0, 0.5, 2.0));
+    let motion = animation::AnimatedTransform::new(
+        hop,
+        animation::Track::constant(vector3::Vec3::new(0.0, 0.0, 0.0)),
+        animation::Track::constant(1.0),
+    );
+
+    let scene =
+        animation::AnimatedScene::new(world.objects().to_vec(), vec![(ball, motion)], camera);
+    (scene, lights)
 }
 
-pub fn random_scene() -> hittable::HittableList {
+pub fn random_scene() -> (hittable::HittableList, light::LightList) {
     let mut world = hittable::HittableList::new();
+    let lights = light::LightList::new();
 
     let ground_material = Arc::new(Mutex::new(material::Material::Lambertian(
         material::Lambertian::new(vector3::Color::new(0.5, 0.5, 0.5)),
@@ -193,5 +351,5 @@ pub fn random_scene() -> hittable::HittableList {
         material3,
     )));
 
-    return world;
+    (world, lights)
 }
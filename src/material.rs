@@ -1,123 +1,1095 @@
+use crate::bsdf;
 use crate::hittable;
+use crate::microfacet;
+use crate::principled;
 use crate::ray;
+use crate::texture;
+use crate::texture::TextureTrait;
 use crate::utils;
 use crate::vector3;
+use std::f64::consts::PI;
+use std::sync::Arc;
 
 pub trait MaterialTrait {
-    fn scatter(&self, r: &ray::Ray, rec: &hittable::HitRecord) -> (bool, vector3::Color, ray::Ray);
+    /// Scattering at the hit point, or `None` if the material doesn't scatter light.
+    fn bsdf(&self, rec: &hittable::HitRecord) -> Option<bsdf::Bsdf>;
+
+    /// Radiance given off at the hit point toward where the ray came from.
+    fn emitted(&self, _rec: &hittable::HitRecord) -> vector3::Color {
+        vector3::Color::new(0.0, 0.0, 0.0)
+    }
+
+    /// Average radiance leaving the front and the back of the surface, when it
+    /// can be known without looking at the surface itself.
+    fn average_emitted(&self) -> Option<(vector3::Color, vector3::Color)> {
+        let black = vector3::Color::new(0.0, 0.0, 0.0);
+        Some((black, black))
+    }
+
+    /// Whether camera rays stop at the surface. Those that don't pass straight
+    /// through, though the surface still lights the scene and shows in reflections.
+    fn visible_to_camera(&self) -> bool {
+        true
+    }
+
+    /// Whether scattering depends on wavelength, so a spectral path that meets
+    /// the surface can only follow one of its wavelengths onward.
+    fn is_dispersive(&self) -> bool {
+        false
+    }
+
+    /// Scattering at the hit point for light of wavelength `lambda` in nm.
+    fn bsdf_at(&self, rec: &hittable::HitRecord, _lambda: f64) -> Option<bsdf::Bsdf> {
+        self.bsdf(rec)
+    }
+
+    /// Samples the BSDF for a continuation ray, returning whether one was found and
+    /// its weight `f * |cos| / pdf`.
+    fn scatter(&self, r: &ray::Ray, rec: &hittable::HitRecord) -> (bool, vector3::Color, ray::Ray) {
+        let black = vector3::Color::new(0.0, 0.0, 0.0);
+        let bsdf = match self.bsdf(rec) {
+            Some(bsdf) => bsdf,
+            None => return (false, black, *r),
+        };
+        let wo = r.dir.unit_vector() * -1.0;
+        let u = [
+            utils::random_double(0.0, 1.0),
+            utils::random_double(0.0, 1.0),
+        ];
+        match bsdf.sample(wo, u) {
+            Some(s) => (
+                true,
+                s.f * bsdf.cos_theta(s.wi).abs() / s.pdf,
+                ray::Ray::new(rec.p, s.wi),
+            ),
+            None => (false, black, *r),
+        }
+    }
 }
 
 pub enum Material {
     Lambertian(Lambertian),
     Metal(Metal),
     Dielectric(Dielectric),
+    Hair(Hair),
+    Principled(principled::Principled),
+    RoughConductor(RoughConductor),
+    RoughDielectric(RoughDielectric),
+    DiffuseLight(DiffuseLight),
 }
 
 impl MaterialTrait for Material {
-    fn scatter(
-        &self,
-        _r: &ray::Ray,
-        rec: &hittable::HitRecord,
-    ) -> (bool, vector3::Color, ray::Ray) {
+    fn bsdf(&self, rec: &hittable::HitRecord) -> Option<bsdf::Bsdf> {
+        match self {
+            Material::Lambertian(x) => x.bsdf(rec),
+            Material::Metal(x) => x.bsdf(rec),
+            Material::Dielectric(x) => x.bsdf(rec),
+            Material::Hair(x) => x.bsdf(rec),
+            Material::Principled(x) => x.bsdf(rec),
+            Material::RoughConductor(x) => x.bsdf(rec),
+            Material::RoughDielectric(x) => x.bsdf(rec),
+            Material::DiffuseLight(x) => x.bsdf(rec),
+        }
+    }
+
+    fn emitted(&self, rec: &hittable::HitRecord) -> vector3::Color {
+        match self {
+            Material::DiffuseLight(x) => x.emitted(rec),
+            _ => vector3::Color::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    fn average_emitted(&self) -> Option<(vector3::Color, vector3::Color)> {
+        match self {
+            Material::DiffuseLight(x) => x.average_emitted(),
+            _ => Some((
+                vector3::Color::new(0.0, 0.0, 0.0),
+                vector3::Color::new(0.0, 0.0, 0.0),
+            )),
+        }
+    }
+
+    fn visible_to_camera(&self) -> bool {
+        match self {
+            Material::DiffuseLight(x) => x.visible_to_camera(),
+            _ => true,
+        }
+    }
+
+    fn is_dispersive(&self) -> bool {
+        match self {
+            Material::Dielectric(x) => x.is_dispersive(),
+            _ => false,
+        }
+    }
+
+    fn bsdf_at(&self, rec: &hittable::HitRecord, lambda: f64) -> Option<bsdf::Bsdf> {
         match self {
-            Material::Lambertian(x) => x.scatter(_r, rec),
-            Material::Metal(x) => x.scatter(_r, rec),
-            Material::Dielectric(x) => x.scatter(_r, rec),
+            Material::Dielectric(x) => x.bsdf_at(rec, lambda),
+            _ => self.bsdf(rec),
         }
     }
 }
 
 pub struct Lambertian {
-    albedo: vector3::Color,
+    albedo: Arc<texture::Texture>,
 }
 
 impl Lambertian {
     pub fn new(p_albedo: vector3::Color) -> Lambertian {
+        Lambertian::from_texture(texture::solid(p_albedo))
+    }
+    pub fn from_texture(p_albedo: Arc<texture::Texture>) -> Lambertian {
         Lambertian { albedo: p_albedo }
     }
 }
 
 impl MaterialTrait for Lambertian {
-    fn scatter(
-        &self,
-        _r: &ray::Ray,
-        rec: &hittable::HitRecord,
-    ) -> (bool, vector3::Color, ray::Ray) {
-        let mut scatter_direction = rec.normal + vector3::Vec3::random_unit_vector();
-        if scatter_direction.near_zero() {
-            scatter_direction = rec.normal.clone();
-        }
-        // yeh sab jo change krke bhej rhe usse bhi hit record mei dalna mangtau
-        let scattered = ray::Ray::new(rec.p, scatter_direction);
-        let attenuation = self.albedo.clone();
-        (true, attenuation, scattered)
+    fn bsdf(&self, rec: &hittable::HitRecord) -> Option<bsdf::Bsdf> {
+        let r = self.albedo.value(rec.u, rec.v, rec.p);
+        Some(bsdf::Bsdf::new(
+            rec,
+            bsdf::Bxdf::Diffuse(bsdf::Diffuse { r }),
+        ))
     }
 }
 
 pub struct Metal {
-    albedo: vector3::Color,
+    albedo: Arc<texture::Texture>,
 }
 
 impl Metal {
     pub fn new(p_albedo: vector3::Color) -> Metal {
+        Metal::from_texture(texture::solid(p_albedo))
+    }
+    pub fn from_texture(p_albedo: Arc<texture::Texture>) -> Metal {
         Metal { albedo: p_albedo }
     }
 }
 
 impl MaterialTrait for Metal {
-    fn scatter(&self, r: &ray::Ray, rec: &hittable::HitRecord) -> (bool, vector3::Color, ray::Ray) {
-        let reflected = vector3::reflect(r.dir.unit_vector(), rec.normal);
-        let scattered = ray::Ray::new(rec.p, reflected);
-        let attenuation = self.albedo.clone();
-        (
-            (vector3::dot(scattered.dir, rec.normal) > 0.0),
-            attenuation,
-            scattered,
-        )
+    fn bsdf(&self, rec: &hittable::HitRecord) -> Option<bsdf::Bsdf> {
+        let r = self.albedo.value(rec.u, rec.v, rec.p);
+        Some(bsdf::Bsdf::new(
+            rec,
+            bsdf::Bxdf::SpecularReflection(bsdf::SpecularReflection { r }),
+        ))
+    }
+}
+
+/// Index of refraction as a function of wavelength. Coefficients take the
+/// wavelength in micrometres, as published.
+#[derive(Copy, Clone, Debug)]
+pub enum Dispersion {
+    Constant(f64),
+    /// `n = a + b / λ²`
+    Cauchy {
+        a: f64,
+        b: f64,
+    },
+    /// `n² = 1 + Σ b λ² / (λ² - c)`
+    Sellmeier {
+        b: [f64; 3],
+        c: [f64; 3],
+    },
+}
+
+impl Dispersion {
+    /// Schott N-BK7, the common optical crown glass
+    pub fn bk7() -> Dispersion {
+        Dispersion::Sellmeier {
+            b: [1.03961212, 0.231792344, 1.01046945],
+            c: [0.00600069867, 0.0200179144, 103.560653],
+        }
+    }
+
+    /// Fused silica (Malitson, 1965)
+    pub fn fused_silica() -> Dispersion {
+        Dispersion::Sellmeier {
+            b: [0.6961663, 0.4079426, 0.8974794],
+            c: [
+                0.0684043 * 0.0684043,
+                0.1162414 * 0.1162414,
+                9.896161 * 9.896161,
+            ],
+        }
+    }
+
+    /// Diamond (Peter, 1923)
+    pub fn diamond() -> Dispersion {
+        Dispersion::Sellmeier {
+            b: [0.3306, 4.3356, 0.0],
+            c: [0.175 * 0.175, 0.106 * 0.106, 0.0],
+        }
+    }
+
+    /// Index of refraction at `lambda` in nm.
+    pub fn ior(&self, lambda: f64) -> f64 {
+        let um = lambda / 1000.0;
+        let um2 = um * um;
+        match *self {
+            Dispersion::Constant(n) => n,
+            Dispersion::Cauchy { a, b } => a + b / um2,
+            Dispersion::Sellmeier { b, c } => {
+                let n2 = 1.0 + (0..3).map(|i| b[i] * um2 / (um2 - c[i])).sum::<f64>();
+                n2.sqrt()
+            }
+        }
     }
 }
 
+/// Wavelength of the helium d line in nm, where glasses' single index of
+/// refraction is usually quoted.
+const D_LINE: f64 = 587.56;
+
 pub struct Dielectric {
-    ir: f64,
+    dispersion: Dispersion,
 }
 
 impl Dielectric {
     pub fn new(p_ir: f64) -> Dielectric {
-        Dielectric { ir: p_ir }
+        Dielectric::dispersive(Dispersion::Constant(p_ir))
+    }
+
+    /// Glass whose index of refraction varies with wavelength, splitting white
+    /// light into colours in spectral rendering. RGB rendering uses the index at
+    /// the d line throughout.
+    pub fn dispersive(dispersion: Dispersion) -> Dielectric {
+        Dielectric { dispersion }
     }
     pub fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
         // Use Schlick's approximation for reflectance.
         let mut r0 = (1.0 - ref_idx) / (1.0 + ref_idx);
         r0 = r0 * r0;
-        return r0 + (1.0 - r0) * (1.0 - cosine).powf(5.0);
+        r0 + (1.0 - r0) * (1.0 - cosine).powf(5.0)
     }
 }
 
 impl MaterialTrait for Dielectric {
-    fn scatter(&self, r: &ray::Ray, rec: &hittable::HitRecord) -> (bool, vector3::Color, ray::Ray) {
-        let attenuation = vector3::Color::new(1.0, 1.0, 1.0);
-        let mut refraction_ratio = 1.0 / self.ir;
-        if !rec.front_face {
-            refraction_ratio = self.ir;
+    fn bsdf(&self, rec: &hittable::HitRecord) -> Option<bsdf::Bsdf> {
+        self.bsdf_at(rec, D_LINE)
+    }
+
+    fn is_dispersive(&self) -> bool {
+        !matches!(self.dispersion, Dispersion::Constant(_))
+    }
+
+    fn bsdf_at(&self, rec: &hittable::HitRecord, lambda: f64) -> Option<bsdf::Bsdf> {
+        Some(bsdf::Bsdf::new(
+            rec,
+            bsdf::Bxdf::SpecularDielectric(bsdf::SpecularDielectric {
+                ir: self.dispersion.ior(lambda),
+            }),
+        ))
+    }
+}
+
+/// Total power sent out by an emitter.
+#[derive(Copy, Clone, Debug)]
+pub enum Power {
+    Watts(f64),
+    /// Converted at 683 lm/W, the efficacy of light at the eye's peak sensitivity
+    Lumens(f64),
+}
+
+impl Power {
+    pub fn watts(self) -> f64 {
+        match self {
+            Power::Watts(w) => w,
+            Power::Lumens(lm) => lm / 683.0,
+        }
+    }
+}
+
+/// Emits light evenly from the front of a surface, or from both sides, and
+/// doesn't reflect any.
+pub struct DiffuseLight {
+    emit: Arc<texture::Texture>,
+    /// Multiplies `emit`
+    scale: f64,
+    two_sided: bool,
+    visible: bool,
+}
+
+impl DiffuseLight {
+    pub fn new(p_emit: vector3::Color) -> DiffuseLight {
+        DiffuseLight::from_texture(texture::solid(p_emit))
+    }
+    pub fn from_texture(p_emit: Arc<texture::Texture>) -> DiffuseLight {
+        DiffuseLight {
+            emit: p_emit,
+            scale: 1.0,
+            two_sided: false,
+            visible: true,
+        }
+    }
+
+    /// Emitter of colour `tint` sending out `power` in total from a surface of
+    /// `area`. Only the tint's chromaticity matters, not its brightness.
+    pub fn from_power(
+        tint: vector3::Color,
+        power: Power,
+        area: f64,
+        two_sided: bool,
+    ) -> DiffuseLight {
+        let mut light = DiffuseLight::new(tint / tint.luminance().max(1e-12));
+        light.set_two_sided(two_sided);
+        light.set_power(power, area);
+        light
+    }
+
+    /// Scales the emission so a surface of `area` sends out `power`, taking the
+    /// texture's values as relative to a luminance of one.
+    pub fn set_power(&mut self, power: Power, area: f64) {
+        let sides = if self.two_sided { 2.0 } else { 1.0 };
+        self.scale = power.watts() / (PI * area * sides);
+    }
+
+    pub fn set_scale(&mut self, scale: f64) {
+        self.scale = scale;
+    }
+
+    pub fn set_two_sided(&mut self, two_sided: bool) {
+        self.two_sided = two_sided;
+    }
+
+    pub fn set_visible_to_camera(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+}
+
+impl MaterialTrait for DiffuseLight {
+    fn bsdf(&self, _rec: &hittable::HitRecord) -> Option<bsdf::Bsdf> {
+        None
+    }
+
+    fn emitted(&self, rec: &hittable::HitRecord) -> vector3::Color {
+        if !rec.front_face && !self.two_sided {
+            return vector3::Color::new(0.0, 0.0, 0.0);
+        }
+        self.emit.value(rec.u, rec.v, rec.p) * self.scale
+    }
+
+    fn average_emitted(&self) -> Option<(vector3::Color, vector3::Color)> {
+        let front = self.emit.average()? * self.scale;
+        let back = if self.two_sided {
+            front
+        } else {
+            vector3::Color::new(0.0, 0.0, 0.0)
+        };
+        Some((front, back))
+    }
+
+    fn visible_to_camera(&self) -> bool {
+        self.visible
+    }
+}
+
+/// Unpolarized Fresnel reflectance of a smooth dielectric boundary, with `eta`
+/// the ratio of the far side's index of refraction to the near side's.
+pub fn fresnel_dielectric(cos_theta_i: f64, eta: f64) -> f64 {
+    let cos_theta_i = cos_theta_i.clamp(-1.0, 1.0);
+    let (cos_theta_i, eta) = if cos_theta_i < 0.0 {
+        (-cos_theta_i, 1.0 / eta)
+    } else {
+        (cos_theta_i, eta)
+    };
+    let sin2_theta_t = (1.0 - cos_theta_i * cos_theta_i) / (eta * eta);
+    if sin2_theta_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+    let r_parl = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let r_perp = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    (r_parl * r_parl + r_perp * r_perp) / 2.0
+}
+
+pub fn same_hemisphere(w: vector3::Vec3, wp: vector3::Vec3) -> bool {
+    w.z * wp.z > 0.0
+}
+
+/// Mirror of `wo` around the normal `n`, both pointing away from the surface.
+pub fn reflect_local(wo: vector3::Vec3, n: vector3::Vec3) -> vector3::Vec3 {
+    wo * -1.0 + n * 2.0 * vector3::dot(wo, n)
+}
+
+/// Refraction of `wi` (pointing away from the surface) through the boundary with
+/// normal `n` and relative index `eta`. Returns the direction and the relative
+/// index actually crossed, or `None` on total internal reflection.
+pub fn refract_local(
+    wi: vector3::Vec3,
+    n: vector3::Vec3,
+    eta: f64,
+) -> Option<(vector3::Vec3, f64)> {
+    let mut cos_theta_i = vector3::dot(n, wi);
+    let (mut eta, mut n) = (eta, n);
+    if cos_theta_i < 0.0 {
+        eta = 1.0 / eta;
+        cos_theta_i = -cos_theta_i;
+        n = n * -1.0;
+    }
+    let sin2_theta_i = (1.0 - cos_theta_i * cos_theta_i).max(0.0);
+    let sin2_theta_t = sin2_theta_i / (eta * eta);
+    if sin2_theta_t >= 1.0 {
+        return None;
+    }
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+    Some((
+        wi * (-1.0 / eta) + n * (cos_theta_i / eta - cos_theta_t),
+        eta,
+    ))
+}
+
+/// GGX microfacet metal with a complex index of refraction `eta + i k` per channel.
+#[derive(Copy, Clone)]
+pub struct RoughConductor {
+    eta: vector3::Color,
+    k: vector3::Color,
+    distrib: microfacet::TrowbridgeReitz,
+}
+
+impl RoughConductor {
+    /// `roughness` runs from 0 (mirror) to 1.
+    pub fn new(eta: vector3::Color, k: vector3::Color, roughness: f64) -> RoughConductor {
+        let alpha = microfacet::TrowbridgeReitz::roughness_to_alpha(roughness);
+        RoughConductor {
+            eta,
+            k,
+            distrib: microfacet::TrowbridgeReitz::new(alpha, alpha),
+        }
+    }
+
+    // RGB fits of measured spectral data (R ~ 650nm, G ~ 550nm, B ~ 450nm)
+    pub fn gold(roughness: f64) -> RoughConductor {
+        RoughConductor::new(
+            vector3::Color::new(0.143, 0.374, 1.442),
+            vector3::Color::new(3.983, 2.385, 1.603),
+            roughness,
+        )
+    }
+
+    pub fn copper(roughness: f64) -> RoughConductor {
+        RoughConductor::new(
+            vector3::Color::new(0.200, 0.924, 1.102),
+            vector3::Color::new(3.912, 2.452, 2.142),
+            roughness,
+        )
+    }
+
+    pub fn aluminium(roughness: f64) -> RoughConductor {
+        RoughConductor::new(
+            vector3::Color::new(1.657, 0.880, 0.521),
+            vector3::Color::new(9.224, 6.270, 4.837),
+            roughness,
+        )
+    }
+
+    pub fn silver(roughness: f64) -> RoughConductor {
+        RoughConductor::new(
+            vector3::Color::new(0.155, 0.117, 0.138),
+            vector3::Color::new(4.828, 3.122, 2.147),
+            roughness,
+        )
+    }
+
+    fn fresnel(&self, cos_theta: f64) -> vector3::Color {
+        vector3::Color::new(
+            microfacet::fresnel_conductor(cos_theta, self.eta.x, self.k.x),
+            microfacet::fresnel_conductor(cos_theta, self.eta.y, self.k.y),
+            microfacet::fresnel_conductor(cos_theta, self.eta.z, self.k.z),
+        )
+    }
+}
+
+impl bsdf::BxdfTrait for RoughConductor {
+    fn eval(&self, wo: vector3::Vec3, wi: vector3::Vec3) -> vector3::Color {
+        let black = vector3::Color::new(0.0, 0.0, 0.0);
+        if !same_hemisphere(wo, wi) {
+            return black;
+        }
+        let wm = wo + wi;
+        if wm.length_squared() == 0.0 {
+            return black;
+        }
+        let wm = wm.unit_vector();
+        self.fresnel(vector3::dot(wo, wm).abs()) * self.distrib.d(wm) * self.distrib.g(wo, wi)
+            / (4.0 * wi.z.abs() * wo.z.abs())
+    }
+
+    /// Samples `wi` from the visible normals.
+    fn sample(&self, wo: vector3::Vec3, u: [f64; 2]) -> Option<bsdf::BsdfSample> {
+        if wo.z == 0.0 {
+            return None;
+        }
+        let wm = self.distrib.sample_wm(wo, u);
+        let wi = reflect_local(wo, wm);
+        if !same_hemisphere(wo, wi) {
+            return None;
+        }
+        let pdf = self.distrib.pdf(wo, wm) / (4.0 * vector3::dot(wo, wm).abs());
+        Some(bsdf::BsdfSample {
+            wi,
+            f: self.eval(wo, wi),
+            pdf,
+            flags: self.flags(),
+        })
+    }
+
+    fn pdf(&self, wo: vector3::Vec3, wi: vector3::Vec3) -> f64 {
+        if !same_hemisphere(wo, wi) {
+            return 0.0;
+        }
+        let wm = wo + wi;
+        if wm.length_squared() == 0.0 {
+            return 0.0;
+        }
+        let mut wm = wm.unit_vector();
+        if wm.z < 0.0 {
+            wm = wm * -1.0;
+        }
+        self.distrib.pdf(wo, wm) / (4.0 * vector3::dot(wo, wm).abs())
+    }
+
+    fn flags(&self) -> bsdf::BsdfFlags {
+        bsdf::BsdfFlags::GLOSSY | bsdf::BsdfFlags::REFLECTION
+    }
+}
+
+impl MaterialTrait for RoughConductor {
+    fn bsdf(&self, rec: &hittable::HitRecord) -> Option<bsdf::Bsdf> {
+        Some(bsdf::Bsdf::new(rec, bsdf::Bxdf::RoughConductor(*self)))
+    }
+}
+
+/// Frosted glass: GGX microfacet reflection and refraction with index of refraction `ir`.
+#[derive(Copy, Clone)]
+pub struct RoughDielectric {
+    ir: f64,
+    distrib: microfacet::TrowbridgeReitz,
+}
+
+impl RoughDielectric {
+    pub fn new(ir: f64, roughness: f64) -> RoughDielectric {
+        let alpha = microfacet::TrowbridgeReitz::roughness_to_alpha(roughness);
+        RoughDielectric {
+            ir,
+            distrib: microfacet::TrowbridgeReitz::new(alpha, alpha),
+        }
+    }
+
+    /// Generalized half vector of `wo` and `wi`, facing +z, with the relative index
+    /// crossed. `None` if the pair can't be connected by any microfacet.
+    fn half_vector(&self, wo: vector3::Vec3, wi: vector3::Vec3) -> Option<(vector3::Vec3, f64)> {
+        let reflect = wo.z * wi.z > 0.0;
+        let etap = if reflect {
+            1.0
+        } else if wo.z > 0.0 {
+            self.ir
+        } else {
+            1.0 / self.ir
+        };
+        let wm = wi * etap + wo;
+        if wi.z == 0.0 || wo.z == 0.0 || wm.length_squared() == 0.0 {
+            return None;
+        }
+        let mut wm = wm.unit_vector();
+        if wm.z < 0.0 {
+            wm = wm * -1.0;
+        }
+        // Discard backfacing microfacets
+        if vector3::dot(wm, wi) * wi.z < 0.0 || vector3::dot(wm, wo) * wo.z < 0.0 {
+            return None;
+        }
+        Some((wm, etap))
+    }
+}
+
+impl bsdf::BxdfTrait for RoughDielectric {
+    /// +z points out of the glass.
+    fn eval(&self, wo: vector3::Vec3, wi: vector3::Vec3) -> vector3::Color {
+        let white = vector3::Color::new(1.0, 1.0, 1.0);
+        let (wm, etap) = match self.half_vector(wo, wi) {
+            Some(x) => x,
+            None => return white * 0.0,
+        };
+        let f = fresnel_dielectric(vector3::dot(wo, wm), self.ir);
+        if etap == 1.0 {
+            white * (self.distrib.d(wm) * self.distrib.g(wo, wi) * f / (4.0 * wi.z * wo.z).abs())
+        } else {
+            let denom = (vector3::dot(wi, wm) + vector3::dot(wo, wm) / etap).powi(2) * wi.z * wo.z;
+            let ft = self.distrib.d(wm)
+                * (1.0 - f)
+                * self.distrib.g(wo, wi)
+                * (vector3::dot(wi, wm) * vector3::dot(wo, wm) / denom).abs();
+            // Radiance is compressed into a smaller solid angle on the denser side
+            white * (ft / (etap * etap))
+        }
+    }
+
+    /// Samples reflection or refraction through a visible microfacet.
+    fn sample(&self, wo: vector3::Vec3, u: [f64; 2]) -> Option<bsdf::BsdfSample> {
+        let white = vector3::Color::new(1.0, 1.0, 1.0);
+        // The choice between reflection and refraction shares `u[0]` with the microfacet
+        let [u_wm, u_choice] = bsdf::split_sample(u[0]);
+        let wm = self.distrib.sample_wm(wo, [u_wm, u[1]]);
+        let r = fresnel_dielectric(vector3::dot(wo, wm), self.ir);
+        let t = 1.0 - r;
+        if u_choice < r / (r + t) {
+            let wi = reflect_local(wo, wm);
+            if !same_hemisphere(wo, wi) {
+                return None;
+            }
+            let pdf = self.distrib.pdf(wo, wm) / (4.0 * vector3::dot(wo, wm).abs()) * r / (r + t);
+            let f = self.distrib.d(wm) * self.distrib.g(wo, wi) * r / (4.0 * wi.z * wo.z).abs();
+            Some(bsdf::BsdfSample {
+                wi,
+                f: white * f,
+                pdf,
+                flags: bsdf::BsdfFlags::GLOSSY | bsdf::BsdfFlags::REFLECTION,
+            })
+        } else {
+            let (wi, etap) = refract_local(wo, wm, self.ir)?;
+            if same_hemisphere(wo, wi) || wi.z == 0.0 {
+                return None;
+            }
+            let denom = (vector3::dot(wi, wm) + vector3::dot(wo, wm) / etap).powi(2);
+            let dwm_dwi = vector3::dot(wi, wm).abs() / denom;
+            let pdf = self.distrib.pdf(wo, wm) * dwm_dwi * t / (r + t);
+            let ft = t
+                * self.distrib.d(wm)
+                * self.distrib.g(wo, wi)
+                * (vector3::dot(wi, wm) * vector3::dot(wo, wm) / (wi.z * wo.z * denom)).abs();
+            Some(bsdf::BsdfSample {
+                wi,
+                f: white * (ft / (etap * etap)),
+                pdf,
+                flags: bsdf::BsdfFlags::GLOSSY | bsdf::BsdfFlags::TRANSMISSION,
+            })
+        }
+    }
+
+    fn pdf(&self, wo: vector3::Vec3, wi: vector3::Vec3) -> f64 {
+        let (wm, etap) = match self.half_vector(wo, wi) {
+            Some(x) => x,
+            None => return 0.0,
+        };
+        let r = fresnel_dielectric(vector3::dot(wo, wm), self.ir);
+        let t = 1.0 - r;
+        if etap == 1.0 {
+            self.distrib.pdf(wo, wm) / (4.0 * vector3::dot(wo, wm).abs()) * r / (r + t)
+        } else {
+            let denom = (vector3::dot(wi, wm) + vector3::dot(wo, wm) / etap).powi(2);
+            let dwm_dwi = vector3::dot(wi, wm).abs() / denom;
+            self.distrib.pdf(wo, wm) * dwm_dwi * t / (r + t)
+        }
+    }
+
+    fn flags(&self) -> bsdf::BsdfFlags {
+        bsdf::BsdfFlags::GLOSSY | bsdf::BsdfFlags::REFLECTION | bsdf::BsdfFlags::TRANSMISSION
+    }
+}
+
+impl MaterialTrait for RoughDielectric {
+    fn bsdf(&self, rec: &hittable::HitRecord) -> Option<bsdf::Bsdf> {
+        Some(bsdf::Bsdf::new(rec, bsdf::Bxdf::RoughDielectric(*self)))
+    }
+}
+
+/// Simplified d'Eon / Marschner hair fiber. Light either reflects off the cuticle (R),
+/// passes through the fiber (TT) or reflects once inside it (TRT). Each lobe is a
+/// Gaussian in the longitudinal angle times a trimmed logistic in the azimuthal
+/// angle, weighted by its attenuation. The local frame has x along the fiber.
+#[derive(Copy, Clone)]
+pub struct Hair {
+    sigma_a: vector3::Color,
+    eta: f64,
+    beta_m: f64,
+    beta_n: f64,
+    alpha: f64,
+}
+
+/// The R, TT and TRT lobes as seen from one outgoing direction.
+struct HairLobes {
+    a: [vector3::Color; 3],
+    phi_o: f64,
+    /// Ideal azimuthal exit angle relative to `phi_o`
+    phi: [f64; 3],
+    /// Mean and variance of the longitudinal angle
+    theta: [f64; 3],
+    variance: [f64; 3],
+    /// Azimuthal logistic scale
+    s: f64,
+}
+
+impl HairLobes {
+    fn weights(&self) -> [f64; 3] {
+        self.a.map(|c| c.luminance())
+    }
+
+    /// Density of lobe `p` per unit longitudinal and azimuthal angle.
+    fn density(&self, p: usize, theta_i: f64, phi_i: f64) -> f64 {
+        let v = self.variance[p];
+        let d = theta_i - self.theta[p];
+        let m = (-d * d / (2.0 * v)).exp() / (2.0 * PI * v).sqrt();
+        let mut dphi = phi_i - self.phi_o - self.phi[p];
+        while dphi > PI {
+            dphi -= 2.0 * PI;
+        }
+        while dphi < -PI {
+            dphi += 2.0 * PI;
+        }
+        m * trimmed_logistic(dphi, self.s)
+    }
+}
+
+impl Hair {
+    /// `color` is the fiber's approximate diffuse color, `beta_m` and `beta_n` its
+    /// longitudinal and azimuthal roughness in [0, 1], and `alpha` the tilt of the
+    /// cuticle scales in degrees (usually around 2).
+    pub fn new(color: vector3::Color, beta_m: f64, beta_n: f64, alpha: f64) -> Hair {
+        Hair {
+            sigma_a: Hair::sigma_a_from_reflectance(color, beta_n),
+            eta: 1.55,
+            beta_m,
+            beta_n,
+            alpha: alpha.to_radians(),
+        }
+    }
+
+    /// Absorption coefficient that gives roughly `c` as the multiple-scattered color.
+    /// Fit from Chiang et al. 2016.
+    fn sigma_a_from_reflectance(c: vector3::Color, beta_n: f64) -> vector3::Color {
+        let denom = 5.969 - 0.215 * beta_n + 2.532 * beta_n.powi(2) - 10.73 * beta_n.powi(3)
+            + 5.574 * beta_n.powi(4)
+            + 0.245 * beta_n.powi(5);
+        let sigma = |x: f64| (x.max(1e-4).ln() / denom).powi(2);
+        vector3::Color::new(sigma(c.x), sigma(c.y), sigma(c.z))
+    }
+
+    fn lobes(&self, wo: vector3::Vec3) -> HairLobes {
+        let sin_theta_o = wo.x.clamp(-1.0, 1.0);
+        let cos_theta_o = (1.0 - sin_theta_o * sin_theta_o).sqrt();
+        let theta_o = sin_theta_o.asin();
+        let phi_o = wo.z.atan2(wo.y);
+
+        // Offset across the fiber where the ray entered, from -1 to 1
+        let h = if wo.y == 0.0 && wo.z == 0.0 {
+            0.0
+        } else {
+            (-wo.y / (wo.y * wo.y + wo.z * wo.z).sqrt()).clamp(-1.0, 1.0)
+        };
+        let gamma_o = h.asin();
+
+        // Refracted direction inside the fiber and the absorption along it
+        let sin_theta_t = sin_theta_o / self.eta;
+        let cos_theta_t = (1.0 - sin_theta_t * sin_theta_t).sqrt();
+        let eta_p =
+            (self.eta * self.eta - sin_theta_o * sin_theta_o).sqrt() / cos_theta_o.max(1e-8);
+        let sin_gamma_t = (h / eta_p).clamp(-1.0, 1.0);
+        let cos_gamma_t = (1.0 - sin_gamma_t * sin_gamma_t).sqrt();
+        let gamma_t = sin_gamma_t.asin();
+        let path = 2.0 * cos_gamma_t / cos_theta_t;
+        let t = vector3::Color::new(
+            (-self.sigma_a.x * path).exp(),
+            (-self.sigma_a.y * path).exp(),
+            (-self.sigma_a.z * path).exp(),
+        );
+
+        let f = fresnel_dielectric(cos_theta_o * gamma_o.cos(), self.eta);
+        let white = vector3::Color::new(1.0, 1.0, 1.0);
+
+        // Longitudinal: mirror around the tilted scales, blurred by beta_m
+        let v = (0.726 * self.beta_m + 0.812 * self.beta_m.powi(2) + 3.7 * self.beta_m.powi(20))
+            .powi(2)
+            .max(1e-6);
+        let shift = [-2.0 * self.alpha, self.alpha, 4.0 * self.alpha];
+
+        // Azimuthal: the ideal exit angle for each lobe, blurred by beta_n
+        let s = ((PI / 8.0).sqrt()
+            * (0.265 * self.beta_n + 1.194 * self.beta_n.powi(2) + 5.372 * self.beta_n.powi(22)))
+        .max(1e-3);
+        let phi = |p: f64| 2.0 * p * gamma_t - 2.0 * gamma_o + p * PI;
+
+        HairLobes {
+            a: [
+                white * f,
+                t * (1.0 - f) * (1.0 - f),
+                t * t * (1.0 - f) * (1.0 - f) * f,
+            ],
+            phi_o,
+            phi: [phi(0.0), phi(1.0), phi(2.0)],
+            theta: shift.map(|a| -(theta_o + a)),
+            variance: [v, 0.25 * v, 4.0 * v],
+            s,
         }
+    }
+}
 
-        let unit_direction = vector3::Vec3::unit_vector(r.dir);
+/// Logistic distribution with scale `s`, restricted to [-PI, PI].
+fn sample_trimmed_logistic(u: f64, s: f64) -> f64 {
+    let cdf = |x: f64| 1.0 / (1.0 + (-x / s).exp());
+    let k = cdf(PI) - cdf(-PI);
+    let x = -s * (1.0 / (u * k + cdf(-PI)) - 1.0).ln();
+    x.clamp(-PI, PI)
+}
+
+fn trimmed_logistic(x: f64, s: f64) -> f64 {
+    let cdf = |x: f64| 1.0 / (1.0 + (-x / s).exp());
+    let e = (-x.abs() / s).exp();
+    e / (s * (1.0 + e) * (1.0 + e)) / (cdf(PI) - cdf(-PI))
+}
+
+/// Longitudinal and azimuthal angles of a direction in the fiber frame.
+fn hair_angles(w: vector3::Vec3) -> (f64, f64) {
+    (w.x.clamp(-1.0, 1.0).asin(), w.z.atan2(w.y))
+}
+
+impl bsdf::BxdfTrait for Hair {
+    fn eval(&self, wo: vector3::Vec3, wi: vector3::Vec3) -> vector3::Color {
+        let black = vector3::Color::new(0.0, 0.0, 0.0);
+        let (theta_i, phi_i) = hair_angles(wi);
+        let cos_theta_i = theta_i.cos();
+        if wi.z == 0.0 || cos_theta_i <= 0.0 {
+            return black;
+        }
+        let lobes = self.lobes(wo);
+        // Divided by |wi.z| so that the usual cosine factor cancels out.
+        (0..3).fold(black, |f, p| {
+            f + lobes.a[p] * lobes.density(p, theta_i, phi_i)
+        }) / (cos_theta_i * wi.z.abs())
+    }
 
-        let cos_theta: f64 = vector3::dot(unit_direction * (-1.0), rec.normal).min(1.0);
-        let sin_theta: f64 = (1.0 - cos_theta * cos_theta).sqrt();
+    /// Picks a lobe by its share of the total attenuation and samples its spread.
+    fn sample(&self, wo: vector3::Vec3, u: [f64; 2]) -> Option<bsdf::BsdfSample> {
+        let lobes = self.lobes(wo);
+        let weights = lobes.weights();
+        let total: f64 = weights.iter().sum();
+        if total <= 0.0 {
+            return None;
+        }
+        // Four dimensions from two: the lobe, the longitudinal Gaussian and the azimuth
+        let [u_lobe, u_theta0] = bsdf::split_sample(u[0]);
+        let [u_theta1, u_phi] = bsdf::split_sample(u[1]);
+        let mut pick = u_lobe * total;
+        let mut p = 0;
+        while p < 2 && pick >= weights[p] {
+            pick -= weights[p];
+            p += 1;
+        }
 
-        let cannot_refract: bool = refraction_ratio * sin_theta > 1.0;
-        let direction;
+        let gaussian = (-2.0 * (1.0 - u_theta0).ln()).sqrt() * (2.0 * PI * u_theta1).cos();
+        let theta_i = lobes.theta[p] + lobes.variance[p].sqrt() * gaussian;
+        if theta_i.abs() >= 0.5 * PI {
+            return None;
+        }
+        let phi_i = lobes.phi_o + lobes.phi[p] + sample_trimmed_logistic(u_phi, lobes.s);
 
-        if cannot_refract
-            || Dielectric::reflectance(cos_theta, refraction_ratio) > utils::random_double(0.0, 1.0)
-        {
-            direction = vector3::reflect(unit_direction, rec.normal);
+        let wi = vector3::Vec3::new(
+            theta_i.sin(),
+            theta_i.cos() * phi_i.cos(),
+            theta_i.cos() * phi_i.sin(),
+        );
+        let pdf = self.pdf(wo, wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+        let flags = if same_hemisphere(wo, wi) {
+            bsdf::BsdfFlags::GLOSSY | bsdf::BsdfFlags::REFLECTION
         } else {
-            direction = vector3::refract(unit_direction, rec.normal, refraction_ratio);
+            bsdf::BsdfFlags::GLOSSY | bsdf::BsdfFlags::TRANSMISSION
+        };
+        Some(bsdf::BsdfSample {
+            wi,
+            f: self.eval(wo, wi),
+            pdf,
+            flags,
+        })
+    }
+
+    fn pdf(&self, wo: vector3::Vec3, wi: vector3::Vec3) -> f64 {
+        let (theta_i, phi_i) = hair_angles(wi);
+        let cos_theta_i = theta_i.cos();
+        if cos_theta_i <= 0.0 {
+            return 0.0;
+        }
+        let lobes = self.lobes(wo);
+        let weights = lobes.weights();
+        let total: f64 = weights.iter().sum();
+        if total <= 0.0 {
+            return 0.0;
+        }
+        (0..3)
+            .map(|p| weights[p] / total * lobes.density(p, theta_i, phi_i))
+            .sum::<f64>()
+            / cos_theta_i
+    }
+
+    fn flags(&self) -> bsdf::BsdfFlags {
+        bsdf::BsdfFlags::GLOSSY | bsdf::BsdfFlags::REFLECTION | bsdf::BsdfFlags::TRANSMISSION
+    }
+}
+
+impl MaterialTrait for Hair {
+    fn bsdf(&self, rec: &hittable::HitRecord) -> Option<bsdf::Bsdf> {
+        Some(bsdf::Bsdf::new(rec, bsdf::Bxdf::Hair(*self)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bsdf::BxdfTrait;
+
+    fn direction(theta: f64, phi: f64) -> vector3::Vec3 {
+        vector3::Vec3::new(
+            theta.sin() * phi.cos(),
+            theta.sin() * phi.sin(),
+            theta.cos(),
+        )
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!(
+            (a - b).abs() <= 1e-6 * a.abs().max(b.abs()).max(1.0),
+            "{} != {}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn rough_conductor_sample_matches_eval_and_pdf() {
+        let m = RoughConductor::gold(0.3);
+        for i in 0..50 {
+            let wo = direction(0.02 * i as f64, 0.7 * i as f64);
+            if let Some(s) = m.sample(wo, [0.37 * i as f64 % 1.0, 0.61 * i as f64 % 1.0]) {
+                let g = m.eval(wo, s.wi);
+                assert_close(s.f.x, g.x);
+                assert_close(s.f.z, g.z);
+                assert_close(s.pdf, m.pdf(wo, s.wi));
+            }
+        }
+    }
+
+    #[test]
+    fn rough_dielectric_sample_matches_eval_and_pdf() {
+        let m = RoughDielectric::new(1.5, 0.4);
+        for i in 0..100 {
+            // Alternate between outside and inside the glass
+            let theta = 0.015 * i as f64 + if i % 2 == 0 { 0.0 } else { PI / 2.0 + 0.05 };
+            let wo = direction(theta, 1.3 * i as f64);
+            if let Some(s) = m.sample(wo, [0.37 * i as f64 % 1.0, 0.61 * i as f64 % 1.0]) {
+                assert_close(s.f.x, m.eval(wo, s.wi).x);
+                assert_close(s.pdf, m.pdf(wo, s.wi));
+            }
+        }
+    }
+
+    #[test]
+    fn rough_dielectric_sample_depends_only_on_u() {
+        let m = RoughDielectric::new(1.5, 0.4);
+        let wo = direction(0.3, 0.2);
+        let (mut reflected, mut refracted) = (0, 0);
+        for i in 0..256 {
+            let u = [(0.618_034 * i as f64) % 1.0, (i as f64 + 0.5) / 256.0];
+            let (a, b) = (m.sample(wo, u), m.sample(wo, u));
+            if let (Some(a), Some(b)) = (a, b) {
+                assert_eq!((a.wi.x, a.wi.y, a.wi.z), (b.wi.x, b.wi.y, b.wi.z));
+                if a.wi.z > 0.0 {
+                    reflected += 1;
+                } else {
+                    refracted += 1;
+                }
+            }
+        }
+        assert!(reflected > 0 && refracted > 0);
+    }
+
+    #[test]
+    fn hair_sample_matches_eval_and_pdf() {
+        let m = Hair::new(vector3::Color::new(0.4, 0.25, 0.1), 0.3, 0.3, 2.0);
+        for i in 0..100 {
+            let wo = direction(0.03 * i as f64 + 0.01, 0.9 * i as f64);
+            let u = [0.37 * i as f64 % 1.0, 0.61 * i as f64 % 1.0];
+            if let Some(s) = m.sample(wo, u) {
+                assert_close(s.f.y, m.eval(wo, s.wi).y);
+                assert_close(s.pdf, m.pdf(wo, s.wi));
+                // The same `u` always gives the same direction
+                let again = m.sample(wo, u).unwrap();
+                assert_eq!(
+                    (s.wi.x, s.wi.y, s.wi.z),
+                    (again.wi.x, again.wi.y, again.wi.z)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn diffuse_and_specular_sample_weights() {
+        let wo = direction(0.6, 0.2);
+        let d = bsdf::Diffuse {
+            r: vector3::Color::new(0.5, 0.5, 0.5),
+        };
+        let s = d.sample(wo, [0.3, 0.8]).unwrap();
+        assert_close(s.f.x * s.wi.z / s.pdf, 0.5);
+        assert_close(s.pdf, d.pdf(wo, s.wi));
+
+        // Light is either reflected or refracted, never absorbed
+        let g = bsdf::SpecularDielectric { ir: 1.5 };
+        for theta in [0.1, 1.0, 2.0, 3.0] {
+            let wo = direction(theta, 0.4);
+            let s = g.sample(wo, [0.5, 0.5]).unwrap();
+            assert_close(s.f.x * s.wi.z.abs() / s.pdf, 1.0);
+            assert!(s.flags.is_specular());
         }
+    }
 
-        let scattered = ray::Ray::new(rec.p, direction);
-        (true, attenuation, scattered)
+    #[test]
+    fn emitter_power_in_physical_units() {
+        let rec = |front_face| hittable::HitRecord {
+            p: vector3::Point::new(0.0, 0.0, 0.0),
+            normal: vector3::Vec3::new(0.0, 0.0, 1.0),
+            t: 1.0,
+            u: 0.5,
+            v: 0.5,
+            tangent: vector3::Vec3::new(1.0, 0.0, 0.0),
+            front_face,
+            material: Arc::new(std::sync::Mutex::new(Material::DiffuseLight(
+                DiffuseLight::new(vector3::Color::new(0.0, 0.0, 0.0)),
+            ))),
+        };
+        let tint = vector3::Color::new(1.0, 0.5, 0.25);
+        // Radiance L from a surface of area A sends out pi * A * L in total
+        let one = DiffuseLight::from_power(tint, Power::Lumens(683.0 * PI * 2.0), 2.0, false);
+        assert_close(one.emitted(&rec(true)).luminance(), 1.0);
+        assert_eq!(one.emitted(&rec(false)).luminance(), 0.0);
+        let e = one.emitted(&rec(true));
+        assert_close(e.x / e.z, 4.0);
+
+        // Two sides share the same power
+        let two = DiffuseLight::from_power(tint, Power::Watts(PI * 2.0), 2.0, true);
+        assert_close(two.emitted(&rec(true)).luminance(), 0.5);
+        assert_close(two.emitted(&rec(false)).luminance(), 0.5);
+    }
+
+    #[test]
+    fn dispersion_presets_match_catalogue_indices() {
+        for (glass, n_d) in [
+            (Dispersion::bk7(), 1.5168),
+            (Dispersion::fused_silica(), 1.4585),
+            (Dispersion::diamond(), 2.4173),
+        ] {
+            assert!(
+                (glass.ior(D_LINE) - n_d).abs() < 1e-3,
+                "{}",
+                glass.ior(D_LINE)
+            );
+            // Blue bends more than red
+            assert!(glass.ior(450.0) > glass.ior(650.0));
+            assert!(Dielectric::dispersive(glass).is_dispersive());
+        }
+        let cauchy = Dispersion::Cauchy { a: 1.5, b: 0.004 };
+        assert!((cauchy.ior(500.0) - 1.516).abs() < 1e-12);
+        assert!(!Dielectric::new(1.5).is_dispersive());
     }
 }
@@ -0,0 +1,171 @@
+// Trowbridge-Reitz (GGX) microfacet distribution, after pbrt-v4. All directions
+// are in a local shading frame with the macro-surface normal along +z.
+use crate::vector3;
+use std::f64::consts::PI;
+
+#[derive(Copy, Clone)]
+pub struct TrowbridgeReitz {
+    alpha_x: f64,
+    alpha_y: f64,
+}
+
+fn cos2_theta(w: vector3::Vec3) -> f64 {
+    w.z * w.z
+}
+
+fn tan2_theta(w: vector3::Vec3) -> f64 {
+    (1.0 - cos2_theta(w)).max(0.0) / cos2_theta(w)
+}
+
+fn cos_sin_phi(w: vector3::Vec3) -> (f64, f64) {
+    let sin_theta = (1.0 - cos2_theta(w)).max(0.0).sqrt();
+    if sin_theta == 0.0 {
+        (1.0, 0.0)
+    } else {
+        (
+            (w.x / sin_theta).clamp(-1.0, 1.0),
+            (w.y / sin_theta).clamp(-1.0, 1.0),
+        )
+    }
+}
+
+impl TrowbridgeReitz {
+    pub fn new(alpha_x: f64, alpha_y: f64) -> TrowbridgeReitz {
+        // Keep away from zero so D stays finite; 1e-4 is already a mirror.
+        TrowbridgeReitz {
+            alpha_x: alpha_x.max(1e-4),
+            alpha_y: alpha_y.max(1e-4),
+        }
+    }
+
+    /// Roughness in [0, 1] to the distribution's alpha, squared as in the Disney
+    /// model so that equal steps look roughly equally rougher.
+    pub fn roughness_to_alpha(roughness: f64) -> f64 {
+        roughness * roughness
+    }
+
+    /// Density of microfacet normals `wm`.
+    pub fn d(&self, wm: vector3::Vec3) -> f64 {
+        let tan2 = tan2_theta(wm);
+        if !tan2.is_finite() {
+            return 0.0;
+        }
+        let cos4 = cos2_theta(wm) * cos2_theta(wm);
+        if cos4 < 1e-16 {
+            return 0.0;
+        }
+        let (cos_phi, sin_phi) = cos_sin_phi(wm);
+        let e = tan2 * ((cos_phi / self.alpha_x).powi(2) + (sin_phi / self.alpha_y).powi(2));
+        1.0 / (PI * self.alpha_x * self.alpha_y * cos4 * (1.0 + e) * (1.0 + e))
+    }
+
+    fn lambda(&self, w: vector3::Vec3) -> f64 {
+        let tan2 = tan2_theta(w);
+        if !tan2.is_finite() {
+            return 0.0;
+        }
+        let (cos_phi, sin_phi) = cos_sin_phi(w);
+        let alpha2 = (cos_phi * self.alpha_x).powi(2) + (sin_phi * self.alpha_y).powi(2);
+        ((1.0 + alpha2 * tan2).sqrt() - 1.0) / 2.0
+    }
+
+    /// Fraction of microfacets visible from `w`.
+    pub fn g1(&self, w: vector3::Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// Fraction of microfacets visible from both `wo` and `wi`.
+    pub fn g(&self, wo: vector3::Vec3, wi: vector3::Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Density of normals `wm` visible from `w`, which is also the pdf of `sample_wm`.
+    pub fn pdf(&self, w: vector3::Vec3, wm: vector3::Vec3) -> f64 {
+        self.g1(w) / w.z.abs() * self.d(wm) * vector3::dot(w, wm).abs()
+    }
+
+    /// Samples a microfacet normal visible from `w` (Heitz 2018).
+    pub fn sample_wm(&self, w: vector3::Vec3, u: [f64; 2]) -> vector3::Vec3 {
+        // Stretch to the hemisphere configuration
+        let mut wh = vector3::Vec3::new(self.alpha_x * w.x, self.alpha_y * w.y, w.z).unit_vector();
+        if wh.z < 0.0 {
+            wh = wh * -1.0;
+        }
+        let t1 = if wh.z < 0.99999 {
+            vector3::cross(vector3::Vec3::new(0.0, 0.0, 1.0), wh).unit_vector()
+        } else {
+            vector3::Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = vector3::cross(wh, t1);
+
+        // Uniform disk sample, warped toward the visible half
+        let r = u[0].sqrt();
+        let phi = 2.0 * PI * u[1];
+        let px = r * phi.cos();
+        let h = (1.0 - px * px).sqrt();
+        let s = (1.0 + wh.z) / 2.0;
+        let py = (1.0 - s) * h + s * r * phi.sin();
+        let pz = (1.0 - px * px - py * py).max(0.0).sqrt();
+        let nh = t1 * px + t2 * py + wh * pz;
+
+        // Unstretch
+        vector3::Vec3::new(self.alpha_x * nh.x, self.alpha_y * nh.y, nh.z.max(1e-6)).unit_vector()
+    }
+}
+
+/// Fresnel reflectance of a conductor with complex index of refraction `eta + i k`,
+/// lit from vacuum.
+pub fn fresnel_conductor(cos_theta_i: f64, eta: f64, k: f64) -> f64 {
+    let cos2 = cos_theta_i.clamp(0.0, 1.0).powi(2);
+    let sin2 = 1.0 - cos2;
+    let eta2 = eta * eta;
+    let k2 = k * k;
+
+    let t0 = eta2 - k2 - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+    let t1 = a2_plus_b2 + cos2;
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+    let t2 = 2.0 * cos_theta_i.clamp(0.0, 1.0) * a;
+    let rs = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+    0.5 * (rp + rs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn projected_area_integrates_to_one() {
+        // Integral of D(wm) cos(theta_m) over the hemisphere, by uniform sampling
+        for (ax, ay) in [(0.3, 0.3), (0.6, 0.2)] {
+            let distrib = TrowbridgeReitz::new(ax, ay);
+            let n = 400;
+            let mut sum = 0.0;
+            for i in 0..n {
+                for j in 0..n {
+                    let cos_theta = (i as f64 + 0.5) / n as f64;
+                    let phi = 2.0 * PI * (j as f64 + 0.5) / n as f64;
+                    let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+                    let wm =
+                        vector3::Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+                    sum += distrib.d(wm) * cos_theta;
+                }
+            }
+            let integral = sum * 2.0 * PI / (n * n) as f64;
+            assert!((integral - 1.0).abs() < 0.02, "integral = {}", integral);
+        }
+    }
+
+    #[test]
+    fn conductor_fresnel_limits() {
+        // Grazing incidence reflects everything; normal incidence matches the closed form.
+        assert!((fresnel_conductor(0.0, 0.2, 3.9) - 1.0).abs() < 1e-9);
+        let (eta, k): (f64, f64) = (0.2, 3.9);
+        let normal = ((eta - 1.0).powi(2) + k * k) / ((eta + 1.0).powi(2) + k * k);
+        assert!((fresnel_conductor(1.0, eta, k) - normal).abs() < 1e-9);
+    }
+}
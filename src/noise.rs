@@ -0,0 +1,198 @@
+use crate::vector3;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+const POINT_COUNT: usize = 256;
+
+/// Gradient (Perlin) noise with random unit gradients on the integer lattice.
+#[derive(Clone)]
+pub struct Perlin {
+    ranvec: Vec<vector3::Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    /// The same `seed` always produces the same noise.
+    pub fn new(seed: u64) -> Perlin {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let ranvec = (0..POINT_COUNT)
+            .map(|_| loop {
+                let p = vector3::Vec3::new(
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                );
+                let len2 = p.length_squared();
+                if len2 > 1e-6 && len2 < 1.0 {
+                    break p.unit_vector();
+                }
+            })
+            .collect();
+        Perlin {
+            ranvec,
+            perm_x: Perlin::generate_perm(&mut rng),
+            perm_y: Perlin::generate_perm(&mut rng),
+            perm_z: Perlin::generate_perm(&mut rng),
+        }
+    }
+
+    fn generate_perm(rng: &mut StdRng) -> Vec<usize> {
+        let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+        p.shuffle(rng);
+        p
+    }
+
+    /// Smooth noise in roughly [-1, 1].
+    pub fn noise(&self, p: vector3::Point) -> f64 {
+        let u = p.x - p.x.floor();
+        let v = p.y - p.y.floor();
+        let w = p.z - p.z.floor();
+        let i = p.x.floor() as i64;
+        let j = p.y.floor() as i64;
+        let k = p.z.floor() as i64;
+
+        let mut accum = 0.0;
+        // Hermite smoothing of the interpolation weights
+        let uu = u * u * (3.0 - 2.0 * u);
+        let vv = v * v * (3.0 - 2.0 * v);
+        let ww = w * w * (3.0 - 2.0 * w);
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let gradient = self.ranvec[self.perm_x[((i + di) & 255) as usize]
+                        ^ self.perm_y[((j + dj) & 255) as usize]
+                        ^ self.perm_z[((k + dk) & 255) as usize]];
+                    let (fi, fj, fk) = (di as f64, dj as f64, dk as f64);
+                    let weight_v = vector3::Vec3::new(u - fi, v - fj, w - fk);
+                    accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                        * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                        * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                        * vector3::dot(gradient, weight_v);
+                }
+            }
+        }
+        accum
+    }
+
+    /// Fractal Brownian motion: `octaves` layers of noise, each `lacunarity` times
+    /// finer and `gain` times weaker than the last.
+    pub fn fbm(&self, p: vector3::Point, octaves: usize, lacunarity: f64, gain: f64) -> f64 {
+        let mut accum = 0.0;
+        let mut temp_p = p;
+        let mut weight = 1.0;
+        for _ in 0..octaves {
+            accum += weight * self.noise(temp_p);
+            weight *= gain;
+            temp_p = temp_p * lacunarity;
+        }
+        accum
+    }
+
+    /// Absolute value of fBm with the usual doubling frequency and halving amplitude.
+    pub fn turb(&self, p: vector3::Point, depth: usize) -> f64 {
+        self.fbm(p, depth, 2.0, 0.5).abs()
+    }
+}
+
+/// Distances from a point to the nearest (`f1`) and second nearest (`f2`)
+/// feature points, plus a hash identifying the nearest one's cell.
+#[derive(Copy, Clone)]
+pub struct WorleySample {
+    pub f1: f64,
+    pub f2: f64,
+    pub id: u32,
+}
+
+/// Worley (cellular) noise with one random feature point per unit cell.
+#[derive(Copy, Clone)]
+pub struct Worley {
+    seed: u32,
+}
+
+impl Worley {
+    pub fn new(seed: u32) -> Worley {
+        Worley { seed }
+    }
+
+    pub fn sample(&self, p: vector3::Point) -> WorleySample {
+        let (ci, cj, ck) = (p.x.floor() as i32, p.y.floor() as i32, p.z.floor() as i32);
+        let mut f1 = f64::INFINITY;
+        let mut f2 = f64::INFINITY;
+        let mut id = 0;
+        for di in -1..=1 {
+            for dj in -1..=1 {
+                for dk in -1..=1 {
+                    let (i, j, k) = (ci + di, cj + dj, ck + dk);
+                    let h = self.hash(i, j, k);
+                    let feature = vector3::Point::new(
+                        i as f64 + unit(h),
+                        j as f64 + unit(hash_u32(h)),
+                        k as f64 + unit(hash_u32(hash_u32(h))),
+                    );
+                    let d = (feature - p).length();
+                    if d < f1 {
+                        f2 = f1;
+                        f1 = d;
+                        id = h;
+                    } else if d < f2 {
+                        f2 = d;
+                    }
+                }
+            }
+        }
+        WorleySample { f1, f2, id }
+    }
+
+    fn hash(&self, i: i32, j: i32, k: i32) -> u32 {
+        let h = hash_u32(self.seed ^ (i as u32).wrapping_mul(0x8da6_b343));
+        let h = hash_u32(h ^ (j as u32).wrapping_mul(0xd816_3841));
+        hash_u32(h ^ (k as u32).wrapping_mul(0xcb1a_b31f))
+    }
+}
+
+/// Integer hash with good avalanche behavior (lowbias32).
+pub fn hash_u32(mut x: u32) -> u32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb_352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846c_a68b);
+    x ^= x >> 16;
+    x
+}
+
+/// Maps a hash to [0, 1).
+pub fn unit(h: u32) -> f64 {
+    h as f64 / (u32::MAX as f64 + 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn perlin_is_deterministic_and_zero_on_lattice() {
+        let a = Perlin::new(7);
+        let b = Perlin::new(7);
+        let p = vector3::Point::new(1.3, -2.7, 0.4);
+        assert_eq!(a.noise(p), b.noise(p));
+        assert!(a.noise(vector3::Point::new(3.0, -1.0, 2.0)).abs() < 1e-12);
+        for i in 0..100 {
+            let n = a.noise(vector3::Point::new(i as f64 * 0.37, i as f64 * 0.11, 0.5));
+            assert!((-1.0..=1.0).contains(&n));
+        }
+    }
+
+    #[test]
+    fn worley_distances_are_ordered() {
+        let w = Worley::new(3);
+        for i in 0..100 {
+            let s = w.sample(vector3::Point::new(i as f64 * 0.23, 1.7, -i as f64 * 0.31));
+            assert!(s.f1 <= s.f2);
+            // A feature point in the point's own cell is never farther than sqrt(3).
+            assert!(s.f1 <= 3.0_f64.sqrt());
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use crate::vector3;
+
+/// Orthonormal basis with `w` as the distinguished axis.
+#[derive(Copy, Clone)]
+pub struct Onb {
+    pub u: vector3::Vec3,
+    pub v: vector3::Vec3,
+    pub w: vector3::Vec3,
+}
+
+impl Onb {
+    pub fn build_from_w(n: vector3::Vec3) -> Onb {
+        let w = n.unit_vector();
+        let a = if w.x.abs() > 0.9 {
+            vector3::Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            vector3::Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = vector3::cross(w, a).unit_vector();
+        let u = vector3::cross(v, w);
+        Onb { u, v, w }
+    }
+
+    /// Basis with `w` along `n` and `u` as close to `u_hint` as possible. Falls back
+    /// to `build_from_w` when the hint is zero or parallel to `n`.
+    pub fn build_from_wu(n: vector3::Vec3, u_hint: vector3::Vec3) -> Onb {
+        let w = n.unit_vector();
+        let u = u_hint - w * vector3::dot(u_hint, w);
+        if u.near_zero() {
+            return Onb::build_from_w(n);
+        }
+        let u = u.unit_vector();
+        let v = vector3::cross(w, u);
+        Onb { u, v, w }
+    }
+
+    /// Local coordinates to world space.
+    pub fn local(&self, a: vector3::Vec3) -> vector3::Vec3 {
+        self.u * a.x + self.v * a.y + self.w * a.z
+    }
+
+    /// World space to local coordinates.
+    pub fn to_local(&self, a: vector3::Vec3) -> vector3::Vec3 {
+        vector3::Vec3::new(
+            vector3::dot(a, self.u),
+            vector3::dot(a, self.v),
+            vector3::dot(a, self.w),
+        )
+    }
+}
@@ -0,0 +1,383 @@
+use crate::bsdf;
+use crate::hittable;
+use crate::material;
+use crate::microfacet;
+use crate::texture;
+use crate::texture::TextureTrait;
+use crate::vector3;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+/// Disney "principled" material (Burley 2012, with the 2015 specular transmission).
+/// Every parameter is a texture; scalar parameters read the first channel, so
+/// constant ones can be given with `texture::scalar`. All scalars are in [0, 1].
+pub struct Principled {
+    pub base_color: Arc<texture::Texture>,
+    pub metallic: Arc<texture::Texture>,
+    pub roughness: Arc<texture::Texture>,
+    /// Dielectric reflectance at normal incidence, 0.5 being 4% (index of refraction 1.5)
+    pub specular: Arc<texture::Texture>,
+    /// Tints dielectric reflection toward the base color
+    pub specular_tint: Arc<texture::Texture>,
+    /// Stretches highlights along the surface tangent
+    pub anisotropic: Arc<texture::Texture>,
+    /// Extra grazing retro-reflection for cloth
+    pub sheen: Arc<texture::Texture>,
+    pub sheen_tint: Arc<texture::Texture>,
+    /// Strength of a second, colorless specular layer
+    pub clearcoat: Arc<texture::Texture>,
+    /// Glossiness of the clearcoat: 0 is satin, 1 is gloss
+    pub clearcoat_gloss: Arc<texture::Texture>,
+    /// Blends from opaque to glass-like refraction
+    pub transmission: Arc<texture::Texture>,
+}
+
+impl Principled {
+    /// Rough dielectric of the given color. Set the other fields to taste.
+    pub fn new(base_color: Arc<texture::Texture>) -> Principled {
+        Principled {
+            base_color,
+            metallic: texture::scalar(0.0),
+            roughness: texture::scalar(0.5),
+            specular: texture::scalar(0.5),
+            specular_tint: texture::scalar(0.0),
+            anisotropic: texture::scalar(0.0),
+            sheen: texture::scalar(0.0),
+            sheen_tint: texture::scalar(0.5),
+            clearcoat: texture::scalar(0.0),
+            clearcoat_gloss: texture::scalar(1.0),
+            transmission: texture::scalar(0.0),
+        }
+    }
+
+    /// Looks up every parameter at the hit point.
+    pub fn lookup(&self, rec: &hittable::HitRecord) -> PrincipledBsdf {
+        let scalar = |t: &Arc<texture::Texture>| t.value(rec.u, rec.v, rec.p).x.clamp(0.0, 1.0);
+        let base_color = self.base_color.value(rec.u, rec.v, rec.p);
+        let metallic = scalar(&self.metallic);
+        let roughness = scalar(&self.roughness);
+        let specular = scalar(&self.specular);
+        let transmission = scalar(&self.transmission);
+
+        let lum = base_color.luminance();
+        let white = vector3::Color::new(1.0, 1.0, 1.0);
+        let tint = if lum > 0.0 { base_color / lum } else { white };
+
+        let aspect = (1.0 - 0.9 * scalar(&self.anisotropic)).sqrt();
+        let alpha = microfacet::TrowbridgeReitz::roughness_to_alpha(roughness);
+        let spec_tint = scalar(&self.specular_tint);
+        let dielectric_f0 = (white * (1.0 - spec_tint) + tint * spec_tint) * (0.08 * specular);
+        // Index of refraction matching the specular reflectance
+        let ior = 2.0 / (1.0 - (0.08 * specular).sqrt()) - 1.0;
+        let sheen_tint = scalar(&self.sheen_tint);
+
+        PrincipledBsdf {
+            base_color,
+            roughness,
+            diffuse_weight: (1.0 - metallic) * (1.0 - transmission),
+            specular_f0: dielectric_f0 * (1.0 - metallic) + base_color * metallic,
+            specular: microfacet::TrowbridgeReitz::new(
+                (alpha / aspect).max(0.001),
+                (alpha * aspect).max(0.001),
+            ),
+            sheen: (white * (1.0 - sheen_tint) + tint * sheen_tint)
+                * (scalar(&self.sheen) * (1.0 - metallic)),
+            clearcoat: scalar(&self.clearcoat),
+            clearcoat_alpha: 0.1 * (1.0 - scalar(&self.clearcoat_gloss))
+                + 0.001 * scalar(&self.clearcoat_gloss),
+            transmission_weight: (1.0 - metallic) * transmission,
+            transmission_tint: vector3::Color::new(
+                base_color.x.sqrt(),
+                base_color.y.sqrt(),
+                base_color.z.sqrt(),
+            ),
+            glass: material::RoughDielectric::new(ior.max(1.0001), roughness),
+        }
+    }
+}
+
+fn schlick_weight(cos_theta: f64) -> f64 {
+    (1.0 - cos_theta).clamp(0.0, 1.0).powi(5)
+}
+
+/// Generalized Trowbridge-Reitz with gamma = 1, used for the clearcoat.
+fn gtr1(cos_theta_h: f64, alpha: f64) -> f64 {
+    let a2 = alpha * alpha;
+    (a2 - 1.0) / (PI * a2.ln() * (1.0 + (a2 - 1.0) * cos_theta_h * cos_theta_h))
+}
+
+fn smith_g_ggx(cos_theta: f64, alpha: f64) -> f64 {
+    let a2 = alpha * alpha;
+    let c2 = cos_theta * cos_theta;
+    1.0 / (cos_theta.abs() + (a2 + c2 - a2 * c2).sqrt())
+}
+
+/// `Principled` evaluated at one point, in a local frame with +z pointing out of the surface.
+#[derive(Clone)]
+pub struct PrincipledBsdf {
+    base_color: vector3::Color,
+    roughness: f64,
+    diffuse_weight: f64,
+    specular_f0: vector3::Color,
+    specular: microfacet::TrowbridgeReitz,
+    sheen: vector3::Color,
+    clearcoat: f64,
+    clearcoat_alpha: f64,
+    transmission_weight: f64,
+    transmission_tint: vector3::Color,
+    glass: material::RoughDielectric,
+}
+
+impl PrincipledBsdf {
+    /// Chance of sampling the diffuse, specular, clearcoat and transmission lobes
+    /// from outside the surface.
+    fn lobe_probabilities(&self) -> [f64; 4] {
+        let weights = [
+            self.diffuse_weight,
+            1.0,
+            0.25 * self.clearcoat,
+            self.transmission_weight,
+        ];
+        let total: f64 = weights.iter().sum();
+        weights.map(|w| w / total)
+    }
+}
+
+impl bsdf::BxdfTrait for PrincipledBsdf {
+    fn eval(&self, wo: vector3::Vec3, wi: vector3::Vec3) -> vector3::Color {
+        let black = vector3::Color::new(0.0, 0.0, 0.0);
+        if wo.z <= 0.0 {
+            // Only light that refracted in can be inside, so behave like the glass.
+            return self.transmission_tint * self.glass.eval(wo, wi);
+        }
+        if wi.z <= 0.0 {
+            return self.transmission_tint * self.glass.eval(wo, wi) * self.transmission_weight;
+        }
+
+        let wh = wo + wi;
+        if wh.length_squared() == 0.0 {
+            return black;
+        }
+        let wh = wh.unit_vector();
+        let cos_theta_d = vector3::dot(wi, wh);
+        let (cos_o, cos_i) = (wo.z, wi.z);
+
+        // Diffuse with grazing retro-reflection, plus sheen
+        let fo = schlick_weight(cos_o);
+        let fi = schlick_weight(cos_i);
+        let fd90 = 0.5 + 2.0 * self.roughness * cos_theta_d * cos_theta_d;
+        let fd = (1.0 + (fd90 - 1.0) * fo) * (1.0 + (fd90 - 1.0) * fi);
+        let diffuse = (self.base_color * (fd / PI) + self.sheen * schlick_weight(cos_theta_d))
+            * self.diffuse_weight;
+
+        // Primary specular
+        let white = vector3::Color::new(1.0, 1.0, 1.0);
+        let f = self.specular_f0 + (white - self.specular_f0) * schlick_weight(cos_theta_d);
+        let specular = f * (self.specular.d(wh) * self.specular.g(wo, wi) / (4.0 * cos_o * cos_i));
+
+        // Clearcoat
+        let dr = gtr1(wh.z, self.clearcoat_alpha);
+        let fr = 0.04 + 0.96 * schlick_weight(cos_theta_d);
+        let gr = smith_g_ggx(cos_o, 0.25) * smith_g_ggx(cos_i, 0.25);
+        let clearcoat = white * (self.clearcoat * 0.25 * gr * fr * dr);
+
+        // The primary specular already reflects off the outside, so the glass only refracts
+        diffuse + specular + clearcoat
+    }
+
+    fn pdf(&self, wo: vector3::Vec3, wi: vector3::Vec3) -> f64 {
+        if wo.z <= 0.0 {
+            return self.glass.pdf(wo, wi);
+        }
+        let [p_diffuse, p_specular, p_clearcoat, p_transmission] = self.lobe_probabilities();
+        if wi.z < 0.0 {
+            return p_transmission * self.glass.pdf(wo, wi);
+        }
+        let mut pdf = 0.0;
+        if wi.z > 0.0 {
+            let wh = (wo + wi).unit_vector();
+            pdf += p_diffuse * wi.z / PI;
+            pdf += p_specular * self.specular.pdf(wo, wh) / (4.0 * vector3::dot(wo, wh).abs());
+            pdf += p_clearcoat * gtr1(wh.z, self.clearcoat_alpha) * wh.z
+                / (4.0 * vector3::dot(wo, wh).abs());
+        }
+        pdf
+    }
+
+    /// Picks a lobe and samples it; `f` and `pdf` are for the whole mixture.
+    fn sample(&self, wo: vector3::Vec3, u: [f64; 2]) -> Option<bsdf::BsdfSample> {
+        if wo.z <= 0.0 {
+            let mut s = self.glass.sample(wo, u)?;
+            s.f = self.transmission_tint * s.f;
+            return Some(s);
+        }
+
+        let probabilities = self.lobe_probabilities();
+        // The lobe is picked with `u[0]`, which is then stretched back over [0, 1)
+        let mut pick = u[0];
+        let mut lobe = 0;
+        while lobe < 3 && pick >= probabilities[lobe] {
+            pick -= probabilities[lobe];
+            lobe += 1;
+        }
+        let u = [(pick / probabilities[lobe]).min(1.0 - f64::EPSILON), u[1]];
+
+        let wi = match lobe {
+            0 => vector3::Vec3::cosine_direction(u[0], u[1]),
+            1 => material::reflect_local(wo, self.specular.sample_wm(wo, u)),
+            2 => {
+                let a2 = self.clearcoat_alpha * self.clearcoat_alpha;
+                let cos_theta = ((1.0 - a2.powf(1.0 - u[0])) / (1.0 - a2)).sqrt();
+                let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+                let phi = 2.0 * PI * u[1];
+                let mut wh =
+                    vector3::Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+                if vector3::dot(wo, wh) < 0.0 {
+                    wh = wh * -1.0;
+                }
+                material::reflect_local(wo, wh)
+            }
+            _ => self.glass.sample(wo, u)?.wi,
+        };
+        // Only the glass lobe goes below the surface, and it isn't used to reflect
+        if wi.z == 0.0 || (wi.z < 0.0) != (lobe == 3) {
+            return None;
+        }
+        let pdf = self.pdf(wo, wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+        let flags = match lobe {
+            0 => bsdf::BsdfFlags::DIFFUSE | bsdf::BsdfFlags::REFLECTION,
+            3 => bsdf::BsdfFlags::GLOSSY | bsdf::BsdfFlags::TRANSMISSION,
+            _ => bsdf::BsdfFlags::GLOSSY | bsdf::BsdfFlags::REFLECTION,
+        };
+        Some(bsdf::BsdfSample {
+            wi,
+            f: self.eval(wo, wi),
+            pdf,
+            flags,
+        })
+    }
+
+    fn flags(&self) -> bsdf::BsdfFlags {
+        let flags =
+            bsdf::BsdfFlags::DIFFUSE | bsdf::BsdfFlags::GLOSSY | bsdf::BsdfFlags::REFLECTION;
+        if self.transmission_weight > 0.0 {
+            flags | bsdf::BsdfFlags::TRANSMISSION
+        } else {
+            flags
+        }
+    }
+}
+
+impl material::MaterialTrait for Principled {
+    fn bsdf(&self, rec: &hittable::HitRecord) -> Option<bsdf::Bsdf> {
+        Some(bsdf::Bsdf::new(
+            rec,
+            bsdf::Bxdf::Principled(self.lookup(rec)),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bsdf::BxdfTrait;
+    use std::sync::Mutex;
+
+    fn record() -> hittable::HitRecord {
+        hittable::HitRecord {
+            p: vector3::Point::new(0.0, 0.0, 0.0),
+            normal: vector3::Vec3::new(0.0, 0.0, 1.0),
+            t: 1.0,
+            u: 0.5,
+            v: 0.5,
+            tangent: vector3::Vec3::new(1.0, 0.0, 0.0),
+            front_face: true,
+            material: Arc::new(Mutex::new(material::Material::Lambertian(
+                material::Lambertian::new(vector3::Color::new(0.5, 0.5, 0.5)),
+            ))),
+        }
+    }
+
+    #[test]
+    fn pdf_integrates_to_at_most_one() {
+        let mut m = Principled::new(texture::solid(vector3::Color::new(0.8, 0.3, 0.2)));
+        m.metallic = texture::scalar(0.3);
+        m.roughness = texture::scalar(0.5);
+        m.anisotropic = texture::scalar(0.5);
+        m.clearcoat = texture::scalar(0.7);
+        m.clearcoat_gloss = texture::scalar(0.0);
+        m.transmission = texture::scalar(0.4);
+        let bsdf = m.lookup(&record());
+        let wo = vector3::Vec3::new(0.5, 0.2, 0.6).unit_vector();
+
+        // Midpoint rule over the whole sphere. Samples that fall below the horizon
+        // are rejected, so the total may be a little under one but never over.
+        let n = 800;
+        let mut sum = 0.0;
+        for i in 0..n {
+            for j in 0..n {
+                let cos_theta = -1.0 + 2.0 * (i as f64 + 0.5) / n as f64;
+                let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+                let phi = 2.0 * PI * (j as f64 + 0.5) / n as f64;
+                let wi =
+                    vector3::Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+                sum += bsdf.pdf(wo, wi);
+            }
+        }
+        let integral = sum * 4.0 * PI / (n * n) as f64;
+        assert!(integral > 0.9 && integral < 1.01, "integral = {}", integral);
+    }
+
+    #[test]
+    fn sample_depends_only_on_u() {
+        let mut m = Principled::new(texture::solid(vector3::Color::new(0.8, 0.3, 0.2)));
+        m.metallic = texture::scalar(0.3);
+        m.clearcoat = texture::scalar(0.7);
+        m.transmission = texture::scalar(0.4);
+        let bsdf = m.lookup(&record());
+        let wo = vector3::Vec3::new(0.5, 0.2, 0.6).unit_vector();
+        let (mut diffuse, mut transmission) = (false, false);
+        for i in 0..256 {
+            let u = [(0.618_034 * i as f64) % 1.0, (i as f64 + 0.5) / 256.0];
+            if let Some(s) = bsdf.sample(wo, u) {
+                let again = bsdf.sample(wo, u).unwrap();
+                assert_eq!(
+                    (s.wi.x, s.wi.y, s.wi.z),
+                    (again.wi.x, again.wi.y, again.wi.z)
+                );
+                diffuse |= s.flags.contains(bsdf::BsdfFlags::DIFFUSE);
+                transmission |= s.flags.is_transmission();
+            }
+        }
+        // The lobes at either end of the pick are still reached
+        assert!(diffuse && transmission);
+    }
+
+    #[test]
+    fn glass_does_not_add_to_outside_reflection() {
+        // Black, so turning transmission off leaves only the primary specular
+        let mut m = Principled::new(texture::solid(vector3::Color::new(0.0, 0.0, 0.0)));
+        m.roughness = texture::scalar(0.2);
+        m.transmission = texture::scalar(1.0);
+        let bsdf = m.lookup(&record());
+        let wo = vector3::Vec3::new(0.3, 0.0, 0.9).unit_vector();
+        for i in 0..2000 {
+            let u = [(i % 50) as f64 / 50.0, (i / 50) as f64 / 40.0];
+            if let Some(s) = bsdf.sample(wo, u) {
+                assert_eq!(s.wi.z > 0.0, s.flags.contains(bsdf::BsdfFlags::REFLECTION));
+                assert_eq!(s.wi.z < 0.0, s.flags.is_transmission());
+            }
+        }
+
+        // In the mirror direction only the primary specular reflects
+        let wi = vector3::Vec3::new(-wo.x, -wo.y, wo.z);
+        assert!(bsdf.glass.eval(wo, wi).x > 0.0);
+        let f = bsdf.eval(wo, wi);
+        m.transmission = texture::scalar(0.0);
+        let opaque = m.lookup(&record());
+        assert!((f.x - opaque.eval(wo, wi).x).abs() < 1e-9 * f.x.max(1.0));
+    }
+}
@@ -0,0 +1,161 @@
+use crate::aabb;
+use crate::hittable;
+use crate::material;
+use crate::ray;
+use crate::vector3;
+use std::sync::{Arc, Mutex};
+
+/// Parallelogram with corner `q` and edges `u` and `v`. It faces `cross(u, v)`.
+#[derive(Clone)]
+pub struct Quad {
+    q: vector3::Point,
+    u: vector3::Vec3,
+    v: vector3::Vec3,
+    normal: vector3::Vec3,
+    d: f64,
+    /// Turns a point's offset from `q` into its coordinates along `u` and `v`
+    w: vector3::Vec3,
+    area: f64,
+    material: Arc<Mutex<material::Material>>,
+}
+
+impl Quad {
+    pub fn new(
+        q: vector3::Point,
+        u: vector3::Vec3,
+        v: vector3::Vec3,
+        mat: Arc<Mutex<material::Material>>,
+    ) -> Quad {
+        let n = vector3::cross(u, v);
+        let normal = n.unit_vector();
+        Quad {
+            q,
+            u,
+            v,
+            normal,
+            d: vector3::dot(normal, q),
+            w: n / vector3::dot(n, n),
+            area: n.length(),
+            material: mat,
+        }
+    }
+
+    pub fn normal(&self) -> vector3::Vec3 {
+        self.normal
+    }
+
+    pub fn area(&self) -> f64 {
+        self.area
+    }
+}
+
+impl hittable::Hittable for Quad {
+    fn hit(&self, r: &ray::Ray, t_min: f64, t_max: f64) -> Option<hittable::HitRecord> {
+        let denom = vector3::dot(self.normal, r.dir);
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+        let root = (self.d - vector3::dot(self.normal, r.origin)) / denom;
+        if root < t_min || t_max < root {
+            return None;
+        }
+
+        let p = r.at(root);
+        let planar = p - self.q;
+        let alpha = vector3::dot(self.w, vector3::cross(planar, self.v));
+        let beta = vector3::dot(self.w, vector3::cross(self.u, planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        let mut hit_record = hittable::HitRecord {
+            p,
+            t: root,
+            u: alpha,
+            v: beta,
+            tangent: self.u.unit_vector(),
+            normal: vector3::Vec3::new(0.0, 0.0, 0.0),
+            front_face: false,
+            material: self.material.clone(),
+        };
+        hit_record.set_face_normal(r, &self.normal);
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        let corners = [self.q + self.u, self.q + self.v, self.q + self.u + self.v];
+        let b = corners
+            .iter()
+            .fold(aabb::Aabb::new(self.q, self.q), |b, &c| {
+                aabb::surrounding_box(b, aabb::Aabb::new(c, c))
+            });
+        // Keep axis-aligned quads from having a zero-thickness box
+        Some(b.pad(1e-4))
+    }
+
+    fn sample(&self, origin: vector3::Point, u: [f64; 2]) -> Option<(hittable::HitRecord, f64)> {
+        let p = self.q + self.u * u[0] + self.v * u[1];
+        let rec = self.hit(&ray::Ray::new(origin, p - origin), 1e-6, f64::INFINITY)?;
+        let pdf = hittable::solid_angle_pdf(origin, &rec, self.area);
+        Some((rec, pdf))
+    }
+
+    fn pdf_value(&self, origin: vector3::Point, dir: vector3::Vec3) -> f64 {
+        match self.hit(&ray::Ray::new(origin, dir), 1e-6, f64::INFINITY) {
+            Some(rec) => hittable::solid_angle_pdf(origin, &rec, self.area),
+            None => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittable;
+    use crate::test_utils::mat;
+
+    #[test]
+    fn quad_hit_uv_and_miss() {
+        let quad = Quad::new(
+            vector3::Point::new(0.0, 0.0, 0.0),
+            vector3::Vec3::new(2.0, 0.0, 0.0),
+            vector3::Vec3::new(0.0, 1.0, 0.0),
+            mat(),
+        );
+        let r = ray::Ray::new(
+            vector3::Point::new(0.5, 0.25, 3.0),
+            vector3::Vec3::new(0.0, 0.0, -1.0),
+        );
+        let rec = quad.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 3.0).abs() < 1e-9);
+        assert!((rec.u - 0.25).abs() < 1e-9 && (rec.v - 0.25).abs() < 1e-9);
+        assert!(rec.front_face);
+
+        let r = ray::Ray::new(
+            vector3::Point::new(2.5, 0.25, 3.0),
+            vector3::Vec3::new(0.0, 0.0, -1.0),
+        );
+        assert!(quad.hit(&r, 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn sample_pdf_matches_pdf_value() {
+        let quad = Quad::new(
+            vector3::Point::new(-1.0, 2.0, -1.0),
+            vector3::Vec3::new(2.0, 0.0, 0.0),
+            vector3::Vec3::new(0.0, 0.0, 2.0),
+            mat(),
+        );
+        let origin = vector3::Point::new(0.3, 0.0, 0.1);
+        let (rec, pdf) = quad.sample(origin, [0.2, 0.7]).unwrap();
+        let dir = (rec.p - origin).unit_vector();
+        assert!((quad.pdf_value(origin, dir) - pdf).abs() < 1e-9);
+
+        // Straight below the center: distance 2, area 4, cosine 1
+        let pdf = quad.pdf_value(
+            vector3::Point::new(0.0, 0.0, 0.0),
+            vector3::Vec3::new(0.0, 1.0, 0.0),
+        );
+        assert!((pdf - 1.0).abs() < 1e-9);
+    }
+}
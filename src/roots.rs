@@ -0,0 +1,182 @@
+// Closed-form polynomial root finders, after Jochen Schwarze's "Cubic and
+// Quartic Roots" (Graphics Gems I). Coefficients are given lowest degree
+// first, so `c[0] + c[1] x + c[2] x^2 + ...`. Only real roots are returned.
+use std::f64::consts::PI;
+
+const EQN_EPS: f64 = 1e-9;
+
+fn is_zero(x: f64) -> bool {
+    x > -EQN_EPS && x < EQN_EPS
+}
+
+pub fn solve_quadric(c: [f64; 3]) -> Vec<f64> {
+    // Normal form: x^2 + 2px + q = 0
+    let p = c[1] / (2.0 * c[2]);
+    let q = c[0] / c[2];
+    let d = p * p - q;
+
+    if is_zero(d) {
+        vec![-p]
+    } else if d < 0.0 {
+        vec![]
+    } else {
+        let sqrt_d = d.sqrt();
+        vec![sqrt_d - p, -sqrt_d - p]
+    }
+}
+
+pub fn solve_cubic(c: [f64; 4]) -> Vec<f64> {
+    // Normal form: x^3 + Ax^2 + Bx + C = 0
+    let a = c[2] / c[3];
+    let b = c[1] / c[3];
+    let cc = c[0] / c[3];
+
+    // Substitute x = y - A/3 to eliminate the quadric term: y^3 + 3py + 2q = 0
+    let sq_a = a * a;
+    let p = 1.0 / 3.0 * (-1.0 / 3.0 * sq_a + b);
+    let q = 1.0 / 2.0 * (2.0 / 27.0 * a * sq_a - 1.0 / 3.0 * a * b + cc);
+
+    // Cardano's formula
+    let cb_p = p * p * p;
+    let d = q * q + cb_p;
+
+    let mut s = if is_zero(d) {
+        if is_zero(q) {
+            // One triple solution
+            vec![0.0]
+        } else {
+            // One single and one double solution
+            let u = (-q).cbrt();
+            vec![2.0 * u, -u]
+        }
+    } else if d < 0.0 {
+        // Casus irreducibilis: three real solutions
+        let phi = 1.0 / 3.0 * (-q / (-cb_p).sqrt()).acos();
+        let t = 2.0 * (-p).sqrt();
+        vec![
+            t * phi.cos(),
+            -t * (phi + PI / 3.0).cos(),
+            -t * (phi - PI / 3.0).cos(),
+        ]
+    } else {
+        // One real solution
+        let sqrt_d = d.sqrt();
+        vec![(sqrt_d - q).cbrt() - (sqrt_d + q).cbrt()]
+    };
+
+    let sub = 1.0 / 3.0 * a;
+    for x in s.iter_mut() {
+        *x -= sub;
+    }
+    s
+}
+
+pub fn solve_quartic(c: [f64; 5]) -> Vec<f64> {
+    // Normal form: x^4 + Ax^3 + Bx^2 + Cx + D = 0
+    let a = c[3] / c[4];
+    let b = c[2] / c[4];
+    let cc = c[1] / c[4];
+    let d = c[0] / c[4];
+
+    // Substitute x = y - A/4 to eliminate the cubic term: y^4 + py^2 + qy + r = 0
+    let sq_a = a * a;
+    let p = -3.0 / 8.0 * sq_a + b;
+    let q = 1.0 / 8.0 * sq_a * a - 1.0 / 2.0 * a * b + cc;
+    let r = -3.0 / 256.0 * sq_a * sq_a + 1.0 / 16.0 * sq_a * b - 1.0 / 4.0 * a * cc + d;
+
+    let mut s = if is_zero(r) {
+        // No absolute term: y(y^3 + py + q) = 0
+        let mut s = solve_cubic([q, p, 0.0, 1.0]);
+        s.push(0.0);
+        s
+    } else {
+        // Take one root of the resolvent cubic...
+        let z = solve_cubic([
+            1.0 / 2.0 * r * p - 1.0 / 8.0 * q * q,
+            -r,
+            -1.0 / 2.0 * p,
+            1.0,
+        ])[0];
+
+        // ...and use it to split the quartic into two quadrics
+        let mut u = z * z - r;
+        let mut v = 2.0 * z - p;
+
+        if is_zero(u) {
+            u = 0.0;
+        } else if u > 0.0 {
+            u = u.sqrt();
+        } else {
+            return vec![];
+        }
+
+        if is_zero(v) {
+            v = 0.0;
+        } else if v > 0.0 {
+            v = v.sqrt();
+        } else {
+            return vec![];
+        }
+
+        let mut s = solve_quadric([z - u, if q < 0.0 { -v } else { v }, 1.0]);
+        s.extend(solve_quadric([z + u, if q < 0.0 { v } else { -v }, 1.0]));
+        s
+    };
+
+    // Resubstitute, then polish with a couple of Newton steps on the
+    // original polynomial since the closed form loses precision quickly.
+    let sub = 1.0 / 4.0 * a;
+    for x in s.iter_mut() {
+        *x -= sub;
+        for _ in 0..2 {
+            let f = (((*x + a) * *x + b) * *x + cc) * *x + d;
+            let df = ((4.0 * *x + 3.0 * a) * *x + 2.0 * b) * *x + cc;
+            if df != 0.0 {
+                *x -= f / df;
+            }
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(mut v: Vec<f64>) -> Vec<f64> {
+        v.sort_by(|a, b| a.total_cmp(b));
+        v
+    }
+
+    fn assert_roots(got: Vec<f64>, expected: &[f64]) {
+        let got = sorted(got);
+        assert_eq!(got.len(), expected.len(), "roots: {:?}", got);
+        for (g, e) in got.iter().zip(expected) {
+            assert!((g - e).abs() < 1e-6, "roots: {:?}", got);
+        }
+    }
+
+    #[test]
+    fn quadric_roots() {
+        // (x - 1)(x + 3)
+        assert_roots(solve_quadric([-3.0, 2.0, 1.0]), &[-3.0, 1.0]);
+        assert_roots(solve_quadric([1.0, 0.0, 1.0]), &[]);
+    }
+
+    #[test]
+    fn cubic_roots() {
+        // (x - 1)(x - 2)(x - 3)
+        assert_roots(solve_cubic([-6.0, 11.0, -6.0, 1.0]), &[1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn quartic_roots() {
+        // (x - 1)(x - 2)(x - 3)(x - 4)
+        assert_roots(
+            solve_quartic([24.0, -50.0, 35.0, -10.0, 1.0]),
+            &[1.0, 2.0, 3.0, 4.0],
+        );
+        // (x^2 + 1)(x - 2)(x + 2)
+        assert_roots(solve_quartic([-4.0, 0.0, -3.0, 0.0, 1.0]), &[-2.0, 2.0]);
+    }
+}
@@ -0,0 +1,302 @@
+use crate::aabb;
+use crate::hittable;
+use crate::material;
+use crate::ray;
+use crate::sphere;
+use crate::vector3;
+use std::sync::{Arc, Mutex};
+
+const MAX_STEPS: usize = 512;
+const HIT_EPSILON: f64 = 1e-5;
+const NORMAL_EPSILON: f64 = 1e-5;
+
+/// A composable signed distance function. Negative inside, positive outside.
+#[derive(Clone)]
+pub enum SdfNode {
+    Sphere {
+        center: vector3::Point,
+        radius: f64,
+    },
+    /// Box with `half_extents`, its edges rounded off by `radius`.
+    RoundBox {
+        center: vector3::Point,
+        half_extents: vector3::Vec3,
+        radius: f64,
+    },
+    /// Torus lying in the XZ plane.
+    Torus {
+        center: vector3::Point,
+        major_radius: f64,
+        minor_radius: f64,
+    },
+    /// Mandelbulb fractal of the given power centered at the origin, fitting in a radius of ~1.2.
+    Mandelbulb {
+        power: f64,
+        iterations: usize,
+    },
+    Union(Box<SdfNode>, Box<SdfNode>),
+    Intersection(Box<SdfNode>, Box<SdfNode>),
+    Difference(Box<SdfNode>, Box<SdfNode>),
+    /// Union that blends the two shapes together over a distance of `k`.
+    SmoothUnion(Box<SdfNode>, Box<SdfNode>, f64),
+    Translate(Box<SdfNode>, vector3::Vec3),
+    Scale(Box<SdfNode>, f64),
+}
+
+impl SdfNode {
+    pub fn distance(&self, p: vector3::Point) -> f64 {
+        match self {
+            SdfNode::Sphere { center, radius } => (p - *center).length() - radius,
+            SdfNode::RoundBox {
+                center,
+                half_extents,
+                radius,
+            } => {
+                let q = p - *center;
+                let q = vector3::Vec3::new(
+                    q.x.abs() - half_extents.x + radius,
+                    q.y.abs() - half_extents.y + radius,
+                    q.z.abs() - half_extents.z + radius,
+                );
+                let outside = vector3::Vec3::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0)).length();
+                let inside = q.x.max(q.y).max(q.z).min(0.0);
+                outside + inside - radius
+            }
+            SdfNode::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => {
+                let q = p - *center;
+                let ring = (q.x * q.x + q.z * q.z).sqrt() - major_radius;
+                (ring * ring + q.y * q.y).sqrt() - minor_radius
+            }
+            SdfNode::Mandelbulb { power, iterations } => mandelbulb(p, *power, *iterations),
+            SdfNode::Union(a, b) => a.distance(p).min(b.distance(p)),
+            SdfNode::Intersection(a, b) => a.distance(p).max(b.distance(p)),
+            SdfNode::Difference(a, b) => a.distance(p).max(-b.distance(p)),
+            SdfNode::SmoothUnion(a, b, k) => {
+                let d1 = a.distance(p);
+                let d2 = b.distance(p);
+                let h = (0.5 + 0.5 * (d2 - d1) / k).clamp(0.0, 1.0);
+                d2 * (1.0 - h) + d1 * h - k * h * (1.0 - h)
+            }
+            SdfNode::Translate(a, offset) => a.distance(p - *offset),
+            SdfNode::Scale(a, s) => a.distance(p / *s) * s,
+        }
+    }
+
+    /// Outward normal, estimated from the gradient by central differences.
+    pub fn normal(&self, p: vector3::Point) -> vector3::Vec3 {
+        let e = NORMAL_EPSILON;
+        let dx = vector3::Vec3::new(e, 0.0, 0.0);
+        let dy = vector3::Vec3::new(0.0, e, 0.0);
+        let dz = vector3::Vec3::new(0.0, 0.0, e);
+        vector3::Vec3::new(
+            self.distance(p + dx) - self.distance(p - dx),
+            self.distance(p + dy) - self.distance(p - dy),
+            self.distance(p + dz) - self.distance(p - dz),
+        )
+        .unit_vector()
+    }
+}
+
+fn mandelbulb(p: vector3::Point, power: f64, iterations: usize) -> f64 {
+    let mut z = p;
+    let mut dr = 1.0;
+    let mut r = 0.0;
+    for _ in 0..iterations {
+        r = z.length();
+        if r == 0.0 {
+            // The angles below are undefined here. Zero would count as the surface,
+            // so give a short step that lets marching carry on past it.
+            return 1e-3;
+        }
+        if r > 2.0 {
+            break;
+        }
+        // Raise to `power` in spherical coordinates
+        let theta = (z.z / r).acos() * power;
+        let phi = z.y.atan2(z.x) * power;
+        let zr = r.powf(power);
+        dr = r.powf(power - 1.0) * power * dr + 1.0;
+        z = vector3::Vec3::new(
+            theta.sin() * phi.cos(),
+            phi.sin() * theta.sin(),
+            theta.cos(),
+        ) * zr
+            + p;
+    }
+    0.5 * r.ln() * r / dr
+}
+
+/// Object whose surface is the zero set of an `SdfNode`, found by sphere tracing.
+/// The tree must lie inside `bounds`; rays are only marched within it.
+#[derive(Clone)]
+pub struct Sdf {
+    root: SdfNode,
+    bounds: aabb::Aabb,
+    material: Arc<Mutex<material::Material>>,
+}
+
+impl Sdf {
+    pub fn new(root: SdfNode, bounds: aabb::Aabb, mat: Arc<Mutex<material::Material>>) -> Sdf {
+        Sdf {
+            root,
+            bounds,
+            material: mat,
+        }
+    }
+}
+
+impl hittable::Hittable for Sdf {
+    fn hit(&self, r: &ray::Ray, t_min: f64, t_max: f64) -> Option<hittable::HitRecord> {
+        let (t_enter, t_exit) = self.bounds.intersect(r, t_min, t_max)?;
+        let dir_length = r.dir.length();
+
+        let mut t = t_enter;
+        // A march starting on a surface, such as just past the last hit when
+        // `intervals` walks the ray, first steps clear of it so it isn't found again.
+        for _ in 0..MAX_STEPS {
+            let tolerance = HIT_EPSILON * t.abs().max(1.0);
+            if self.root.distance(r.at(t)).abs() >= tolerance {
+                break;
+            }
+            t += tolerance / dir_length;
+        }
+        if t > t_exit {
+            return None;
+        }
+        // March on |d| measured from whichever side of the surface we start on,
+        // so rays leaving the inside of a glass SDF find the surface too.
+        let side = self.root.distance(r.at(t)).signum();
+        let mut root = None;
+        for _ in 0..MAX_STEPS {
+            let d = side * self.root.distance(r.at(t));
+            if d < HIT_EPSILON * t.abs().max(1.0) {
+                root = Some(t);
+                break;
+            }
+            t += d / dir_length;
+            if t > t_exit {
+                break;
+            }
+        }
+        let root = root?;
+
+        let mut hit_record = hittable::HitRecord {
+            p: r.at(root),
+            t: root,
+            u: 0.0,
+            v: 0.0,
+            tangent: vector3::Vec3::new(0.0, 0.0, 0.0),
+            normal: vector3::Vec3::new(0.0, 0.0, 0.0),
+            front_face: false,
+            material: self.material.clone(),
+        };
+        let outward_normal = self.root.normal(hit_record.p);
+        hit_record.set_face_normal(r, &outward_normal);
+        // No natural parameterization, so map UVs from the normal's direction.
+        (hit_record.u, hit_record.v) = sphere::Sphere::get_sphere_uv(outward_normal);
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        Some(self.bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittable;
+    use crate::test_utils::mat;
+
+    fn bounds() -> aabb::Aabb {
+        aabb::Aabb::new(
+            vector3::Point::new(-2.0, -2.0, -2.0),
+            vector3::Point::new(2.0, 2.0, 2.0),
+        )
+    }
+
+    #[test]
+    fn traces_sphere() {
+        let sdf = Sdf::new(
+            SdfNode::Sphere {
+                center: vector3::Point::new(0.0, 0.0, 0.0),
+                radius: 1.0,
+            },
+            bounds(),
+            mat(),
+        );
+        let r = ray::Ray::new(
+            vector3::Point::new(0.0, 0.0, 5.0),
+            vector3::Vec3::new(0.0, 0.0, -2.0),
+        );
+        let rec = sdf.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-4);
+        assert!((rec.normal.z - 1.0).abs() < 1e-4);
+
+        // From inside, the far wall is found with the normal facing back at the ray.
+        let r = ray::Ray::new(
+            vector3::Point::new(0.0, 0.0, 0.0),
+            vector3::Vec3::new(1.0, 0.0, 0.0),
+        );
+        let rec = sdf.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 1.0).abs() < 1e-4);
+        assert!(!rec.front_face);
+    }
+
+    #[test]
+    fn difference_of_round_box() {
+        let node = SdfNode::Difference(
+            Box::new(SdfNode::RoundBox {
+                center: vector3::Point::new(0.0, 0.0, 0.0),
+                half_extents: vector3::Vec3::new(1.0, 1.0, 1.0),
+                radius: 0.1,
+            }),
+            Box::new(SdfNode::Sphere {
+                center: vector3::Point::new(0.0, 0.0, 1.0),
+                radius: 0.5,
+            }),
+        );
+        let sdf = Sdf::new(node, bounds(), mat());
+        // Straight into the spherical dent on the +z face.
+        let r = ray::Ray::new(
+            vector3::Point::new(0.0, 0.0, 5.0),
+            vector3::Vec3::new(0.0, 0.0, -1.0),
+        );
+        let rec = sdf.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 4.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn intervals_find_each_wall_once() {
+        let sdf = Sdf::new(
+            SdfNode::Sphere {
+                center: vector3::Point::new(0.0, 0.0, 0.0),
+                radius: 1.0,
+            },
+            bounds(),
+            mat(),
+        );
+        let r = ray::Ray::new(
+            vector3::Point::new(0.0, 0.0, 5.0),
+            vector3::Vec3::new(0.0, 0.0, -1.0),
+        );
+        let intervals = sdf.intervals(&r);
+        assert_eq!(intervals.len(), 1);
+        assert!((intervals[0].enter.t - 4.0).abs() < 1e-4);
+        assert!((intervals[0].exit.t - 6.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn mandelbulb_steps_past_origin() {
+        let bulb = SdfNode::Mandelbulb {
+            power: 8.0,
+            iterations: 8,
+        };
+        let d = bulb.distance(vector3::Point::new(0.0, 0.0, 0.0));
+        assert!(d > HIT_EPSILON && d < 0.01, "d = {}", d);
+    }
+}
@@ -0,0 +1,317 @@
+// Animated previews of a frame sequence, as GIF or APNG
+use std::fs::File;
+use std::io;
+use std::io::Write;
+
+/// How colours between palette entries are approximated in a GIF.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Dither {
+    /// Nearest palette colour. Flat, but bands on gradients
+    None,
+    /// Floyd-Steinberg error diffusion
+    #[default]
+    FloydSteinberg,
+}
+
+/// Colours shared by every frame of a GIF, so they don't flicker between frames.
+pub struct Palette {
+    colors: Vec<[u8; 3]>,
+}
+
+impl Palette {
+    /// Splits the colours used across `frames` into at most `max_colors` groups,
+    /// each time halving the group with the widest spread along that channel,
+    /// and takes the average of each.
+    pub fn median_cut(frames: &[image::RgbImage], max_colors: usize) -> Palette {
+        // A few hundred thousand pixels are plenty to find the colours
+        let total: usize = frames.iter().map(|f| f.len() / 3).sum();
+        let stride = (total / 200_000).max(1);
+        let mut pixels: Vec<[u8; 3]> = frames
+            .iter()
+            .flat_map(|f| f.chunks(3))
+            .step_by(stride)
+            .map(|p| [p[0], p[1], p[2]])
+            .collect();
+        if pixels.is_empty() {
+            return Palette {
+                colors: vec![[0, 0, 0]],
+            };
+        }
+
+        let spread = |group: &[[u8; 3]]| {
+            (0..3)
+                .map(|c| {
+                    let lo = group.iter().map(|p| p[c]).min().unwrap_or(0);
+                    let hi = group.iter().map(|p| p[c]).max().unwrap_or(0);
+                    (hi - lo, c)
+                })
+                .max()
+                .unwrap_or((0, 0))
+        };
+        let mut groups: Vec<&mut [[u8; 3]]> = vec![&mut pixels[..]];
+        while groups.len() < max_colors {
+            let widest = groups
+                .iter()
+                .enumerate()
+                .map(|(i, g)| (spread(g), i))
+                .max_by_key(|&((range, _), _)| range);
+            let ((range, channel), i) = match widest {
+                Some(w) => w,
+                None => break,
+            };
+            if range == 0 {
+                break;
+            }
+            let group = groups.swap_remove(i);
+            group.sort_unstable_by_key(|p| p[channel]);
+            let (below, above) = group.split_at_mut(group.len() / 2);
+            groups.push(below);
+            groups.push(above);
+        }
+
+        let colors = groups
+            .iter()
+            .map(|g| {
+                let mut sum = [0u64; 3];
+                for p in g.iter() {
+                    for c in 0..3 {
+                        sum[c] += p[c] as u64;
+                    }
+                }
+                let n = g.len() as u64;
+                [
+                    ((sum[0] + n / 2) / n) as u8,
+                    ((sum[1] + n / 2) / n) as u8,
+                    ((sum[2] + n / 2) / n) as u8,
+                ]
+            })
+            .collect();
+        Palette { colors }
+    }
+
+    pub fn colors(&self) -> &[[u8; 3]] {
+        &self.colors
+    }
+
+    /// Index of the palette colour closest to `c`.
+    pub fn nearest(&self, c: [f64; 3]) -> usize {
+        let distance = |p: &[u8; 3]| (0..3).map(|i| (p[i] as f64 - c[i]).powi(2)).sum::<f64>();
+        (0..self.colors.len())
+            .min_by(|&a, &b| distance(&self.colors[a]).total_cmp(&distance(&self.colors[b])))
+            .unwrap_or(0)
+    }
+
+    /// Palette index of every pixel of `frame`, row by row from the top.
+    pub fn map(&self, frame: &image::RgbImage, dither: Dither) -> Vec<u8> {
+        let (width, height) = (frame.width() as usize, frame.height() as usize);
+        let mut values: Vec<f64> = frame.iter().map(|&v| v as f64).collect();
+        let mut indices = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let at = 3 * (y * width + x);
+                let wanted = [values[at], values[at + 1], values[at + 2]];
+                let index = self.nearest(wanted);
+                indices.push(index as u8);
+                if dither == Dither::None {
+                    continue;
+                }
+                let got = self.colors[index];
+                for (dx, dy, share) in [
+                    (1, 0, 7.0 / 16.0),
+                    (-1, 1, 3.0 / 16.0),
+                    (0, 1, 5.0 / 16.0),
+                    (1, 1, 1.0 / 16.0),
+                ] {
+                    let (nx, ny) = (x as isize + dx, y + dy);
+                    if nx < 0 || nx as usize >= width || ny >= height {
+                        continue;
+                    }
+                    let to = 3 * (ny * width + nx as usize);
+                    for c in 0..3 {
+                        values[to + c] += (wanted[c] - got[c] as f64) * share;
+                    }
+                }
+            }
+        }
+        indices
+    }
+}
+
+/// Writes `frames` as a looping GIF shown at `fps`, quantized to one shared
+/// 256-colour palette.
+pub fn write_gif(
+    path: &str,
+    frames: &[image::RgbImage],
+    fps: f64,
+    dither: Dither,
+) -> io::Result<()> {
+    let first = match frames.first() {
+        Some(f) => f,
+        None => return Ok(()),
+    };
+    let palette = Palette::median_cut(frames, 256);
+    let table: Vec<u8> = palette.colors().iter().flatten().copied().collect();
+    let (width, height) = (first.width() as u16, first.height() as u16);
+    let mut encoder = gif::Encoder::new(File::create(path)?, width, height, &table)?;
+    encoder.write_extension(gif::ExtensionData::Repetitions(gif::Repeat::Infinite))?;
+    for f in frames {
+        let frame = gif::Frame {
+            // Hundredths of a second
+            delay: (100.0 / fps).round() as u16,
+            width,
+            height,
+            buffer: palette.map(f, dither).into(),
+            ..gif::Frame::default()
+        };
+        encoder.write_frame(&frame)?;
+    }
+    Ok(())
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in bytes {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn write_chunk<W: Write>(w: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    w.write_all(&(data.len() as u32).to_be_bytes())?;
+    let mut body = kind.to_vec();
+    body.extend_from_slice(data);
+    w.write_all(&body)?;
+    w.write_all(&crc32(&body).to_be_bytes())
+}
+
+/// Writes `frames` as a looping animated PNG shown at `fps`, in full colour.
+/// Viewers without APNG support show the first frame.
+pub fn write_apng(path: &str, frames: &[image::RgbImage], fps: f64) -> io::Result<()> {
+    let first = match frames.first() {
+        Some(f) => f,
+        None => return Ok(()),
+    };
+    let (width, height) = (first.width(), first.height());
+    let mut w = io::BufWriter::new(File::create(path)?);
+    w.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8 bit RGB, deflate, adaptive filtering, not interlaced
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+    write_chunk(&mut w, b"IHDR", &header)?;
+
+    let mut control = Vec::new();
+    control.extend_from_slice(&(frames.len() as u32).to_be_bytes());
+    // Loop forever
+    control.extend_from_slice(&0u32.to_be_bytes());
+    write_chunk(&mut w, b"acTL", &control)?;
+
+    // fcTL and fdAT chunks share one running sequence number
+    let mut sequence = 0u32;
+    let delay = (1000.0 / fps).round() as u16;
+    for (i, f) in frames.iter().enumerate() {
+        let mut frame_control = Vec::new();
+        frame_control.extend_from_slice(&sequence.to_be_bytes());
+        frame_control.extend_from_slice(&width.to_be_bytes());
+        frame_control.extend_from_slice(&height.to_be_bytes());
+        frame_control.extend_from_slice(&[0; 8]);
+        // Milliseconds, then replace the whole canvas each frame
+        frame_control.extend_from_slice(&delay.to_be_bytes());
+        frame_control.extend_from_slice(&1000u16.to_be_bytes());
+        frame_control.extend_from_slice(&[0, 0]);
+        write_chunk(&mut w, b"fcTL", &frame_control)?;
+        sequence += 1;
+
+        // Each row starts with its filter type, none
+        let row = 3 * width as usize;
+        let mut raw = Vec::with_capacity((row + 1) * height as usize);
+        for line in f.chunks(row) {
+            raw.push(0);
+            raw.extend_from_slice(line);
+        }
+        let compressed = deflate::deflate_bytes_zlib(&raw);
+        if i == 0 {
+            write_chunk(&mut w, b"IDAT", &compressed)?;
+        } else {
+            let mut data = sequence.to_be_bytes().to_vec();
+            data.extend_from_slice(&compressed);
+            write_chunk(&mut w, b"fdAT", &data)?;
+            sequence += 1;
+        }
+    }
+    write_chunk(&mut w, b"IEND", &[])?;
+    w.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ramp(shift: u8) -> image::RgbImage {
+        image::ImageBuffer::from_fn(16, 8, |x, y| {
+            image::Rgb([(x * 16) as u8, (y * 32) as u8, shift])
+        })
+    }
+
+    #[test]
+    fn palette_keeps_few_colours_exactly() {
+        let frame = image::ImageBuffer::from_fn(4, 4, |x, _| {
+            if x < 2 {
+                image::Rgb([255, 0, 0])
+            } else {
+                image::Rgb([0, 0, 255])
+            }
+        });
+        let palette = Palette::median_cut(std::slice::from_ref(&frame), 256);
+        assert_eq!(palette.colors().len(), 2);
+        let indices = palette.map(&frame, Dither::FloydSteinberg);
+        assert_eq!(palette.colors()[indices[0] as usize], [255, 0, 0]);
+        assert_eq!(palette.colors()[indices[3] as usize], [0, 0, 255]);
+    }
+
+    #[test]
+    fn dithering_keeps_the_average_colour() {
+        let grey: image::RgbImage =
+            image::ImageBuffer::from_pixel(32, 32, image::Rgb([100, 100, 100]));
+        let palette = Palette {
+            colors: vec![[0, 0, 0], [255, 255, 255]],
+        };
+        let mean = |dither| {
+            let indices = palette.map(&grey, dither);
+            indices
+                .iter()
+                .map(|&i| palette.colors()[i as usize][0] as f64)
+                .sum::<f64>()
+                / indices.len() as f64
+        };
+        assert_eq!(mean(Dither::None), 0.0);
+        assert!((mean(Dither::FloydSteinberg) - 100.0).abs() < 4.0);
+    }
+
+    #[test]
+    fn previews_open_as_their_first_frame() {
+        let frames = vec![ramp(0), ramp(200)];
+        let dir = std::env::temp_dir();
+        let apng = dir.join("sequence_encoder_test.apng");
+        let gif = dir.join("sequence_encoder_test.gif");
+        write_apng(apng.to_str().unwrap(), &frames, 24.0).unwrap();
+        write_gif(gif.to_str().unwrap(), &frames, 24.0, Dither::None).unwrap();
+
+        let file = io::BufReader::new(File::open(&apng).unwrap());
+        let decoded = image::load(file, image::PNG).unwrap().to_rgb();
+        assert_eq!(decoded.into_raw(), frames[0].clone().into_raw());
+        let decoded = image::open(&gif).unwrap().to_rgb();
+        assert_eq!((decoded.width(), decoded.height()), (16, 8));
+        std::fs::remove_file(apng).unwrap();
+        std::fs::remove_file(gif).unwrap();
+    }
+}
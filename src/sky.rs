@@ -0,0 +1,245 @@
+use crate::environment;
+use crate::light;
+use crate::texture;
+use crate::vector3;
+use std::f64::consts::PI;
+
+/// Illuminance of sunlight above the atmosphere, in kilolux.
+const SOLAR_ILLUMINANCE: f64 = 127.5;
+
+/// Angular diameter of the sun seen from the earth, in degrees.
+const SUN_DIAMETER: f64 = 0.53;
+
+/// Coefficients of the Perez sky luminance distribution.
+#[derive(Copy, Clone)]
+struct Perez {
+    a: f64,
+    b: f64,
+    c: f64,
+    d: f64,
+    e: f64,
+}
+
+impl Perez {
+    /// Relative brightness at zenith angle `theta` and angle `gamma` from the sun.
+    fn f(&self, theta: f64, gamma: f64) -> f64 {
+        let cos_gamma = gamma.cos();
+        (1.0 + self.a * (self.b / theta.cos().max(0.01)).exp())
+            * (1.0 + self.c * (self.d * gamma).exp() + self.e * cos_gamma * cos_gamma)
+    }
+}
+
+/// Preetham et al. 1999 clear-sky daylight, with a sun to match. The sun sits at
+/// `elevation` degrees above the horizon and `azimuth` degrees around the up axis
+/// from +x toward +z. `turbidity` runs from about 2 (very clear) to 10 (hazy).
+/// Below the horizon is a diffuse ground of `ground_albedo` lit by sun and sky.
+///
+/// The model gives luminance in kcd/m² and illuminance in klux; `intensity`
+/// scales both into the renderer's units.
+pub struct PhysicalSky {
+    to_sun: vector3::Vec3,
+    theta_sun: f64,
+    turbidity: f64,
+    ground_albedo: vector3::Color,
+    intensity: f64,
+    perez: [Perez; 3],
+    /// Luminance and chromaticity straight up
+    zenith: [f64; 3],
+    /// Radiance of the ground, the same in every direction below the horizon
+    ground: vector3::Color,
+}
+
+impl PhysicalSky {
+    pub fn new(
+        elevation: f64,
+        azimuth: f64,
+        turbidity: f64,
+        ground_albedo: vector3::Color,
+        intensity: f64,
+    ) -> PhysicalSky {
+        let (el, az) = (elevation.to_radians(), azimuth.to_radians());
+        let to_sun = vector3::Vec3::new(el.cos() * az.cos(), el.sin(), el.cos() * az.sin());
+        let t = turbidity;
+        // The fit is only valid with the sun up, so hold it just above the horizon.
+        let theta_sun = (0.5 * PI - el).clamp(0.0, 0.5 * PI - 0.01);
+
+        let perez = [
+            Perez {
+                a: 0.1787 * t - 1.4630,
+                b: -0.3554 * t + 0.4275,
+                c: -0.0227 * t + 5.3251,
+                d: 0.1206 * t - 2.5771,
+                e: -0.0670 * t + 0.3703,
+            },
+            Perez {
+                a: -0.0193 * t - 0.2592,
+                b: -0.0665 * t + 0.0008,
+                c: -0.0004 * t + 0.2125,
+                d: -0.0641 * t - 0.8989,
+                e: -0.0033 * t + 0.0452,
+            },
+            Perez {
+                a: -0.0167 * t - 0.2608,
+                b: -0.0950 * t + 0.0092,
+                c: -0.0079 * t + 0.2102,
+                d: -0.0441 * t - 1.6537,
+                e: -0.0109 * t + 0.0529,
+            },
+        ];
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_sun);
+        let y_zenith = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+        let (s, s2, s3) = (theta_sun, theta_sun.powi(2), theta_sun.powi(3));
+        let x_zenith = t * t * (0.00166 * s3 - 0.00375 * s2 + 0.00209 * s)
+            + t * (-0.02903 * s3 + 0.06377 * s2 - 0.03202 * s + 0.00394)
+            + (0.11693 * s3 - 0.21196 * s2 + 0.06052 * s + 0.25886);
+        let yc_zenith = t * t * (0.00275 * s3 - 0.00610 * s2 + 0.00317 * s)
+            + t * (-0.04214 * s3 + 0.08970 * s2 - 0.04153 * s + 0.00516)
+            + (0.15346 * s3 - 0.26756 * s2 + 0.06670 * s + 0.26688);
+
+        let mut sky = PhysicalSky {
+            to_sun,
+            theta_sun,
+            turbidity,
+            ground_albedo,
+            intensity,
+            perez,
+            zenith: [y_zenith, x_zenith, yc_zenith],
+            ground: vector3::Color::new(0.0, 0.0, 0.0),
+        };
+        sky.ground = sky.ground_radiance();
+        sky
+    }
+
+    /// Sky luminance toward the unit direction `w` above the horizon, before `intensity`.
+    fn sky(&self, w: vector3::Vec3) -> vector3::Color {
+        let theta = w.y.clamp(0.0, 1.0).acos();
+        let gamma = vector3::dot(w, self.to_sun).clamp(-1.0, 1.0).acos();
+        let [y, x, yc] = [0, 1, 2].map(|i| {
+            self.zenith[i] * self.perez[i].f(theta, gamma) / self.perez[i].f(0.0, self.theta_sun)
+        });
+        if yc <= 0.0 {
+            return vector3::Color::new(0.0, 0.0, 0.0);
+        }
+        let c = vector3::Color::from_xyz(x * y / yc, y, (1.0 - x - yc) * y / yc);
+        vector3::Color::new(c.x.max(0.0), c.y.max(0.0), c.z.max(0.0))
+    }
+
+    /// Fraction of each channel of sunlight that makes it through the atmosphere
+    /// (Rayleigh and aerosol extinction at 650, 550 and 450 nm).
+    fn sun_transmittance(&self) -> vector3::Color {
+        if self.to_sun.y <= 0.0 {
+            return vector3::Color::new(0.0, 0.0, 0.0);
+        }
+        let theta_deg = self.theta_sun.to_degrees();
+        let air_mass = 1.0 / (self.theta_sun.cos() + 0.15 * (93.885 - theta_deg).powf(-1.253));
+        let beta = 0.04608 * self.turbidity - 0.04586;
+        let tau = |lambda: f64| {
+            (-0.008735 * lambda.powf(-4.08) * air_mass).exp()
+                * (-beta * lambda.powf(-1.3) * air_mass).exp()
+        };
+        vector3::Color::new(tau(0.65), tau(0.55), tau(0.45))
+    }
+
+    /// Sunlight falling on a surface facing the sun, before `intensity`.
+    fn sun_illuminance(&self) -> vector3::Color {
+        self.sun_transmittance() * SOLAR_ILLUMINANCE
+    }
+
+    /// Radiance of the diffuse ground, lit by the sun and the upper hemisphere.
+    fn ground_radiance(&self) -> vector3::Color {
+        let n = 64;
+        let mut sky = vector3::Color::new(0.0, 0.0, 0.0);
+        for i in 0..n {
+            for j in 0..n {
+                // Cosine weighted over the upper hemisphere, so the sum estimates E / PI
+                let u = [(i as f64 + 0.5) / n as f64, (j as f64 + 0.5) / n as f64];
+                let d = vector3::Vec3::cosine_direction(u[0], u[1]);
+                sky = sky + self.sky(vector3::Vec3::new(d.x, d.z, d.y));
+            }
+        }
+        let sky = sky / (n * n) as f64;
+        let sun = self.sun_illuminance() * (self.to_sun.y.max(0.0) / PI);
+        self.ground_albedo * (sky + sun)
+    }
+
+    /// Radiance toward `w`, excluding the sun disk.
+    pub fn radiance(&self, w: vector3::Vec3) -> vector3::Color {
+        let w = w.unit_vector();
+        if w.y < 0.0 {
+            return self.ground * self.intensity;
+        }
+        self.sky(w) * self.intensity
+    }
+
+    /// The sky as an importance-sampled environment light, tabulated at
+    /// `width` by `height` texels.
+    pub fn environment(&self, width: usize, height: usize) -> environment::EnvironmentLight {
+        let mut data = Vec::with_capacity(width * height);
+        for j in 0..height {
+            for i in 0..width {
+                let w = environment::map_direction(
+                    (i as f64 + 0.5) / width as f64,
+                    (j as f64 + 0.5) / height as f64,
+                );
+                data.push(if w.y < 0.0 { self.ground } else { self.sky(w) });
+            }
+        }
+        let map = texture::ImageTexture::new(width, height, data, texture::WrapMode::Repeat);
+        environment::EnvironmentLight::new(map, self.intensity, 0.0)
+    }
+
+    /// The sun as a small disk light, dark when it has set.
+    pub fn sun(&self) -> light::DirectionalLight {
+        light::DirectionalLight::new(
+            self.to_sun,
+            self.sun_illuminance() * self.intensity,
+            SUN_DIAMETER,
+        )
+    }
+
+    /// Adds the sky and the sun to `lights`.
+    pub fn add_to(&self, lights: &mut light::LightList) {
+        lights.add(light::Light::Environment(self.environment(256, 128)));
+        if self.to_sun.y > 0.0 {
+            lights.add(light::Light::Directional(self.sun()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sky(elevation: f64) -> PhysicalSky {
+        PhysicalSky::new(elevation, 0.0, 3.0, vector3::Color::new(0.3, 0.3, 0.3), 1.0)
+    }
+
+    #[test]
+    fn clear_sky_zenith_is_plausible() {
+        // A clear midday zenith is a few kcd/m², blue, and brighter than a low sun's.
+        let noon = sky(70.0);
+        let zenith = noon.radiance(vector3::Vec3::new(0.0, 1.0, 0.0));
+        assert!(zenith.luminance() > 2.0 && zenith.luminance() < 20.0);
+        assert!(zenith.z > zenith.x);
+        let evening = sky(10.0).radiance(vector3::Vec3::new(0.0, 1.0, 0.0));
+        assert!(evening.luminance() < zenith.luminance());
+    }
+
+    #[test]
+    fn brighter_toward_the_sun() {
+        let s = sky(30.0);
+        let toward = vector3::Vec3::new(1.0, 0.6, 0.0);
+        let away = vector3::Vec3::new(-1.0, 0.6, 0.0);
+        assert!(s.radiance(toward).luminance() > s.radiance(away).luminance());
+    }
+
+    #[test]
+    fn sun_reddens_and_dims_toward_the_horizon() {
+        let high = sky(60.0).sun_transmittance();
+        let low = sky(5.0).sun_transmittance();
+        assert!(low.luminance() < high.luminance());
+        assert!(low.x / low.z > high.x / high.z);
+        assert_eq!(sky(-5.0).sun_transmittance().luminance(), 0.0);
+    }
+}
@@ -0,0 +1,506 @@
+use crate::vector3;
+use std::ops;
+use std::sync::OnceLock;
+
+pub const LAMBDA_MIN: f64 = 360.0;
+pub const LAMBDA_MAX: f64 = 830.0;
+/// Wavelengths carried by each path
+pub const N_SAMPLES: usize = 4;
+
+/// Wavelength grid used to integrate against the colour matching functions, in nm.
+const STEP: f64 = 5.0;
+/// Table cells along each axis of the RGB to spectrum table
+const RES: usize = 16;
+
+/// One lobe of the piecewise Gaussian fit, with different widths either side of
+/// its peak.
+fn lobe(lambda: f64, mu: f64, below: f64, above: f64) -> f64 {
+    let sigma = if lambda < mu { below } else { above };
+    let t = (lambda - mu) / sigma;
+    (-0.5 * t * t).exp()
+}
+
+/// CIE 1931 2° colour matching functions at `lambda`, from the multi-lobe fit of
+/// Wyman, Sloan and Shirley, "Simple Analytic Approximations to the CIE XYZ
+/// Color Matching Functions" (2013).
+pub fn cie_xyz(lambda: f64) -> [f64; 3] {
+    [
+        1.056 * lobe(lambda, 599.8, 37.9, 31.0) + 0.362 * lobe(lambda, 442.0, 16.0, 26.7)
+            - 0.065 * lobe(lambda, 501.1, 20.4, 26.2),
+        0.821 * lobe(lambda, 568.8, 46.9, 40.5) + 0.286 * lobe(lambda, 530.9, 16.3, 31.1),
+        1.217 * lobe(lambda, 437.0, 11.8, 36.0) + 0.681 * lobe(lambda, 459.0, 26.0, 13.8),
+    ]
+}
+
+/// Integration weights shared by everything that turns spectra into colour.
+struct Tables {
+    /// Integral of the y matching function, so a flat spectrum of 1 has Y = 1
+    y_integral: f64,
+    /// Linear sRGB of a flat spectrum of 1 before white balancing
+    white: vector3::Color,
+    /// Balanced RGB of each grid wavelength, times its share of the integral
+    weights: Vec<(f64, [f64; 3])>,
+}
+
+fn tables() -> &'static Tables {
+    static TABLES: OnceLock<Tables> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        let n = ((LAMBDA_MAX - LAMBDA_MIN) / STEP) as usize;
+        let grid: Vec<f64> = (0..=n).map(|i| LAMBDA_MIN + i as f64 * STEP).collect();
+        let y_integral: f64 = grid.iter().map(|&l| cie_xyz(l)[1] * STEP).sum();
+        let rgb = |l: f64| {
+            let [x, y, z] = cie_xyz(l);
+            vector3::Color::from_xyz(x, y, z) * (STEP / y_integral)
+        };
+        let white = grid
+            .iter()
+            .fold(vector3::Color::new(0.0, 0.0, 0.0), |acc, &l| acc + rgb(l));
+        let weights = grid
+            .iter()
+            .map(|&l| {
+                let c = rgb(l) / white;
+                (l, [c.x, c.y, c.z])
+            })
+            .collect();
+        Tables {
+            y_integral,
+            white,
+            weights,
+        }
+    })
+}
+
+/// The wavelengths a path carries, in nm, with the density each was picked with.
+#[derive(Copy, Clone)]
+pub struct SampledWavelengths {
+    lambda: [f64; N_SAMPLES],
+    pdf: [f64; N_SAMPLES],
+}
+
+impl SampledWavelengths {
+    /// A hero wavelength picked with `u` in [0, 1), and the rest spread evenly
+    /// after it around the range. Each is drawn in proportion to how visible it
+    /// is, so the eye's blind ends of the range cost few samples.
+    pub fn sample_visible(u: f64) -> SampledWavelengths {
+        let mut lambda = [0.0; N_SAMPLES];
+        let mut pdf = [0.0; N_SAMPLES];
+        for i in 0..N_SAMPLES {
+            let up = (u + i as f64 / N_SAMPLES as f64).fract();
+            lambda[i] = 538.0 - 138.888889 * (0.85691062 - 1.82750197 * up).atanh();
+            pdf[i] = visible_pdf(lambda[i]);
+        }
+        SampledWavelengths { lambda, pdf }
+    }
+
+    pub fn lambda(&self, i: usize) -> f64 {
+        self.lambda[i]
+    }
+
+    pub fn pdf(&self, i: usize) -> f64 {
+        self.pdf[i]
+    }
+
+    /// Keeps only the hero wavelength, for when a path splits by wavelength and
+    /// the others can no longer follow it.
+    pub fn terminate_secondary(&mut self) {
+        if self.secondary_terminated() {
+            return;
+        }
+        for i in 1..N_SAMPLES {
+            self.pdf[i] = 0.0;
+        }
+        self.pdf[0] /= N_SAMPLES as f64;
+    }
+
+    pub fn secondary_terminated(&self) -> bool {
+        self.pdf[1..].iter().all(|&p| p == 0.0)
+    }
+}
+
+/// Density `sample_visible` picks `lambda` with, per nm.
+pub fn visible_pdf(lambda: f64) -> f64 {
+    if !(LAMBDA_MIN..=LAMBDA_MAX).contains(&lambda) {
+        return 0.0;
+    }
+    0.0039398042 / (0.0072 * (lambda - 538.0)).cosh().powi(2)
+}
+
+/// A spectrum's values at a path's `SampledWavelengths`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SampledSpectrum(pub [f64; N_SAMPLES]);
+
+impl SampledSpectrum {
+    pub fn constant(c: f64) -> SampledSpectrum {
+        SampledSpectrum([c; N_SAMPLES])
+    }
+
+    /// An RGB reflectance or radiance as a smooth spectrum, seen at `lambda`.
+    /// Colours with a channel above 1 are treated as unbounded radiance.
+    pub fn from_rgb(c: vector3::Color, lambda: &SampledWavelengths) -> SampledSpectrum {
+        let m = c.x.max(c.y).max(c.z);
+        if m <= 0.0 {
+            return SampledSpectrum::constant(0.0);
+        }
+        let (scale, rgb) = if m <= 1.0 {
+            (1.0, c)
+        } else {
+            // Halving keeps the shape well inside what a reflectance can reach
+            (2.0 * m, c / (2.0 * m))
+        };
+        let poly = RgbSigmoidPolynomial::from_rgb(rgb);
+        let mut s = [0.0; N_SAMPLES];
+        for (i, v) in s.iter_mut().enumerate() {
+            *v = scale * poly.eval(lambda.lambda[i]);
+        }
+        SampledSpectrum(s)
+    }
+
+    pub fn max_value(&self) -> f64 {
+        self.0.iter().copied().fold(0.0, f64::max)
+    }
+
+    pub fn is_black(&self) -> bool {
+        self.0.iter().all(|&v| v == 0.0)
+    }
+
+    /// Monte Carlo estimate of the CIE XYZ of the spectrum, with Y = 1 for a
+    /// flat spectrum of 1.
+    pub fn to_xyz(&self, lambda: &SampledWavelengths) -> [f64; 3] {
+        let mut xyz = [0.0; 3];
+        for i in 0..N_SAMPLES {
+            if lambda.pdf[i] == 0.0 {
+                continue;
+            }
+            let cmf = cie_xyz(lambda.lambda[i]);
+            for c in 0..3 {
+                xyz[c] += cmf[c] * self.0[i] / lambda.pdf[i];
+            }
+        }
+        let norm = N_SAMPLES as f64 * tables().y_integral;
+        [xyz[0] / norm, xyz[1] / norm, xyz[2] / norm]
+    }
+
+    /// Linear sRGB through CIE XYZ, balanced so a flat spectrum comes out as the
+    /// same white (1, 1, 1) as in RGB rendering.
+    pub fn to_rgb(&self, lambda: &SampledWavelengths) -> vector3::Color {
+        let [x, y, z] = self.to_xyz(lambda);
+        vector3::Color::from_xyz(x, y, z) / tables().white
+    }
+}
+
+impl ops::Add for SampledSpectrum {
+    type Output = SampledSpectrum;
+    fn add(self, o: SampledSpectrum) -> SampledSpectrum {
+        let mut s = self.0;
+        for (a, b) in s.iter_mut().zip(o.0) {
+            *a += b;
+        }
+        SampledSpectrum(s)
+    }
+}
+
+impl ops::Mul for SampledSpectrum {
+    type Output = SampledSpectrum;
+    fn mul(self, o: SampledSpectrum) -> SampledSpectrum {
+        let mut s = self.0;
+        for (a, b) in s.iter_mut().zip(o.0) {
+            *a *= b;
+        }
+        SampledSpectrum(s)
+    }
+}
+
+impl ops::Mul<f64> for SampledSpectrum {
+    type Output = SampledSpectrum;
+    fn mul(self, k: f64) -> SampledSpectrum {
+        SampledSpectrum(self.0.map(|v| v * k))
+    }
+}
+
+impl ops::Div<f64> for SampledSpectrum {
+    type Output = SampledSpectrum;
+    fn div(self, k: f64) -> SampledSpectrum {
+        SampledSpectrum(self.0.map(|v| v / k))
+    }
+}
+
+fn sigmoid(x: f64) -> f64 {
+    if x.is_infinite() {
+        return if x > 0.0 { 1.0 } else { 0.0 };
+    }
+    0.5 + x / (2.0 * (1.0 + x * x).sqrt())
+}
+
+/// Wavelength rescaled to [0, 1] over the range, which keeps the fitted
+/// coefficients a reasonable size.
+fn unit_lambda(lambda: f64) -> f64 {
+    (lambda - LAMBDA_MIN) / (LAMBDA_MAX - LAMBDA_MIN)
+}
+
+/// Jakob and Hanika's smooth reflectance spectra, "A Low-Dimensional Function
+/// Space for Efficient Spectral Upsampling" (2019): a quadratic in wavelength
+/// squashed into [0, 1] by a sigmoid.
+#[derive(Copy, Clone, Debug)]
+pub struct RgbSigmoidPolynomial {
+    c: [f64; 3],
+}
+
+impl RgbSigmoidPolynomial {
+    pub fn eval(&self, lambda: f64) -> f64 {
+        let t = unit_lambda(lambda);
+        sigmoid((self.c[0] * t + self.c[1]) * t + self.c[2])
+    }
+
+    /// The spectrum for an RGB reflectance with channels in [0, 1], interpolated
+    /// from a table of fits built the first time it's needed.
+    pub fn from_rgb(rgb: vector3::Color) -> RgbSigmoidPolynomial {
+        let rgb = [rgb.x, rgb.y, rgb.z].map(|v| v.clamp(0.0, 1.0));
+        if rgb[0] == rgb[1] && rgb[1] == rgb[2] {
+            // Flat, with the sigmoid of the constant giving the value
+            let v = rgb[0];
+            let c = if v <= 0.0 {
+                f64::NEG_INFINITY
+            } else if v >= 1.0 {
+                f64::INFINITY
+            } else {
+                (v - 0.5) / (v * (1.0 - v)).sqrt()
+            };
+            return RgbSigmoidPolynomial { c: [0.0, 0.0, c] };
+        }
+
+        let table = RgbToSpectrumTable::get();
+        let l = if rgb[0] >= rgb[1] && rgb[0] >= rgb[2] {
+            0
+        } else if rgb[1] >= rgb[2] {
+            1
+        } else {
+            2
+        };
+        let z = rgb[l];
+        let x = rgb[(l + 1) % 3] * (RES - 1) as f64 / z;
+        let y = rgb[(l + 2) % 3] * (RES - 1) as f64 / z;
+        let xi = (x as usize).min(RES - 2);
+        let yi = (y as usize).min(RES - 2);
+        let zi = table.scale.partition_point(|&s| s <= z).clamp(1, RES - 1) - 1;
+        let (dx, dy) = (x - xi as f64, y - yi as f64);
+        let dz = (z - table.scale[zi]) / (table.scale[zi + 1] - table.scale[zi]);
+
+        let mut c = [0.0; 3];
+        for (k, ck) in c.iter_mut().enumerate() {
+            let at = |i: usize, j: usize, m: usize| table.coefficients[l][j][i][m][k];
+            let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+            let plane = |m: usize| {
+                lerp(
+                    lerp(at(xi, yi, m), at(xi + 1, yi, m), dx),
+                    lerp(at(xi, yi + 1, m), at(xi + 1, yi + 1, m), dx),
+                    dy,
+                )
+            };
+            *ck = lerp(plane(zi), plane(zi + 1), dz);
+        }
+        RgbSigmoidPolynomial { c }
+    }
+
+    /// Fits coefficients whose spectrum has colour `target`, by Gauss-Newton
+    /// from `start`.
+    fn fit(target: [f64; 3], start: [f64; 3]) -> [f64; 3] {
+        let weights = &tables().weights;
+        let mut c = start;
+        for _ in 0..30 {
+            let mut residual = [-target[0], -target[1], -target[2]];
+            let mut jacobian = [[0.0; 3]; 3];
+            for &(lambda, w) in weights {
+                let t = unit_lambda(lambda);
+                let x = (c[0] * t + c[1]) * t + c[2];
+                let s = sigmoid(x);
+                let ds = 0.5 / (1.0 + x * x).powf(1.5);
+                let dc = [t * t * ds, t * ds, ds];
+                for ch in 0..3 {
+                    residual[ch] += w[ch] * s;
+                    for k in 0..3 {
+                        jacobian[ch][k] += w[ch] * dc[k];
+                    }
+                }
+            }
+            if residual.iter().map(|r| r * r).sum::<f64>() < 1e-12 {
+                break;
+            }
+            let step = match solve3(jacobian, residual) {
+                Some(step) => step,
+                None => break,
+            };
+            // Long steps overshoot where the sigmoid saturates
+            let length = step.iter().map(|s| s * s).sum::<f64>().sqrt();
+            let damp = if length > 10.0 { 10.0 / length } else { 1.0 };
+            for k in 0..3 {
+                c[k] -= step[k] * damp;
+            }
+        }
+        c
+    }
+}
+
+/// Solves `a x = b` by Cramer's rule, or `None` if `a` is singular.
+fn solve3(a: [[f64; 3]; 3], b: [f64; 3]) -> Option<[f64; 3]> {
+    let det = |m: [[f64; 3]; 3]| {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    };
+    let d = det(a);
+    if d.abs() < 1e-300 {
+        return None;
+    }
+    let mut x = [0.0; 3];
+    for (k, xk) in x.iter_mut().enumerate() {
+        let mut m = a;
+        for row in 0..3 {
+            m[row][k] = b[row];
+        }
+        *xk = det(m) / d;
+    }
+    Some(x)
+}
+
+/// Fitted coefficients over the RGB cube, indexed like pbrt's: by which channel
+/// is largest, the other two divided by it, and that channel's value `z`.
+struct RgbToSpectrumTable {
+    /// The values of `z` fitted at, bunched toward black and full brightness
+    scale: [f64; RES],
+    coefficients: Vec<[[[[f64; 3]; RES]; RES]; RES]>,
+}
+
+impl RgbToSpectrumTable {
+    fn get() -> &'static RgbToSpectrumTable {
+        static TABLE: OnceLock<RgbToSpectrumTable> = OnceLock::new();
+        TABLE.get_or_init(RgbToSpectrumTable::build)
+    }
+
+    fn build() -> RgbToSpectrumTable {
+        let smoothstep = |x: f64| x * x * (3.0 - 2.0 * x);
+        let mut scale = [0.0; RES];
+        for (i, s) in scale.iter_mut().enumerate() {
+            *s = smoothstep(smoothstep(i as f64 / (RES - 1) as f64));
+        }
+        let mut coefficients = vec![[[[[0.0; 3]; RES]; RES]; RES]; 3];
+        // Fits converge from a nearby solution, so each run along `z` starts from
+        // a flat grey a little way up and walks outward from there
+        let start = RES / 5;
+        for (l, table) in coefficients.iter_mut().enumerate() {
+            for (j, row) in table.iter_mut().enumerate() {
+                let y = j as f64 / (RES - 1) as f64;
+                for (i, column) in row.iter_mut().enumerate() {
+                    let x = i as f64 / (RES - 1) as f64;
+                    let target = |z: f64| {
+                        let mut rgb = [0.0; 3];
+                        rgb[l] = z;
+                        rgb[(l + 1) % 3] = x * z;
+                        rgb[(l + 2) % 3] = y * z;
+                        rgb
+                    };
+                    let mut c = [0.0; 3];
+                    for (z, out) in scale[start..].iter().zip(&mut column[start..]) {
+                        c = RgbSigmoidPolynomial::fit(target(*z), c);
+                        *out = c;
+                    }
+                    c = column[start];
+                    for (z, out) in scale[..start].iter().zip(&mut column[..start]).rev() {
+                        c = RgbSigmoidPolynomial::fit(target(*z), c);
+                        *out = c;
+                    }
+                }
+            }
+        }
+        RgbToSpectrumTable {
+            scale,
+            coefficients,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Colour of a reflectance spectrum, integrated on the fitting grid.
+    fn rgb_of(poly: &RgbSigmoidPolynomial) -> [f64; 3] {
+        let mut rgb = [0.0; 3];
+        for &(lambda, w) in &tables().weights {
+            for c in 0..3 {
+                rgb[c] += w[c] * poly.eval(lambda);
+            }
+        }
+        rgb
+    }
+
+    #[test]
+    fn visible_wavelengths_pdf_integrates_to_one() {
+        let n = 4700;
+        let h = (LAMBDA_MAX - LAMBDA_MIN) / n as f64;
+        let total: f64 = (0..n)
+            .map(|i| visible_pdf(LAMBDA_MIN + (i as f64 + 0.5) * h) * h)
+            .sum();
+        assert!((total - 1.0).abs() < 1e-3, "{}", total);
+        let l = SampledWavelengths::sample_visible(0.3);
+        for i in 0..N_SAMPLES {
+            assert!((LAMBDA_MIN..=LAMBDA_MAX).contains(&l.lambda(i)));
+        }
+    }
+
+    #[test]
+    fn upsampled_colours_round_trip() {
+        for rgb in [
+            [0.8, 0.3, 0.1],
+            [0.1, 0.5, 0.9],
+            [0.2, 0.7, 0.25],
+            [0.5, 0.5, 0.5],
+            [0.95, 0.9, 0.02],
+        ] {
+            let poly = RgbSigmoidPolynomial::from_rgb(vector3::Color::new(rgb[0], rgb[1], rgb[2]));
+            let back = rgb_of(&poly);
+            for c in 0..3 {
+                assert!(
+                    (back[c] - rgb[c]).abs() < 0.02,
+                    "{:?} came back as {:?}",
+                    rgb,
+                    back
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sampled_spectra_average_to_their_colour() {
+        let target = vector3::Color::new(0.7, 0.4, 0.2);
+        let n = 20000;
+        let mut sum = vector3::Color::new(0.0, 0.0, 0.0);
+        for k in 0..n {
+            let lambda = SampledWavelengths::sample_visible((k as f64 + 0.5) / n as f64);
+            // Emission brighter than 1 goes through the unbounded path
+            let s = SampledSpectrum::from_rgb(target * 3.0, &lambda);
+            sum = sum + s.to_rgb(&lambda);
+        }
+        let mean = sum / n as f64 / 3.0;
+        assert!(
+            (mean - target).length() < 0.02,
+            "{} {} {}",
+            mean.x,
+            mean.y,
+            mean.z
+        );
+    }
+
+    #[test]
+    fn hero_wavelength_alone_stays_unbiased() {
+        let n = 20000;
+        let mut sum = vector3::Color::new(0.0, 0.0, 0.0);
+        for k in 0..n {
+            let mut lambda = SampledWavelengths::sample_visible((k as f64 + 0.5) / n as f64);
+            lambda.terminate_secondary();
+            assert!(lambda.secondary_terminated());
+            sum = sum + SampledSpectrum::constant(1.0).to_rgb(&lambda);
+        }
+        let mean = sum / n as f64;
+        assert!((mean - vector3::Color::new(1.0, 1.0, 1.0)).length() < 0.02);
+    }
+}
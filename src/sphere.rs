@@ -1,7 +1,10 @@
+use crate::aabb;
 use crate::hittable;
 use crate::material;
+use crate::onb;
 use crate::ray;
 use crate::vector3;
+use std::f64::consts::PI;
 use std::sync::{Arc, Mutex};
 #[derive(Clone)]
 pub struct Sphere {
@@ -17,6 +20,39 @@ impl Sphere {
             radius: r,
         }
     }
+
+    pub fn area(&self) -> f64 {
+        4.0 * PI * self.radius * self.radius
+    }
+
+    /// `p` is a point on the unit sphere centered at the origin.
+    /// u: angle around the Y axis from X=-1, v: angle from Y=-1 to Y=+1, both in [0, 1].
+    pub fn get_sphere_uv(p: vector3::Point) -> (f64, f64) {
+        let theta = (-p.y).acos();
+        let phi = (-p.z).atan2(p.x) + PI;
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    /// `1 - cos(theta_max)` for the cone a sphere of squared radius `r2` subtends at
+    /// squared distance `d2`, kept accurate for small, distant spheres.
+    fn one_minus_cos_max(d2: f64, r2: f64) -> f64 {
+        let sin2 = r2 / d2;
+        if sin2 < 1e-3 {
+            sin2 / 2.0 + sin2 * sin2 / 8.0
+        } else {
+            1.0 - (1.0 - sin2).sqrt()
+        }
+    }
+
+    /// Direction of increasing `u` at `p` on the unit sphere, or zero at the poles.
+    pub fn get_sphere_tangent(p: vector3::Point) -> vector3::Vec3 {
+        let t = vector3::Vec3::new(p.z, 0.0, -p.x);
+        if t.near_zero() {
+            t
+        } else {
+            t.unit_vector()
+        }
+    }
 }
 
 impl hittable::Hittable for Sphere {
@@ -43,12 +79,66 @@ impl hittable::Hittable for Sphere {
         let mut hit_record = hittable::HitRecord {
             p: r.at(root),
             t: root,
+            u: 0.0,
+            v: 0.0,
+            tangent: vector3::Vec3::new(0.0, 0.0, 0.0),
             normal: vector3::Vec3::new(0.0, 0.0, 0.0),
             front_face: false,
             material: self.material.clone(),
         };
         let outward_normal: vector3::Vec3 = (hit_record.p - self.center) / self.radius;
         hit_record.set_face_normal(r, &outward_normal);
+        (hit_record.u, hit_record.v) = Sphere::get_sphere_uv(outward_normal);
+        hit_record.tangent = Sphere::get_sphere_tangent(outward_normal);
         Some(hit_record)
     }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        let r = vector3::Vec3::new(self.radius, self.radius, self.radius);
+        Some(aabb::Aabb::new(self.center - r, self.center + r))
+    }
+
+    fn sample(&self, origin: vector3::Point, u: [f64; 2]) -> Option<(hittable::HitRecord, f64)> {
+        let to_center = self.center - origin;
+        let d2 = to_center.length_squared();
+        let r2 = self.radius * self.radius;
+        if d2 <= r2 {
+            // Inside: pick uniformly over the area
+            let z = 1.0 - 2.0 * u[0];
+            let s = (1.0 - z * z).max(0.0).sqrt();
+            let phi = 2.0 * PI * u[1];
+            let p = self.center + vector3::Vec3::new(s * phi.cos(), s * phi.sin(), z) * self.radius;
+            let rec = self.hit(&ray::Ray::new(origin, p - origin), 1e-6, f64::INFINITY)?;
+            let pdf = hittable::solid_angle_pdf(origin, &rec, 4.0 * PI * r2);
+            return Some((rec, pdf));
+        }
+
+        // Outside: pick uniformly within the cone the sphere subtends
+        let one_minus_cos_max = Sphere::one_minus_cos_max(d2, r2);
+        let cos_theta = 1.0 - u[0] * one_minus_cos_max;
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * u[1];
+        let frame = onb::Onb::build_from_w(to_center);
+        let dir = frame.local(vector3::Vec3::new(
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+        ));
+        let rec = self.hit(&ray::Ray::new(origin, dir), 1e-6, f64::INFINITY)?;
+        Some((rec, 1.0 / (2.0 * PI * one_minus_cos_max)))
+    }
+
+    fn pdf_value(&self, origin: vector3::Point, dir: vector3::Vec3) -> f64 {
+        let rec = match self.hit(&ray::Ray::new(origin, dir), 1e-6, f64::INFINITY) {
+            Some(rec) => rec,
+            None => return 0.0,
+        };
+        let d2 = (self.center - origin).length_squared();
+        let r2 = self.radius * self.radius;
+        if d2 <= r2 {
+            hittable::solid_angle_pdf(origin, &rec, 4.0 * PI * r2)
+        } else {
+            1.0 / (2.0 * PI * Sphere::one_minus_cos_max(d2, r2))
+        }
+    }
 }
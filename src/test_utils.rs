@@ -0,0 +1,15 @@
+// Fixtures shared by the unit tests
+use crate::material;
+use crate::vector3;
+use std::sync::{Arc, Mutex};
+
+/// Plain grey diffuse material, for tests that only care about geometry.
+pub fn mat() -> Arc<Mutex<material::Material>> {
+    Arc::new(Mutex::new(material::Material::Lambertian(
+        material::Lambertian::new(vector3::Color::new(0.5, 0.5, 0.5)),
+    )))
+}
+
+pub fn assert_vec(v: vector3::Vec3, x: f64, y: f64, z: f64) {
+    assert!((v.x - x).abs() < 1e-6 && (v.y - y).abs() < 1e-6 && (v.z - z).abs() < 1e-6);
+}
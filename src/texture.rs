@@ -0,0 +1,441 @@
+use crate::noise;
+use crate::vector3;
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+
+pub trait TextureTrait {
+    fn value(&self, u: f64, v: f64, p: vector3::Point) -> vector3::Color;
+}
+
+#[derive(Clone)]
+pub enum Texture {
+    SolidColor(SolidColor),
+    Checker(CheckerTexture),
+    UvChecker(UvCheckerTexture),
+    Image(ImageTexture),
+    Noise(NoiseTexture),
+    Marble(MarbleTexture),
+    Wood(WoodTexture),
+    Granite(GraniteTexture),
+}
+
+impl TextureTrait for Texture {
+    fn value(&self, u: f64, v: f64, p: vector3::Point) -> vector3::Color {
+        match self {
+            Texture::SolidColor(x) => x.value(u, v, p),
+            Texture::Checker(x) => x.value(u, v, p),
+            Texture::UvChecker(x) => x.value(u, v, p),
+            Texture::Image(x) => x.value(u, v, p),
+            Texture::Noise(x) => x.value(u, v, p),
+            Texture::Marble(x) => x.value(u, v, p),
+            Texture::Wood(x) => x.value(u, v, p),
+            Texture::Granite(x) => x.value(u, v, p),
+        }
+    }
+}
+
+impl Texture {
+    /// Mean value over the unit square of texture space. `None` for textures
+    /// that vary with position in the world instead.
+    pub fn average(&self) -> Option<vector3::Color> {
+        match self {
+            Texture::SolidColor(x) => Some(x.color_value),
+            Texture::UvChecker(x) => Some((x.even.average()? + x.odd.average()?) * 0.5),
+            Texture::Image(x) => Some(x.average()),
+            _ => None,
+        }
+    }
+}
+
+/// Shorthand for a shared constant texture.
+pub fn solid(c: vector3::Color) -> Arc<Texture> {
+    Arc::new(Texture::SolidColor(SolidColor::new(c)))
+}
+
+/// Constant texture for scalar parameters, which are read from the first channel.
+pub fn scalar(x: f64) -> Arc<Texture> {
+    solid(vector3::Color::new(x, x, x))
+}
+
+#[derive(Clone)]
+pub struct SolidColor {
+    color_value: vector3::Color,
+}
+
+impl SolidColor {
+    pub fn new(c: vector3::Color) -> SolidColor {
+        SolidColor { color_value: c }
+    }
+}
+
+impl TextureTrait for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: vector3::Point) -> vector3::Color {
+        self.color_value
+    }
+}
+
+/// Checkerboard of 3D cells of side `scale` in world space.
+#[derive(Clone)]
+pub struct CheckerTexture {
+    scale: f64,
+    even: Arc<Texture>,
+    odd: Arc<Texture>,
+}
+
+impl CheckerTexture {
+    pub fn new(scale: f64, even: Arc<Texture>, odd: Arc<Texture>) -> CheckerTexture {
+        CheckerTexture { scale, even, odd }
+    }
+}
+
+impl TextureTrait for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: vector3::Point) -> vector3::Color {
+        let x = (p.x / self.scale).floor() as i64;
+        let y = (p.y / self.scale).floor() as i64;
+        let z = (p.z / self.scale).floor() as i64;
+        if (x + y + z).rem_euclid(2) == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+/// Checkerboard in texture space with `columns` by `rows` squares across [0, 1]^2.
+#[derive(Clone)]
+pub struct UvCheckerTexture {
+    columns: f64,
+    rows: f64,
+    even: Arc<Texture>,
+    odd: Arc<Texture>,
+}
+
+impl UvCheckerTexture {
+    pub fn new(columns: f64, rows: f64, even: Arc<Texture>, odd: Arc<Texture>) -> UvCheckerTexture {
+        UvCheckerTexture {
+            columns,
+            rows,
+            even,
+            odd,
+        }
+    }
+}
+
+impl TextureTrait for UvCheckerTexture {
+    fn value(&self, u: f64, v: f64, p: vector3::Point) -> vector3::Color {
+        let i = (u * self.columns).floor() as i64;
+        let j = (v * self.rows).floor() as i64;
+        if (i + j).rem_euclid(2) == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+/// What to do with texture coordinates outside [0, 1].
+#[derive(Copy, Clone)]
+pub enum WrapMode {
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl WrapMode {
+    fn wrap(self, i: i64, n: usize) -> usize {
+        let n = n as i64;
+        let i = match self {
+            WrapMode::Repeat => i.rem_euclid(n),
+            WrapMode::Clamp => i.clamp(0, n - 1),
+            WrapMode::Mirror => {
+                let m = i.rem_euclid(2 * n);
+                if m < n {
+                    m
+                } else {
+                    2 * n - 1 - m
+                }
+            }
+        };
+        i as usize
+    }
+}
+
+/// Image lookup with bilinear filtering. `v = 0` is the bottom row of the image.
+#[derive(Clone)]
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    data: Arc<Vec<vector3::Color>>,
+    wrap: WrapMode,
+}
+
+impl ImageTexture {
+    /// `data` holds `width * height` texels, row by row from the top.
+    pub fn new(
+        width: usize,
+        height: usize,
+        data: Vec<vector3::Color>,
+        wrap: WrapMode,
+    ) -> ImageTexture {
+        ImageTexture {
+            width,
+            height,
+            data: Arc::new(data),
+            wrap,
+        }
+    }
+
+    /// Loads a Radiance `.hdr` file as linear radiance, or any other format the
+    /// `image` crate reads (PNG, JPEG, ...) with channels scaled to [0, 1].
+    pub fn load(path: &str, wrap: WrapMode) -> image::ImageResult<ImageTexture> {
+        if path.to_lowercase().ends_with(".hdr") {
+            let decoder = image::hdr::HDRDecoder::new(BufReader::new(File::open(path)?))?;
+            let meta = decoder.metadata();
+            let data = decoder
+                .read_image_hdr()?
+                .iter()
+                .map(|p| vector3::Color::new(p.data[0] as f64, p.data[1] as f64, p.data[2] as f64))
+                .collect();
+            Ok(ImageTexture::new(
+                meta.width as usize,
+                meta.height as usize,
+                data,
+                wrap,
+            ))
+        } else {
+            let img = image::open(path)?.to_rgb();
+            let (w, h) = img.dimensions();
+            let data = img
+                .pixels()
+                .map(|p| {
+                    vector3::Color::new(
+                        p.data[0] as f64 / 255.0,
+                        p.data[1] as f64 / 255.0,
+                        p.data[2] as f64 / 255.0,
+                    )
+                })
+                .collect();
+            Ok(ImageTexture::new(w as usize, h as usize, data, wrap))
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Mean of all the texels.
+    pub fn average(&self) -> vector3::Color {
+        if self.data.is_empty() {
+            return vector3::Color::new(0.0, 1.0, 1.0);
+        }
+        let sum = self
+            .data
+            .iter()
+            .fold(vector3::Color::new(0.0, 0.0, 0.0), |acc, &c| acc + c);
+        sum / self.data.len() as f64
+    }
+
+    /// Texel at column `i`, row `j` (from the top), after wrapping.
+    pub fn texel(&self, i: i64, j: i64) -> vector3::Color {
+        let i = self.wrap.wrap(i, self.width);
+        let j = self.wrap.wrap(j, self.height);
+        self.data[i + j * self.width]
+    }
+}
+
+impl TextureTrait for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: vector3::Point) -> vector3::Color {
+        if self.data.is_empty() {
+            // Solid cyan as a debugging aid
+            return vector3::Color::new(0.0, 1.0, 1.0);
+        }
+        // Texel centers sit at half-integer coordinates
+        let x = u * self.width as f64 - 0.5;
+        let y = (1.0 - v) * self.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (i, j) = (x0 as i64, y0 as i64);
+
+        let top = self.texel(i, j) * (1.0 - fx) + self.texel(i + 1, j) * fx;
+        let bottom = self.texel(i, j + 1) * (1.0 - fx) + self.texel(i + 1, j + 1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+}
+
+fn mix(a: vector3::Color, b: vector3::Color, t: f64) -> vector3::Color {
+    a * (1.0 - t) + b * t
+}
+
+/// Grayscale fractal noise, `scale` cycles per world unit.
+#[derive(Clone)]
+pub struct NoiseTexture {
+    noise: noise::Perlin,
+    scale: f64,
+}
+
+impl NoiseTexture {
+    pub fn new(scale: f64, seed: u64) -> NoiseTexture {
+        NoiseTexture {
+            noise: noise::Perlin::new(seed),
+            scale,
+        }
+    }
+}
+
+impl TextureTrait for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, p: vector3::Point) -> vector3::Color {
+        let n = 0.5 * (1.0 + self.noise.fbm(p * self.scale, 7, 2.0, 0.5));
+        vector3::Color::new(1.0, 1.0, 1.0) * n.clamp(0.0, 1.0)
+    }
+}
+
+/// Veins of `vein` color running through `base`, bands along z distorted by turbulence.
+#[derive(Clone)]
+pub struct MarbleTexture {
+    noise: noise::Perlin,
+    scale: f64,
+    base: vector3::Color,
+    vein: vector3::Color,
+}
+
+impl MarbleTexture {
+    pub fn new(scale: f64, base: vector3::Color, vein: vector3::Color, seed: u64) -> MarbleTexture {
+        MarbleTexture {
+            noise: noise::Perlin::new(seed),
+            scale,
+            base,
+            vein,
+        }
+    }
+}
+
+impl TextureTrait for MarbleTexture {
+    fn value(&self, _u: f64, _v: f64, p: vector3::Point) -> vector3::Color {
+        let s = 0.5 * (1.0 + (self.scale * p.z + 10.0 * self.noise.turb(p, 7)).sin());
+        // Sharpen so veins are thin lines rather than soft stripes
+        mix(self.vein, self.base, s.powf(0.25))
+    }
+}
+
+/// Growth rings around the y axis, `rings` per world unit, wobbled by noise.
+#[derive(Clone)]
+pub struct WoodTexture {
+    noise: noise::Perlin,
+    rings: f64,
+    light: vector3::Color,
+    dark: vector3::Color,
+}
+
+impl WoodTexture {
+    pub fn new(rings: f64, light: vector3::Color, dark: vector3::Color, seed: u64) -> WoodTexture {
+        WoodTexture {
+            noise: noise::Perlin::new(seed),
+            rings,
+            light,
+            dark,
+        }
+    }
+}
+
+impl TextureTrait for WoodTexture {
+    fn value(&self, _u: f64, _v: f64, p: vector3::Point) -> vector3::Color {
+        let grain = vector3::Point::new(p.x * 4.0, p.y * 0.5, p.z * 4.0);
+        let r =
+            (p.x * p.x + p.z * p.z).sqrt() * self.rings + 0.6 * self.noise.fbm(grain, 4, 2.0, 0.5);
+        let ring = r - r.floor();
+        // Early wood fades slowly into a sharp band of late wood
+        let t = ring * ring * (3.0 - 2.0 * ring);
+        mix(self.light, self.dark, t)
+    }
+}
+
+/// Crystals of varying brightness between `dark` and `light`, with dark grain
+/// boundaries, mottled by fractal noise. `scale` is crystals per world unit.
+#[derive(Clone)]
+pub struct GraniteTexture {
+    noise: noise::Perlin,
+    cells: noise::Worley,
+    scale: f64,
+    dark: vector3::Color,
+    light: vector3::Color,
+}
+
+impl GraniteTexture {
+    pub fn new(
+        scale: f64,
+        dark: vector3::Color,
+        light: vector3::Color,
+        seed: u64,
+    ) -> GraniteTexture {
+        GraniteTexture {
+            noise: noise::Perlin::new(seed),
+            cells: noise::Worley::new(seed as u32),
+            scale,
+            dark,
+            light,
+        }
+    }
+}
+
+impl TextureTrait for GraniteTexture {
+    fn value(&self, _u: f64, _v: f64, p: vector3::Point) -> vector3::Color {
+        let q = p * self.scale;
+        let cell = self.cells.sample(q);
+        let crystal = noise::unit(cell.id);
+        let boundary = ((cell.f2 - cell.f1) * 8.0).clamp(0.0, 1.0);
+        let mottle = 0.5 * (1.0 + self.noise.fbm(q * 0.5, 5, 2.0, 0.5));
+        let t = (0.6 * crystal + 0.4 * mottle) * (0.3 + 0.7 * boundary);
+        mix(self.dark, self.light, t.clamp(0.0, 1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(v: f64) -> Arc<Texture> {
+        Arc::new(Texture::SolidColor(SolidColor::new(vector3::Color::new(
+            v, v, v,
+        ))))
+    }
+
+    #[test]
+    fn checker_alternates() {
+        let checker = CheckerTexture::new(1.0, solid(0.0), solid(1.0));
+        let p = |x, y, z| vector3::Point::new(x, y, z);
+        assert_eq!(checker.value(0.0, 0.0, p(0.5, 0.5, 0.5)).x, 0.0);
+        assert_eq!(checker.value(0.0, 0.0, p(1.5, 0.5, 0.5)).x, 1.0);
+        assert_eq!(checker.value(0.0, 0.0, p(-0.5, 0.5, 0.5)).x, 1.0);
+
+        let uv = UvCheckerTexture::new(2.0, 2.0, solid(0.0), solid(1.0));
+        assert_eq!(uv.value(0.25, 0.25, p(0.0, 0.0, 0.0)).x, 0.0);
+        assert_eq!(uv.value(0.75, 0.25, p(0.0, 0.0, 0.0)).x, 1.0);
+    }
+
+    #[test]
+    fn image_filtering_and_wrapping() {
+        // 2 x 1 image: black on the left, white on the right.
+        let data = vec![
+            vector3::Color::new(0.0, 0.0, 0.0),
+            vector3::Color::new(1.0, 1.0, 1.0),
+        ];
+        let p = vector3::Point::new(0.0, 0.0, 0.0);
+
+        let clamp = ImageTexture::new(2, 1, data.clone(), WrapMode::Clamp);
+        assert!((clamp.value(0.5, 0.5, p).x - 0.5).abs() < 1e-9);
+        assert!((clamp.value(0.0, 0.5, p).x - 0.0).abs() < 1e-9);
+        assert!((clamp.value(1.0, 0.5, p).x - 1.0).abs() < 1e-9);
+
+        // Repeating, the left edge blends with the right-hand texel of the next tile.
+        let repeat = ImageTexture::new(2, 1, data.clone(), WrapMode::Repeat);
+        assert!((repeat.value(0.0, 0.5, p).x - 0.5).abs() < 1e-9);
+
+        let mirror = ImageTexture::new(2, 1, data, WrapMode::Mirror);
+        assert!((mirror.value(1.25, 0.5, p).x - 1.0).abs() < 1e-9);
+    }
+}
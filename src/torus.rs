@@ -0,0 +1,157 @@
+use crate::aabb;
+use crate::hittable;
+use crate::material;
+use crate::onb;
+use crate::ray;
+use crate::roots;
+use crate::utils;
+use crate::vector3;
+use std::f64::consts::PI;
+use std::sync::{Arc, Mutex};
+
+/// Torus around `axis`: a tube of `minor_radius` swept along a circle of `major_radius`.
+#[derive(Clone)]
+pub struct Torus {
+    center: vector3::Point,
+    frame: onb::Onb,
+    major_radius: f64,
+    minor_radius: f64,
+    material: Arc<Mutex<material::Material>>,
+}
+
+impl Torus {
+    pub fn new(
+        center: vector3::Point,
+        axis: vector3::Vec3,
+        major_radius: f64,
+        minor_radius: f64,
+        mat: Arc<Mutex<material::Material>>,
+    ) -> Torus {
+        Torus {
+            center,
+            frame: onb::Onb::build_from_w(axis),
+            major_radius,
+            minor_radius,
+            material: mat,
+        }
+    }
+}
+
+impl hittable::Hittable for Torus {
+    fn hit(&self, r: &ray::Ray, t_min: f64, t_max: f64) -> Option<hittable::HitRecord> {
+        let big_r2 = self.major_radius * self.major_radius;
+        let small_r2 = self.minor_radius * self.minor_radius;
+        let bound = self.major_radius + self.minor_radius;
+
+        let o = self.frame.to_local(r.origin - self.center);
+        let d = self.frame.to_local(r.dir);
+        let len = d.length();
+        let dn = d / len;
+
+        // Solve from the point of closest approach to the center, which keeps the
+        // quartic's coefficients small and the roots well conditioned.
+        let t0 = -vector3::dot(o, dn);
+        let oc = o + dn * t0;
+        if oc.length_squared() > bound * bound {
+            return None;
+        }
+
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + y^2) with p = oc + s * dn
+        let m = oc.length_squared();
+        let n = vector3::dot(oc, dn);
+        let k = m + big_r2 - small_r2;
+        let coeffs = [
+            k * k - 4.0 * big_r2 * (oc.x * oc.x + oc.y * oc.y),
+            4.0 * n * k - 8.0 * big_r2 * (oc.x * dn.x + oc.y * dn.y),
+            4.0 * n * n + 2.0 * k - 4.0 * big_r2 * (dn.x * dn.x + dn.y * dn.y),
+            4.0 * n,
+            1.0,
+        ];
+
+        let root = roots::solve_quartic(coeffs)
+            .into_iter()
+            .map(|s| (t0 + s) / len)
+            .filter(|t| *t >= t_min && *t <= t_max)
+            .fold(None, |best: Option<f64>, t| match best {
+                Some(b) if b <= t => Some(b),
+                _ => Some(t),
+            })?;
+
+        // The outward normal points away from the nearest point on the major circle.
+        let p = o + d * root;
+        let rho = (p.x * p.x + p.y * p.y).sqrt();
+        let ring = if rho > 0.0 {
+            vector3::Vec3::new(p.x, p.y, 0.0) * (self.major_radius / rho)
+        } else {
+            vector3::Vec3::new(self.major_radius, 0.0, 0.0)
+        };
+        let local_normal = (p - ring) / self.minor_radius;
+
+        let mut hit_record = hittable::HitRecord {
+            p: r.at(root),
+            t: root,
+            u: utils::azimuth(p.x, p.y),
+            v: (p.z.atan2(rho - self.major_radius) + PI) / (2.0 * PI),
+            tangent: self.frame.local(utils::azimuth_tangent(p)),
+            normal: vector3::Vec3::new(0.0, 0.0, 0.0),
+            front_face: false,
+            material: self.material.clone(),
+        };
+        let outward_normal = self.frame.local(local_normal);
+        hit_record.set_face_normal(r, &outward_normal);
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        Some(aabb::Aabb::disk(self.center, self.frame.w, self.major_radius).pad(self.minor_radius))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittable;
+    use crate::test_utils::{assert_vec, mat};
+
+    fn torus() -> Torus {
+        Torus::new(
+            vector3::Point::new(0.0, 0.0, 0.0),
+            vector3::Vec3::new(0.0, 1.0, 0.0),
+            2.0,
+            0.5,
+            mat(),
+        )
+    }
+
+    #[test]
+    fn hits_outer_rim() {
+        let r = ray::Ray::new(
+            vector3::Point::new(0.0, 0.0, 10.0),
+            vector3::Vec3::new(0.0, 0.0, -1.0),
+        );
+        let rec = torus().hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 7.5).abs() < 1e-6);
+        assert_vec(rec.normal, 0.0, 0.0, 1.0);
+    }
+
+    #[test]
+    fn hits_top_of_tube() {
+        // Unnormalized direction: t is still measured in units of the ray's direction.
+        let r = ray::Ray::new(
+            vector3::Point::new(2.0, 5.0, 0.0),
+            vector3::Vec3::new(0.0, -2.0, 0.0),
+        );
+        let rec = torus().hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 2.25).abs() < 1e-6);
+        assert_vec(rec.normal, 0.0, 1.0, 0.0);
+    }
+
+    #[test]
+    fn misses_through_hole() {
+        let r = ray::Ray::new(
+            vector3::Point::new(0.0, 5.0, 0.0),
+            vector3::Vec3::new(0.0, -1.0, 0.0),
+        );
+        assert!(torus().hit(&r, 0.001, f64::INFINITY).is_none());
+    }
+}
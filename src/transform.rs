@@ -0,0 +1,158 @@
+use crate::aabb;
+use crate::hittable;
+use crate::onb;
+use crate::ray;
+use crate::vector3;
+
+/// Placement of an object: scaled uniformly about its origin, then rotated about
+/// x, y and z in that order, then moved.
+#[derive(Copy, Clone)]
+pub struct Transform {
+    pub translation: vector3::Vec3,
+    /// Degrees about each axis
+    pub rotation: vector3::Vec3,
+    pub scale: f64,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::new(
+            vector3::Vec3::new(0.0, 0.0, 0.0),
+            vector3::Vec3::new(0.0, 0.0, 0.0),
+            1.0,
+        )
+    }
+}
+
+impl Transform {
+    pub fn new(translation: vector3::Vec3, rotation: vector3::Vec3, scale: f64) -> Transform {
+        Transform {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    /// Where the object's axes end up, as the columns of the rotation.
+    fn basis(&self) -> onb::Onb {
+        let rotate = |a: vector3::Vec3| {
+            let (sx, cx) = self.rotation.x.to_radians().sin_cos();
+            let (sy, cy) = self.rotation.y.to_radians().sin_cos();
+            let (sz, cz) = self.rotation.z.to_radians().sin_cos();
+            let a = vector3::Vec3::new(a.x, cx * a.y - sx * a.z, sx * a.y + cx * a.z);
+            let a = vector3::Vec3::new(cy * a.x + sy * a.z, a.y, -sy * a.x + cy * a.z);
+            vector3::Vec3::new(cz * a.x - sz * a.y, sz * a.x + cz * a.y, a.z)
+        };
+        onb::Onb {
+            u: rotate(vector3::Vec3::new(1.0, 0.0, 0.0)),
+            v: rotate(vector3::Vec3::new(0.0, 1.0, 0.0)),
+            w: rotate(vector3::Vec3::new(0.0, 0.0, 1.0)),
+        }
+    }
+}
+
+/// An object drawn with a `Transform` applied. Rays are taken into the object's
+/// own space, so the object itself never moves and can be repositioned cheaply.
+#[derive(Clone)]
+pub struct Instance {
+    object: Box<hittable::HittableObj>,
+    transform: Transform,
+    basis: onb::Onb,
+}
+
+impl Instance {
+    pub fn new(object: hittable::HittableObj, transform: Transform) -> Instance {
+        Instance {
+            object: Box::new(object),
+            basis: transform.basis(),
+            transform,
+        }
+    }
+
+    pub fn transform(&self) -> Transform {
+        self.transform
+    }
+
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.basis = transform.basis();
+        self.transform = transform;
+    }
+
+    fn to_world(&self, p: vector3::Point) -> vector3::Point {
+        self.basis.local(p) * self.transform.scale + self.transform.translation
+    }
+}
+
+impl hittable::Hittable for Instance {
+    fn hit(&self, r: &ray::Ray, t_min: f64, t_max: f64) -> Option<hittable::HitRecord> {
+        // Scaling the direction along with the origin keeps `t` the same in both spaces
+        let s = self.transform.scale;
+        let local = ray::Ray::new(
+            self.basis.to_local(r.origin - self.transform.translation) / s,
+            self.basis.to_local(r.dir) / s,
+        );
+        let mut rec = self.object.hit(&local, t_min, t_max)?;
+        rec.p = self.to_world(rec.p);
+        rec.normal = self.basis.local(rec.normal);
+        rec.tangent = self.basis.local(rec.tangent);
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        let b = self.object.bounding_box()?;
+        let mut corners = (0..8).map(|i| {
+            self.to_world(vector3::Point::new(
+                if i & 1 == 0 { b.minimum.x } else { b.maximum.x },
+                if i & 2 == 0 { b.minimum.y } else { b.maximum.y },
+                if i & 4 == 0 { b.minimum.z } else { b.maximum.z },
+            ))
+        });
+        let first = corners.next()?;
+        let corner = aabb::Aabb::new(first, first);
+        Some(corners.fold(corner, |acc, p| {
+            aabb::surrounding_box(acc, aabb::Aabb::new(p, p))
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittable;
+    use crate::material;
+    use crate::sphere;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn instance_moves_rotates_and_scales() {
+        let mat = Arc::new(Mutex::new(material::Material::Lambertian(
+            material::Lambertian::new(vector3::Color::new(0.5, 0.5, 0.5)),
+        )));
+        let ball = hittable::HittableObj::Sphere(sphere::Sphere::new(
+            vector3::Point::new(1.0, 0.0, 0.0),
+            0.5,
+            mat,
+        ));
+        // Quarter turn about y takes +x to -z, then doubled and lifted
+        let instance = Instance::new(
+            ball,
+            Transform::new(
+                vector3::Vec3::new(0.0, 3.0, 0.0),
+                vector3::Vec3::new(0.0, 90.0, 0.0),
+                2.0,
+            ),
+        );
+        let r = ray::Ray::new(
+            vector3::Point::new(0.0, 3.0, 5.0),
+            vector3::Vec3::new(0.0, 0.0, -1.0),
+        );
+        let rec = instance.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 6.0).abs() < 1e-9);
+        assert!((rec.p - vector3::Point::new(0.0, 3.0, -1.0)).length() < 1e-9);
+        assert!((rec.normal - vector3::Vec3::new(0.0, 0.0, 1.0)).length() < 1e-9);
+
+        let b = instance.bounding_box().unwrap();
+        assert!((b.minimum - vector3::Point::new(-1.0, 2.0, -3.0)).length() < 1e-9);
+        assert!((b.maximum - vector3::Point::new(1.0, 4.0, -1.0)).length() < 1e-9);
+    }
+}
@@ -0,0 +1,156 @@
+use crate::aabb;
+use crate::hittable;
+use crate::material;
+use crate::ray;
+use crate::vector3;
+use std::sync::{Arc, Mutex};
+
+/// Triangle with corners `a`, `b` and `c`, facing `cross(b - a, c - a)`.
+/// `u` and `v` are the barycentric weights of `b` and `c`.
+#[derive(Clone)]
+pub struct Triangle {
+    a: vector3::Point,
+    e1: vector3::Vec3,
+    e2: vector3::Vec3,
+    normal: vector3::Vec3,
+    area: f64,
+    material: Arc<Mutex<material::Material>>,
+}
+
+impl Triangle {
+    pub fn new(
+        a: vector3::Point,
+        b: vector3::Point,
+        c: vector3::Point,
+        mat: Arc<Mutex<material::Material>>,
+    ) -> Triangle {
+        let (e1, e2) = (b - a, c - a);
+        let n = vector3::cross(e1, e2);
+        Triangle {
+            a,
+            e1,
+            e2,
+            normal: n.unit_vector(),
+            area: 0.5 * n.length(),
+            material: mat,
+        }
+    }
+
+    pub fn normal(&self) -> vector3::Vec3 {
+        self.normal
+    }
+
+    pub fn area(&self) -> f64 {
+        self.area
+    }
+}
+
+impl hittable::Hittable for Triangle {
+    // Möller–Trumbore
+    fn hit(&self, r: &ray::Ray, t_min: f64, t_max: f64) -> Option<hittable::HitRecord> {
+        let pvec = vector3::cross(r.dir, self.e2);
+        let det = vector3::dot(self.e1, pvec);
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+        let tvec = r.origin - self.a;
+        let u = vector3::dot(tvec, pvec) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let qvec = vector3::cross(tvec, self.e1);
+        let v = vector3::dot(r.dir, qvec) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let root = vector3::dot(self.e2, qvec) * inv_det;
+        if root < t_min || t_max < root {
+            return None;
+        }
+
+        let mut hit_record = hittable::HitRecord {
+            p: r.at(root),
+            t: root,
+            u,
+            v,
+            tangent: self.e1.unit_vector(),
+            normal: vector3::Vec3::new(0.0, 0.0, 0.0),
+            front_face: false,
+            material: self.material.clone(),
+        };
+        hit_record.set_face_normal(r, &self.normal);
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        let (b, c) = (self.a + self.e1, self.a + self.e2);
+        let bounds = aabb::surrounding_box(aabb::Aabb::new(self.a, self.a), aabb::Aabb::new(b, b));
+        Some(aabb::surrounding_box(bounds, aabb::Aabb::new(c, c)).pad(1e-4))
+    }
+
+    fn sample(&self, origin: vector3::Point, u: [f64; 2]) -> Option<(hittable::HitRecord, f64)> {
+        // Uniform over the area
+        let su = u[0].sqrt();
+        let p = self.a + self.e1 * (u[1] * su) + self.e2 * (1.0 - su);
+        let rec = self.hit(&ray::Ray::new(origin, p - origin), 1e-6, f64::INFINITY)?;
+        let pdf = hittable::solid_angle_pdf(origin, &rec, self.area);
+        Some((rec, pdf))
+    }
+
+    fn pdf_value(&self, origin: vector3::Point, dir: vector3::Vec3) -> f64 {
+        match self.hit(&ray::Ray::new(origin, dir), 1e-6, f64::INFINITY) {
+            Some(rec) => hittable::solid_angle_pdf(origin, &rec, self.area),
+            None => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittable;
+
+    fn triangle() -> Triangle {
+        Triangle::new(
+            vector3::Point::new(0.0, 0.0, 0.0),
+            vector3::Point::new(1.0, 0.0, 0.0),
+            vector3::Point::new(0.0, 1.0, 0.0),
+            Arc::new(Mutex::new(material::Material::Lambertian(
+                material::Lambertian::new(vector3::Color::new(0.5, 0.5, 0.5)),
+            ))),
+        )
+    }
+
+    #[test]
+    fn triangle_hit_barycentrics_and_miss() {
+        let tri = triangle();
+        let r = ray::Ray::new(
+            vector3::Point::new(0.25, 0.5, -2.0),
+            vector3::Vec3::new(0.0, 0.0, 1.0),
+        );
+        let rec = tri.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-9);
+        assert!((rec.u - 0.25).abs() < 1e-9 && (rec.v - 0.5).abs() < 1e-9);
+        assert!(!rec.front_face);
+
+        let r = ray::Ray::new(
+            vector3::Point::new(0.6, 0.6, -2.0),
+            vector3::Vec3::new(0.0, 0.0, 1.0),
+        );
+        assert!(tri.hit(&r, 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn samples_stay_on_the_triangle() {
+        let tri = triangle();
+        let origin = vector3::Point::new(0.2, 0.2, 1.0);
+        for i in 0..20 {
+            let u = [(i as f64 + 0.5) / 20.0, (i as f64 * 0.37) % 1.0];
+            let (rec, pdf) = tri.sample(origin, u).unwrap();
+            assert!(rec.u >= 0.0 && rec.v >= 0.0 && rec.u + rec.v <= 1.0 + 1e-9);
+            let dir = rec.p - origin;
+            assert!((tri.pdf_value(origin, dir) - pdf).abs() < 1e-9 * pdf);
+        }
+    }
+}
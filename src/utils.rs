@@ -1,15 +1,27 @@
+use crate::vector3;
 use rand::Rng;
+use std::f64::consts::PI;
 pub fn clamp(p: f64) -> f64 {
-    if p < 0.0 {
-        return 0.0;
-    } else if p > 0.999 {
-        return 0.999;
-    } else {
-        return p;
-    }
+    p.clamp(0.0, 0.999)
 }
 
 pub fn random_double(min: f64, max: f64) -> f64 {
     let mut rng = rand::thread_rng();
     rng.gen_range(min..max)
 }
+
+/// Angle of `(x, y)` around the origin, mapped to `[0, 1]`. Used as the `u`
+/// texture coordinate on shapes of revolution.
+pub fn azimuth(x: f64, y: f64) -> f64 {
+    (y.atan2(x) + PI) / (2.0 * PI)
+}
+
+/// Unit direction in which `azimuth` increases at local point `p`, or zero on the axis.
+pub fn azimuth_tangent(p: vector3::Vec3) -> vector3::Vec3 {
+    let rho = (p.x * p.x + p.y * p.y).sqrt();
+    if rho > 0.0 {
+        vector3::Vec3::new(-p.y / rho, p.x / rho, 0.0)
+    } else {
+        vector3::Vec3::new(0.0, 0.0, 0.0)
+    }
+}
@@ -41,11 +41,11 @@ impl Vec3 {
     }
 
     pub fn random(min: f64, max: f64) -> Vec3 {
-        return Vec3::new(
+        Vec3::new(
             utils::random_double(min, max),
             utils::random_double(min, max),
             utils::random_double(min, max),
-        );
+        )
     }
 
     pub fn random_in_unit_sphere() -> Vec3 {
@@ -73,12 +73,30 @@ impl Vec3 {
         if dot(in_unit_sphere, normal) > 0.0
         // In the same hemisphere as the normal
         {
-            return in_unit_sphere;
+            in_unit_sphere
         } else {
-            return in_unit_sphere * (-1.0);
+            in_unit_sphere * (-1.0)
         }
     }
 
+    /// Cosine-weighted direction about +z.
+    pub fn random_cosine_direction() -> Vec3 {
+        Vec3::cosine_direction(
+            utils::random_double(0.0, 1.0),
+            utils::random_double(0.0, 1.0),
+        )
+    }
+
+    /// Cosine weighted direction around +z from two uniform numbers in [0, 1).
+    pub fn cosine_direction(u1: f64, u2: f64) -> Vec3 {
+        let phi = 2.0 * std::f64::consts::PI * u1;
+        Vec3::new(
+            phi.cos() * u2.sqrt(),
+            phi.sin() * u2.sqrt(),
+            (1.0 - u2).sqrt(),
+        )
+    }
+
     pub fn random_in_unit_disk() -> Vec3 {
         loop {
             let p = Vec3::new(
@@ -190,6 +208,18 @@ impl ops::Div<f64> for Vec3 {
     }
 }
 
+impl ops::Index<usize> for Vec3 {
+    type Output = f64;
+
+    fn index(&self, i: usize) -> &f64 {
+        match i {
+            0 => &self.x,
+            1 => &self.y,
+            _ => &self.z,
+        }
+    }
+}
+
 pub fn dot(a: Vec3, b: Vec3) -> f64 {
     a.x * b.x + a.y * b.y + a.z * b.z
 }
@@ -205,7 +235,7 @@ pub fn refract(uv: Vec3, n: Vec3, etai_over_etat: f64) -> Vec3 {
     }
     let r_out_perp = (uv + n * cos_theta) * etai_over_etat;
     let r_out_parallel = n * -((1.0 - r_out_perp.length_squared()).abs()).sqrt();
-    return r_out_perp + r_out_parallel;
+    r_out_perp + r_out_parallel
 }
 
 pub fn cross(a: Vec3, b: Vec3) -> Vec3 {